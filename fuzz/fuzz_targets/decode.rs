@@ -0,0 +1,21 @@
+#![no_main]
+//! Decodes arbitrary bytes as a stream of packets of every version
+
+use libfuzzer_sys::fuzz_target;
+use mqtt_packet_3_5::{DecoderOptions, PacketDecoder, ProtocolVersion};
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    for version in [
+        ProtocolVersion::V31,
+        ProtocolVersion::V311,
+        ProtocolVersion::V5,
+    ] {
+        for options in [DecoderOptions::strict(), DecoderOptions::lenient()] {
+            let mut decoder = PacketDecoder::from_stream(Cursor::new(data)).with_options(options);
+            while decoder.has_more() {
+                let _ = decoder.try_decode_packet(version);
+            }
+        }
+    }
+});
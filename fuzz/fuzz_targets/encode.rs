@@ -0,0 +1,17 @@
+#![no_main]
+//! Encodes arbitrary packets and decodes the result again. Packets that
+//! don't follow the spec may fail to encode, but nothing may panic
+
+use libfuzzer_sys::fuzz_target;
+use mqtt_packet_3_5::{MqttPacket, PacketDecoder, ProtocolVersion};
+use std::io::Cursor;
+
+fuzz_target!(|input: (MqttPacket, ProtocolVersion)| {
+    let (packet, version) = input;
+    if let Ok(buf) = packet.encode(version) {
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(buf));
+        while decoder.has_more() {
+            let _ = decoder.decode_packet(version);
+        }
+    }
+});
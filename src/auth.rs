@@ -5,8 +5,8 @@ use std::io;
 
 impl Packet for AuthPacket {
     /// This
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>> {
-        if protocol_version != 5 {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
+        if !protocol_version.has_auth() {
             return Err(format!(
                 "Invalid mqtt version for auth packet {}",
                 protocol_version
@@ -24,7 +24,7 @@ impl Packet for AuthPacket {
         writer.write_header(FixedHeader::for_type(PacketType::Auth));
 
         // Length
-        writer.write_variable_num(length as u32)?;
+        writer.write_length(length)?;
 
         // reason code
         writer.write_u8(self.reason_code.to_byte());
@@ -38,10 +38,10 @@ impl Packet for AuthPacket {
     fn decode<R: io::Read>(
         reader: &mut ByteReader<R>,
         _: FixedHeader,
-        _: u32,
-        protocol_version: u8,
+        length: u32,
+        protocol_version: ProtocolVersion,
     ) -> Res<AuthPacket> {
-        if protocol_version != 5 {
+        if !protocol_version.has_auth() {
             return Err("Not supported auth packet for this version MQTT".to_string());
         }
         // response code
@@ -49,11 +49,19 @@ impl Packet for AuthPacket {
             reason_code: AuthCode::Success,
             properties: None,
         };
+        if length == 0 && reader.options().is_lenient() {
+            reader.warn(DecodeWarning::EmptyPacket(PacketType::Auth));
+            return Ok(packet);
+        }
         packet.reason_code = AuthCode::from_byte(reader.read_u8()?)?;
-        // properies mqtt 5
-        packet.properties = match reader.read_properties()? {
-            None => None,
-            Some(props) => Some(AuthProperties::from_properties(props)?),
+        // properies mqtt 5, absent with a remaining length below 2
+        packet.properties = if length >= 2 {
+            match reader.read_properties()? {
+                None => None,
+                Some(props) => Some(AuthProperties::decode_properties(props)?),
+            }
+        } else {
+            None
         };
         Ok(packet)
     }
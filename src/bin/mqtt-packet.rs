@@ -0,0 +1,217 @@
+//! Command line tool to decode and encode MQTT packets
+//!
+//! ```sh
+//! echo "c0 00" | mqtt-packet decode
+//! echo '{"type":"pingreq"}' | mqtt-packet encode --hex
+//! mqtt-packet decode --raw --auto capture.bin
+//! mqtt-packet pcap --port 1883 capture.pcapng
+//! ```
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use mqtt_packet_3_5::*;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "mqtt-packet", about = "Decode and encode MQTT packets")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read packets in wire format and print them
+    Decode {
+        /// Read raw bytes instead of hex
+        #[arg(long)]
+        raw: bool,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        #[command(flatten)]
+        stream: Stream,
+    },
+    /// Read packets as JSON and write them in wire format
+    Encode {
+        /// Write hex, one line per packet, instead of raw bytes
+        #[arg(long)]
+        hex: bool,
+        #[command(flatten)]
+        stream: Stream,
+    },
+    /// Print the MQTT conversations of a pcap or pcapng capture
+    Pcap {
+        /// Port of the MQTT server
+        #[arg(long, default_value_t = 1883)]
+        port: u16,
+        /// Protocol level of connections whose CONNECT was not captured
+        #[arg(long, value_parser = parse_version, default_value = "5")]
+        version: ProtocolVersion,
+        /// `json` prints one document per connection
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        file: PathBuf,
+    },
+    /// Print the JSON Schema of the packet representation
+    Schema,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// one JSON document per line
+    Json,
+    /// pretty printed Rust structures
+    Text,
+}
+
+#[derive(Args)]
+struct Stream {
+    /// Protocol level of the stream: 3 (MQTT 3.1), 4 (MQTT 3.1.1) or 5 (MQTT 5)
+    #[arg(long, value_parser = parse_version, default_value = "5")]
+    version: ProtocolVersion,
+    /// Switch to the protocol version of each CONNECT in the stream
+    #[arg(long)]
+    auto: bool,
+    /// Input file, stdin if not given
+    file: Option<PathBuf>,
+}
+
+impl Stream {
+    fn read(&self) -> Res<Vec<u8>> {
+        let mut input = vec![];
+        match &self.file {
+            Some(path) => {
+                input = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            }
+            None => {
+                io::stdin()
+                    .read_to_end(&mut input)
+                    .map_err(|e| format!("stdin: {}", e))?;
+            }
+        }
+        Ok(input)
+    }
+
+    /// the version of packet and the packets following it
+    fn version_of(&self, version: ProtocolVersion, packet: &MqttPacket) -> ProtocolVersion {
+        match packet {
+            MqttPacket::Connect(connect) if self.auto => connect.protocol_version,
+            _ => version,
+        }
+    }
+}
+
+fn parse_version(s: &str) -> Res<ProtocolVersion> {
+    s.parse::<u8>()
+        .map_err(|e| e.to_string())
+        .and_then(ProtocolVersion::from_byte)
+}
+
+/// hex digits, whitespace between bytes is ignored
+fn parse_hex(input: &[u8]) -> Res<Vec<u8>> {
+    let digits: Vec<u8> = input
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err("Odd number of hex digits".to_string());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).map_err(|_| "Invalid hex digit".to_string())?;
+            u8::from_str_radix(pair, 16).map_err(|_| format!("Invalid hex byte {:?}", pair))
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    hex.join(" ")
+}
+
+fn decode(raw: bool, format: Format, stream: &Stream) -> Res<()> {
+    let input = stream.read()?;
+    let bytes = if raw { input } else { parse_hex(&input)? };
+    let mut decoder = PacketDecoder::from_stream(io::Cursor::new(bytes));
+    let mut version = stream.version;
+    let mut out = io::stdout().lock();
+    while decoder.has_more() {
+        let packet = decoder.decode_packet(version)?;
+        version = stream.version_of(version, &packet);
+        let res = match format {
+            Format::Json => {
+                let json = serde_json::to_string(&packet).map_err(|e| e.to_string())?;
+                writeln!(out, "{}", json)
+            }
+            Format::Text => writeln!(out, "{:#?}", packet),
+        };
+        res.map_err(|e| format!("stdout: {}", e))?;
+    }
+    Ok(())
+}
+
+fn encode(hex: bool, stream: &Stream) -> Res<()> {
+    let input = stream.read()?;
+    let mut version = stream.version;
+    let mut out = io::stdout().lock();
+    for packet in serde_json::Deserializer::from_slice(&input).into_iter::<MqttPacket>() {
+        let packet = packet.map_err(|e| format!("Invalid packet: {}", e))?;
+        version = stream.version_of(version, &packet);
+        let bytes = packet.encode(version)?;
+        let res = if hex {
+            writeln!(out, "{}", to_hex(&bytes))
+        } else {
+            out.write_all(&bytes)
+        };
+        res.map_err(|e| format!("stdout: {}", e))?;
+    }
+    Ok(())
+}
+
+fn pcap(port: u16, version: ProtocolVersion, format: Format, file: &PathBuf) -> Res<()> {
+    let capture = fs::read(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let mut out = io::stdout().lock();
+    for conversation in pcap::extract(&capture, port, version)? {
+        let res = match format {
+            Format::Json => {
+                let json = serde_json::to_string(&conversation).map_err(|e| e.to_string())?;
+                writeln!(out, "{}", json)
+            }
+            Format::Text => write!(out, "{}", conversation),
+        };
+        res.map_err(|e| format!("stdout: {}", e))?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let res = match &cli.command {
+        Command::Decode {
+            raw,
+            format,
+            stream,
+        } => decode(*raw, *format, stream),
+        Command::Encode { hex, stream } => encode(*hex, stream),
+        Command::Pcap {
+            port,
+            version,
+            format,
+            file,
+        } => pcap(*port, *version, *format, file),
+        Command::Schema => {
+            print!("{}", JSON_SCHEMA);
+            Ok(())
+        }
+    };
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mqtt-packet: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,13 +1,24 @@
 use crate::structure::*;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{self, BufRead, BufReader, Read};
 
 static VARBYTEINT_MASK: u32 = 0x7F;
 static VARBYTEINT_FIN_MASK: u32 = 0x80;
+// initial buffer size when reading a field of a declared length
+static READ_CHUNK: u32 = 4096;
 
 pub struct ByteReader<R: Read> {
     reader: BufReader<R>,
     curr_limit: Option<u32>,
     read_limits: Vec<u32>,
+    options: DecoderOptions,
+    /// warnings of the packet that is currently decoded
+    warnings: Vec<DecodeWarning>,
+    /// set when the current packet went over one of the DecoderLimits
+    limit_exceeded: Option<LimitExceeded>,
+    /// number of bytes read from the stream so far
+    position: u64,
+    /// position at which the current packet ends, once its length is known
+    packet_end: Option<u64>,
 }
 
 impl<R: Read> ByteReader<R> {
@@ -16,20 +27,97 @@ impl<R: Read> ByteReader<R> {
             reader,
             curr_limit: None,
             read_limits: vec![],
+            options: DecoderOptions::default(),
+            warnings: vec![],
+            limit_exceeded: None,
+            position: 0,
+            packet_end: None,
         }
     }
 
+    pub fn set_options(&mut self, options: DecoderOptions) {
+        self.options = options;
+    }
+
+    pub fn options(&self) -> DecoderOptions {
+        self.options
+    }
+
+    /// records something that was tolerated in lenient mode
+    pub fn warn(&mut self, warning: DecodeWarning) {
+        self.warnings.push(warning);
+    }
+
+    /// returns and clears the warnings recorded so far
+    pub fn take_warnings(&mut self) -> Vec<DecodeWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// records which limit was exceeded and returns the message for it
+    pub fn exceeded(&mut self, limit: LimitExceeded) -> String {
+        let msg = limit.to_string();
+        self.limit_exceeded = Some(limit);
+        msg
+    }
+
+    /// returns and clears the limit that the last packet went over
+    pub fn take_limit_exceeded(&mut self) -> Option<LimitExceeded> {
+        self.limit_exceeded.take()
+    }
+
+    /// fails if a SUBSCRIBE or UNSUBSCRIBE can't hold `count` topics
+    pub fn check_subscriptions(&mut self, count: usize) -> Res<()> {
+        let max = self.options.limits.max_subscriptions;
+        if count > max {
+            return Err(self.exceeded(LimitExceeded::Subscriptions { count, max }));
+        }
+        Ok(())
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// true if everything up to the end of the current packet has been read
+    pub fn at_packet_end(&self) -> bool {
+        self.packet_end == Some(self.position)
+    }
+
     pub fn read_header(&mut self) -> Result<(u32, FixedHeader), String> {
+        self.packet_end = None;
         // There is at least one byte in the buffer
         let first = self.read_u8()?;
-        let fixed = FixedHeader::from_byte(first);
+        let fixed = match FixedHeader::from_byte(first) {
+            Err(e) if self.options.is_lenient() => match FixedHeader::from_byte_lenient(first) {
+                Ok((fixed, _)) => {
+                    self.warn(DecodeWarning::ReservedHeaderBits {
+                        cmd: fixed.cmd,
+                        flags: first & 0x0F,
+                    });
+                    Ok(fixed)
+                }
+                Err(_) => Err(e),
+            },
+            fixed => fixed,
+        };
         // always read variable length to know how much we need to discard
         let length = self.read_variable_int();
+        if let Ok(len) = length {
+            let size = len + variable_int_size(len) + 1;
+            let max = self.options.limits.max_packet_size;
+            if size > max {
+                // don't even try to read what's left of it
+                return Err(self.exceeded(LimitExceeded::PacketSize { size, max }));
+            }
+        }
+        if let Ok(len) = length {
+            self.packet_end = Some(self.position + len as u64);
+        }
         let fixed = match fixed {
             Err(e) => {
                 if let Ok(len) = length {
-                    self.take(len);
-                    self.consume()?;
+                    self.take(len)?;
+                    self.discard()?;
                     self.reset_limit();
                 }
                 return Err(e);
@@ -40,23 +128,25 @@ impl<R: Read> ByteReader<R> {
         match length {
             Err(e) => Err(e),
             Ok(len) => {
-                self.take(len);
+                self.take(len)?;
                 Ok((len, fixed))
             }
         }
     }
 
     /// sets a limit of reading, has to be used together
-    /// with has_more() and reset_limit()
-    pub fn take(&mut self, len: u32) {
+    /// with has_more() and reset_limit(). A nested limit can never exceed
+    /// the outer one, a length beyond it means a malformed packet
+    pub fn take(&mut self, len: u32) -> Res<()> {
         if let Some(l) = self.curr_limit {
-            if len >= l {
-                return;
+            if len > l {
+                return Err(format!("Cannot take more than {}", l));
             }
             // push alread subtracted number
             self.read_limits.push(l - len);
         }
         self.curr_limit = Some(len);
+        Ok(())
     }
 
     // this function tracks the curr_limit and imitates behaviour
@@ -91,16 +181,54 @@ impl<R: Read> ByteReader<R> {
 
     pub fn read_len(&mut self, len: u32) -> Result<Vec<u8>, String> {
         self.ensure_limit(len)?;
-        let mut buf = vec![0; len as usize];
-        match self.reader.read_exact(&mut buf) {
-            Ok(_) => {
+        // the buffer only grows with the data that actually arrives, so a
+        // made up length can't make us allocate more than what was sent
+        let mut buf = Vec::with_capacity(len.min(READ_CHUNK) as usize);
+        let res = (&mut self.reader).take(len as u64).read_to_end(&mut buf);
+        // whatever was read is gone from the stream, even if it wasn't enough
+        self.position += buf.len() as u64;
+        match res {
+            Ok(n) if n == len as usize => {
                 self.limit(len);
                 Ok(buf)
             }
+            Ok(n) => Err(format!(
+                "Failed to read {} bytes. Reason: only {} available",
+                len, n
+            )),
             Err(e) => Err(format!("Failed to read {} bytes. Reason: {:?}", len, e)),
         }
     }
 
+    /// skips the rest of the current limit without keeping the data
+    pub fn discard(&mut self) -> Res<u64> {
+        if let Some(n) = self.curr_limit {
+            match io::copy(&mut (&mut self.reader).take(n as u64), &mut io::sink()) {
+                Ok(read) => {
+                    self.position += read;
+                    self.limit(read as u32);
+                    Ok(read)
+                }
+                Err(e) => Err(format!("Failed to discard {} bytes. Reason: {:?}", n, e)),
+            }
+        } else {
+            Err("Cannot discard if no limit specified".to_string())
+        }
+    }
+
+    /// skips everything up to the end of the current packet and drops all
+    /// limits, no matter how deeply nested the failed read was
+    pub fn skip_packet(&mut self) -> Res<()> {
+        if let Some(end) = self.packet_end {
+            self.read_limits.clear();
+            self.curr_limit = Some((end - self.position) as u32);
+            self.discard()?;
+        }
+        self.read_limits.clear();
+        self.curr_limit = None;
+        Ok(())
+    }
+
     pub fn read_u8(&mut self) -> Result<u8, String> {
         let d = self.read_len(1)?;
         if !d.is_empty() {
@@ -131,6 +259,30 @@ impl<R: Read> ByteReader<R> {
     // reads utf-8 encoded strings with 2 bytes indicating length of string
     pub fn read_utf8_string(&mut self) -> Res<String> {
         let len = self.read_u16()?;
+        self.read_utf8_bytes(len)
+    }
+
+    /// reads a topic name or topic filter, which has its own length limit
+    pub fn read_topic(&mut self) -> Res<String> {
+        let len = self.read_u16()?;
+        let max = self.options.limits.max_topic_length;
+        if len as usize > max {
+            return Err(self.exceeded(LimitExceeded::TopicLength {
+                length: len as usize,
+                max,
+            }));
+        }
+        self.read_utf8_bytes(len)
+    }
+
+    fn read_utf8_bytes(&mut self, len: u16) -> Res<String> {
+        let max = self.options.limits.max_string_length;
+        if len as usize > max {
+            return Err(self.exceeded(LimitExceeded::StringLength {
+                length: len as usize,
+                max,
+            }));
+        }
         match String::from_utf8(self.read_len(len as u32)?) {
             Ok(s) => Ok(s),
             Err(e) => Err(format!("Failed to read string: {:?}", e)),
@@ -155,13 +307,16 @@ impl<R: Read> ByteReader<R> {
     pub fn read_variable_int(&mut self) -> Result<u32, String> {
         let mut num = 0u32;
         let mut mult = 1;
-        for _ in 0..4 {
-            let next = self.read_len(1)?[0] as u32;
+        for i in 0..4 {
+            let next = self.read_u8()? as u32;
             num += mult * (next & VARBYTEINT_MASK);
             mult *= 0x80;
             if next & VARBYTEINT_FIN_MASK == 0 {
                 break;
             }
+            if i == 3 {
+                return Err("Invalid variable int, longer than 4 bytes".to_string());
+            }
         }
         if num > VARBYTEINT_MAX {
             return Err(format!("Invalid variable int {}", num));
@@ -185,12 +340,12 @@ impl<R: Read> ByteReader<R> {
     fn start_properties_decode(&mut self) -> Res<u32> {
         let length = self.read_variable_int()?;
         if length > 0 {
-            self.take(length);
+            self.take(length)?;
         }
         Ok(length)
     }
 
-    fn decode_property<'a, 'b>(&'a mut self) -> Result<(u8, PropType<'b>), String> {
+    fn decode_property<'b>(&mut self) -> Result<(u8, PropType<'b>), String> {
         let prop_type = self.read_u8()?;
         match prop_type {
             0x02 | 0x18 | 0x11 | 0x27 => Ok((prop_type, PropType::U32(self.read_u32()?))),
@@ -210,6 +365,14 @@ impl<R: Read> ByteReader<R> {
                 Ok((prop_type, PropType::Pair(name, value)))
             }
             0x24 => Ok((prop_type, PropType::U8(self.read_u8()?))),
+            _ if self.options.is_lenient() => {
+                // The length of an unknown property can't be known, so
+                // everything up to the end of the properties is taken as its
+                // value. Known properties after it are lost, their bytes are
+                // only kept in the DecodeWarning
+                let bytes = self.consume()?;
+                Ok((prop_type, PropType::Binary(bytes)))
+            }
             _ => Err(format!("Invalid property code: {}", prop_type)),
         }
     }
@@ -222,22 +385,38 @@ impl<R: Read> ByteReader<R> {
         }
     }
 
-    pub fn read_properties(&mut self) -> Res<Option<Vec<(u8, PropType)>>> {
+    pub fn read_properties(&mut self) -> Res<Option<Vec<(u8, PropType<'_>)>>> {
         let mut props = vec![];
         // zero length properties are also valid
         if self.start_properties_decode()? == 0 {
             return Ok(None);
         }
         let mut user_properties = UserProperties::new();
+        let mut user_property_bytes = 0;
         // let mut subscription_identifiers = vec![];
 
-        // TODO: return Err when key is repeated, but not allowed to
         while self.has_more() {
             let prop = self.decode_property()?;
             match prop {
                 (0x26, PropType::Pair(k, v)) => {
-                    let p = user_properties.entry(k).or_insert_with(Vec::new);
-                    p.push(v);
+                    let limits = self.options.limits;
+                    user_property_bytes += k.len() + v.len();
+                    if user_properties.len() >= limits.max_user_properties {
+                        return Err(self.exceeded(LimitExceeded::UserProperties {
+                            count: user_properties.len() + 1,
+                            max: limits.max_user_properties,
+                        }));
+                    }
+                    if user_property_bytes > limits.max_user_property_bytes {
+                        return Err(self.exceeded(LimitExceeded::UserPropertyBytes {
+                            bytes: user_property_bytes,
+                            max: limits.max_user_property_bytes,
+                        }));
+                    }
+                    user_properties.push(k, v)
+                }
+                (id, PropType::Binary(bytes)) if property_name(id).is_none() => {
+                    self.warn(DecodeWarning::UnknownProperty { id, bytes })
                 }
                 // parse variable byte int
                 // (0x0B, PropType::U32(next)) => subscription_identifiers.push(next),
@@ -248,10 +427,20 @@ impl<R: Read> ByteReader<R> {
         if !user_properties.is_empty() {
             props.push((0x26, PropType::Map(user_properties)));
         }
-        let _ = self.reset_limit();
+        self.reset_limit();
         if props.is_empty() {
             return Ok(None);
         }
         Ok(Some(props))
     }
 }
+
+/// number of bytes a variable byte integer takes
+fn variable_int_size(num: u32) -> u32 {
+    match num {
+        0..=127 => 1,
+        128..=16_383 => 2,
+        16_384..=2_097_151 => 3,
+        _ => 4,
+    }
+}
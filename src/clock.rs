@@ -0,0 +1,53 @@
+//! Source of the current time for the time dependent helpers, so that they
+//! can be driven by virtual time in tests
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// `Instant::now()`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Virtual time that only moves when it is advanced
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Cell<Instant>,
+}
+
+impl ManualClock {
+    /// starts at the current time
+    pub fn new() -> ManualClock {
+        ManualClock::starting_at(Instant::now())
+    }
+
+    pub fn starting_at(now: Instant) -> ManualClock {
+        ManualClock {
+            now: Cell::new(now),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
@@ -1,112 +1,165 @@
 use crate::byte_reader::ByteReader;
 use crate::mqtt_writer::MqttWriter;
+use crate::packet::MqttPacket;
 use crate::structure::*;
+use std::convert::TryFrom;
 use std::io;
 
-// convenience constructors for ConfirmationPacket
-impl ConfirmationPacket {
-    fn with_message_id(cmd: PacketType, message_id: u16) -> ConfirmationPacket {
-        ConfirmationPacket {
-            cmd,
-            message_id,
-            properties: None,
-            puback_reason_code: None,
-            pubcomp_reason_code: None,
-        }
+/// Encodes PUBACK, PUBREC, PUBREL and PUBCOMP which only differ in
+/// their packet type and the set of valid reason codes
+fn encode_confirmation(
+    cmd: PacketType,
+    message_id: u16,
+    code: Option<u8>,
+    properties: Option<&ConfirmationProperties>,
+    protocol_version: ProtocolVersion,
+) -> Res<Vec<u8>> {
+    let mut length = 2;
+    // Bits 3,2,1 and 0 of the Fixed Header in the PUBREL packet are reserved
+    // and MUST be set to 0,0,1 and 0 respectively. The Server MUST treat
+    // any other value as malformed and close the
+    // Network Connection [MQTT-3.6.1-1].
+    let qos = if cmd == PacketType::Pubrel { 1 } else { 0 };
+
+    // reason code in header, a missing code is Success
+    let code = if protocol_version.has_reason_codes() {
+        code.unwrap_or(0)
+    } else {
+        0
+    };
+
+    // properies mqtt 5
+    let (props_len, properties_data) = Properties::encode_option(properties, protocol_version)?;
+    // The Client or Server sending the PUBREL packet MUST use one of
+    // the PUBREL Reason Code values [MQTT-3.6.2-1]. The Reason Code
+    // and Property Length can be omitted if the Reason Code is 0x00 (Success)
+    // and there are no Properties. In this case the PUBREL has a
+    // Remaining Length of 2.
+    if protocol_version.has_reason_codes() && (code != 0 || !properties_data.is_empty()) {
+        length += 1 + properties_data.len() + props_len.len();
     }
 
-    fn puback_v5_builder(
-        cmd: PacketType,
-        message_id: u16,
-        reason_code: PubackPubrecCode,
-        properties: Option<ConfirmationProperties>,
-    ) -> ConfirmationPacket {
-        ConfirmationPacket {
-            cmd,
-            message_id,
-            properties,
-            puback_reason_code: Some(reason_code),
-            pubcomp_reason_code: None,
-        }
+    let mut writer = MqttWriter::new(length);
+    // Header
+    let mut header = FixedHeader::encode(&FixedHeader::for_type(cmd));
+    if qos > 0 {
+        header |= qos << 1;
     }
+    writer.write_u8(header);
 
-    fn pubcomp_v5_builder(
-        cmd: PacketType,
-        message_id: u16,
-        reason_code: PubcompPubrelCode,
-        properties: Option<ConfirmationProperties>,
-    ) -> ConfirmationPacket {
-        ConfirmationPacket {
-            cmd,
-            message_id,
-            properties,
-            puback_reason_code: None,
-            pubcomp_reason_code: Some(reason_code),
-        }
+    // Length
+    writer.write_length(length)?;
+
+    // Message ID
+    writer.write_u16(message_id);
+
+    // reason code and properies mqtt 5
+    if length > 2 {
+        writer.write_u8(code);
+        writer.write_sized(&properties_data, &props_len)?;
+    }
+    Ok(writer.into_vec())
+}
+
+/// Decodes the message id, the raw reason code and the properties of
+/// PUBACK, PUBREC, PUBREL and PUBCOMP. The reason code is only present for
+/// MQTT 5 and defaults to 0 = Success if omitted
+fn decode_confirmation<R: io::Read>(
+    reader: &mut ByteReader<R>,
+    length: u32,
+    protocol_version: ProtocolVersion,
+) -> Res<(u16, Option<u8>, Option<ConfirmationProperties>)> {
+    let message_id = reader.read_u16()?;
+    if !protocol_version.has_reason_codes() {
+        return Ok((message_id, None, None));
     }
+    let reason_code = if length > 2 { reader.read_u8()? } else { 0 };
+    let properties = if length > 3 {
+        match reader.read_properties()? {
+            None => None,
+            Some(props) => Some(ConfirmationProperties::decode_properties(props)?),
+        }
+    } else {
+        None
+    };
+    Ok((message_id, Some(reason_code), properties))
+}
 
+impl PubackPacket {
     /// create a correct v3 PUBACK packet. A v3 PUBACK requires
     /// only a 2 byte message_id
     /// # Example
     ///
     /// ```
-    /// use mqtt_packet_3_5::{ConfirmationPacket, PacketType};
-    /// let packet = ConfirmationPacket::puback_v3(123);
-    /// assert_eq!(packet, ConfirmationPacket {
-    ///     cmd: PacketType::Puback,
+    /// use mqtt_packet_3_5::PubackPacket;
+    /// let packet = PubackPacket::v3(123);
+    /// assert_eq!(packet, PubackPacket {
     ///     message_id: 123,
-    ///     properties: None,          // v3 has no properties
-    ///     puback_reason_code: None,  // v3 has no reason code
-    ///     pubcomp_reason_code: None, // v3 has no reason code
+    ///     reason_code: None, // v3 has no reason code
+    ///     properties: None,  // v3 has no properties
     /// });
-    ///
-    ///
     /// ```
-    pub fn puback_v3(message_id: u16) -> ConfirmationPacket {
-        ConfirmationPacket::with_message_id(PacketType::Puback, message_id)
+    pub fn v3(message_id: u16) -> PubackPacket {
+        PubackPacket {
+            message_id,
+            reason_code: None,
+            properties: None,
+        }
     }
 
     /// create a correct v5 PUBACK packet. A v5 PUBACK requires
     /// a 2 byte message_id, a reason code and possibly empty properties
-    pub fn puback_v5(
+    pub fn v5(
         message_id: u16,
         reason_code: PubackPubrecCode,
         properties: Option<ConfirmationProperties>,
-    ) -> ConfirmationPacket {
-        Self::puback_v5_builder(PacketType::Puback, message_id, reason_code, properties)
+    ) -> PubackPacket {
+        PubackPacket {
+            message_id,
+            reason_code: Some(reason_code),
+            properties,
+        }
     }
+}
 
+impl PubrecPacket {
     /// create a correct v3 PUBREC packet. A v3 PUBREC requires
     /// only a 2 byte message_id
     /// # Example
     ///
     /// ```
-    /// use mqtt_packet_3_5::{ConfirmationPacket, PacketType};
-    /// let packet = ConfirmationPacket::pubrec_v3(123);
-    /// assert_eq!(packet, ConfirmationPacket {
-    ///     cmd: PacketType::Pubrec,
+    /// use mqtt_packet_3_5::PubrecPacket;
+    /// let packet = PubrecPacket::v3(123);
+    /// assert_eq!(packet, PubrecPacket {
     ///     message_id: 123,
-    ///     properties: None,          // v3 has no properties
-    ///     puback_reason_code: None,  // v3 has no reason code
-    ///     pubcomp_reason_code: None, // v3 has no reason code
+    ///     reason_code: None, // v3 has no reason code
+    ///     properties: None,  // v3 has no properties
     /// });
-    ///
-    ///
     /// ```
-    pub fn pubrec_v3(message_id: u16) -> ConfirmationPacket {
-        ConfirmationPacket::with_message_id(PacketType::Pubrec, message_id)
+    pub fn v3(message_id: u16) -> PubrecPacket {
+        PubrecPacket {
+            message_id,
+            reason_code: None,
+            properties: None,
+        }
     }
 
     /// create a correct v5 PUBREC packet. A v5 PUBREC requires
     /// a 2 byte message_id, a reason code and possibly empty properties
-    pub fn pubrec_v5(
+    pub fn v5(
         message_id: u16,
         reason_code: PubackPubrecCode,
         properties: Option<ConfirmationProperties>,
-    ) -> ConfirmationPacket {
-        Self::puback_v5_builder(PacketType::Pubrec, message_id, reason_code, properties)
+    ) -> PubrecPacket {
+        PubrecPacket {
+            message_id,
+            reason_code: Some(reason_code),
+            properties,
+        }
     }
+}
 
+impl PubrelPacket {
     /// create a correct v3 PUBREL packet. A v3 PUBREL requires
     /// only a 2 byte message_id. Since a PUBREL needs to have QoS 1
     /// set there's no need to provide a constructor for this and is taken
@@ -114,197 +167,288 @@ impl ConfirmationPacket {
     /// # Example
     ///
     /// ```
-    /// use mqtt_packet_3_5::{ConfirmationPacket, PacketType};
-    /// let packet = ConfirmationPacket::pubrel_v3(123);
-    /// assert_eq!(packet, ConfirmationPacket {
-    ///     cmd: PacketType::Pubrel,
+    /// use mqtt_packet_3_5::PubrelPacket;
+    /// let packet = PubrelPacket::v3(123);
+    /// assert_eq!(packet, PubrelPacket {
     ///     message_id: 123,
-    ///     properties: None,          // v3 has no properties
-    ///     puback_reason_code: None,  // v3 has no reason code
-    ///     pubcomp_reason_code: None, // v3 has no reason code
+    ///     reason_code: None, // v3 has no reason code
+    ///     properties: None,  // v3 has no properties
     /// });
-    ///
-    ///
     /// ```
-    pub fn pubrel_v3(message_id: u16) -> ConfirmationPacket {
-        ConfirmationPacket::with_message_id(PacketType::Pubrel, message_id)
+    pub fn v3(message_id: u16) -> PubrelPacket {
+        PubrelPacket {
+            message_id,
+            reason_code: None,
+            properties: None,
+        }
     }
 
     /// create a correct v5 PUBREL packet. A v5 PUBREL requires
     /// a 2 byte message_id, a reason code and possibly empty properties
-    pub fn pubrel_v5(
+    pub fn v5(
         message_id: u16,
         reason_code: PubcompPubrelCode,
         properties: Option<ConfirmationProperties>,
-    ) -> ConfirmationPacket {
-        Self::pubcomp_v5_builder(PacketType::Pubrel, message_id, reason_code, properties)
+    ) -> PubrelPacket {
+        PubrelPacket {
+            message_id,
+            reason_code: Some(reason_code),
+            properties,
+        }
     }
+}
 
+impl PubcompPacket {
     /// create a correct v3 PUBCOMP packet. A v3 PUBCOMP requires
     /// only a 2 byte message_id
     /// # Example
     ///
     /// ```
-    /// use mqtt_packet_3_5::{ConfirmationPacket, PacketType};
-    /// let packet = ConfirmationPacket::pubcomp_v3(123);
-    /// assert_eq!(packet, ConfirmationPacket {
-    ///     cmd: PacketType::Pubcomp,
+    /// use mqtt_packet_3_5::PubcompPacket;
+    /// let packet = PubcompPacket::v3(123);
+    /// assert_eq!(packet, PubcompPacket {
     ///     message_id: 123,
-    ///     properties: None,          // v3 has no properties
-    ///     puback_reason_code: None,  // v3 has no reason code
-    ///     pubcomp_reason_code: None, // v3 has no reason code
+    ///     reason_code: None, // v3 has no reason code
+    ///     properties: None,  // v3 has no properties
     /// });
-    ///
-    ///
     /// ```
-    pub fn pubcomp_v3(message_id: u16) -> ConfirmationPacket {
-        ConfirmationPacket::with_message_id(PacketType::Pubcomp, message_id)
+    pub fn v3(message_id: u16) -> PubcompPacket {
+        PubcompPacket {
+            message_id,
+            reason_code: None,
+            properties: None,
+        }
     }
 
-    /// create a correct v5 PUBREL packet. A v5 PUBREL requires
+    /// create a correct v5 PUBCOMP packet. A v5 PUBCOMP requires
     /// a 2 byte message_id, a reason code and possibly empty properties
-    pub fn pubcomp_v5(
+    pub fn v5(
         message_id: u16,
         reason_code: PubcompPubrelCode,
         properties: Option<ConfirmationProperties>,
-    ) -> ConfirmationPacket {
-        Self::pubcomp_v5_builder(PacketType::Pubcomp, message_id, reason_code, properties)
+    ) -> PubcompPacket {
+        PubcompPacket {
+            message_id,
+            reason_code: Some(reason_code),
+            properties,
+        }
     }
 }
 
-impl Packet for ConfirmationPacket {
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>> {
-        //   const dup = (settings.dup && type === 'pubrel') ? protocol.DUP_MASK : 0
-        let ConfirmationPacket {
-            cmd,
+impl Packet for PubackPacket {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
+        encode_confirmation(
+            PacketType::Puback,
+            self.message_id,
+            self.reason_code.as_ref().map(|c| c.to_byte()),
+            self.properties.as_ref(),
+            protocol_version,
+        )
+    }
+
+    fn decode<R: io::Read>(
+        reader: &mut ByteReader<R>,
+        _: FixedHeader,
+        length: u32,
+        protocol_version: ProtocolVersion,
+    ) -> Res<PubackPacket> {
+        let (message_id, code, properties) = decode_confirmation(reader, length, protocol_version)?;
+        Ok(PubackPacket {
+            message_id,
+            reason_code: code.map(PubackPubrecCode::from_byte).transpose()?,
             properties,
-            pubcomp_reason_code,
-            puback_reason_code,
+        })
+    }
+}
+
+impl Packet for PubrecPacket {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
+        encode_confirmation(
+            PacketType::Pubrec,
+            self.message_id,
+            self.reason_code.as_ref().map(|c| c.to_byte()),
+            self.properties.as_ref(),
+            protocol_version,
+        )
+    }
+
+    fn decode<R: io::Read>(
+        reader: &mut ByteReader<R>,
+        _: FixedHeader,
+        length: u32,
+        protocol_version: ProtocolVersion,
+    ) -> Res<PubrecPacket> {
+        let (message_id, code, properties) = decode_confirmation(reader, length, protocol_version)?;
+        Ok(PubrecPacket {
             message_id,
-            ..
-        } = &self;
-        // let mut length = if protocol_version == 5 { 3 } else { 2 };
-        let mut length = 2;
-        // Bits 3,2,1 and 0 of the Fixed Header in the PUBREL packet are reserved
-        // and MUST be set to 0,0,1 and 0 respectively. The Server MUST treat
-        // any other value as malformed and close the
-        // Network Connection [MQTT-3.6.1-1].
-        let qos = if *cmd == PacketType::Pubrel { 1 } else { 0 };
-
-        // reason code in header
-        let code = if protocol_version == 5 {
-            match (puback_reason_code, pubcomp_reason_code, cmd) {
-                (Some(_), Some(_), t) => {
-                    return Err(format!(
-                    "Only puback_reason_code OR pubcomp_reason_code can be set simultaneously {:?}",
-                    t
-                ))
-                }
-                (Some(code), None, PacketType::Pubrec | PacketType::Puback) => Some(code.to_byte()),
-                (None, Some(code), PacketType::Pubcomp | PacketType::Pubrel) => {
-                    Some(code.to_byte())
-                }
-                (x, y, t) => {
-                    return Err(format!(
-                        "Invalid combination of confirmation type {:?} and codes {:?} | {:?}",
-                        t, x, y
-                    ))
-                }
-            }
-        } else {
-            None
-        };
-
-        // properies mqtt 5
-        let (props_len, properties_data) =
-            Properties::encode_option(properties.as_ref(), protocol_version)?;
-        // The Client or Server sending the PUBREL packet MUST use one of
-        // the PUBREL Reason Code values [MQTT-3.6.2-1]. The Reason Code
-        // and Property Length can be omitted if the Reason Code is 0x00 (Success)
-        // and there are no Properties. In this case the PUBREL has a
-        // Remaining Length of 2.
-        match (code, properties_data.is_empty()) {
-            (Some(0), true) => length = 2,
-            _ => length += properties_data.len() + props_len.len(),
-        };
-
-        let mut writer = MqttWriter::new(length);
-        // Header
-        let mut header = FixedHeader::encode(&FixedHeader::for_type(*cmd));
-        // if fixed.dup {
-        //     header |= 0x08;
-        // }
-        if qos > 0 {
-            header |= qos << 1;
-        }
-        writer.write_u8(header);
+            reason_code: code.map(PubackPubrecCode::from_byte).transpose()?,
+            properties,
+        })
+    }
+}
 
-        // Length
-        writer.write_variable_num(length as u32)?;
+impl Packet for PubrelPacket {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
+        encode_confirmation(
+            PacketType::Pubrel,
+            self.message_id,
+            self.reason_code.as_ref().map(|c| c.to_byte()),
+            self.properties.as_ref(),
+            protocol_version,
+        )
+    }
 
-        // Message ID
-        writer.write_u16(*message_id);
-        // maybe write code
-        if let (true, Some(c)) = (length > 2, code) {
-            writer.write_u8(c);
-        }
+    fn decode<R: io::Read>(
+        reader: &mut ByteReader<R>,
+        _: FixedHeader,
+        length: u32,
+        protocol_version: ProtocolVersion,
+    ) -> Res<PubrelPacket> {
+        let (message_id, code, properties) = decode_confirmation(reader, length, protocol_version)?;
+        Ok(PubrelPacket {
+            message_id,
+            reason_code: code.map(PubcompPubrelCode::from_byte).transpose()?,
+            properties,
+        })
+    }
+}
 
-        // properies mqtt 5
-        if length > 2 {
-            writer.write_sized(&properties_data, &props_len)?;
-        }
-        Ok(writer.into_vec())
+impl Packet for PubcompPacket {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
+        encode_confirmation(
+            PacketType::Pubcomp,
+            self.message_id,
+            self.reason_code.as_ref().map(|c| c.to_byte()),
+            self.properties.as_ref(),
+            protocol_version,
+        )
     }
 
-    /// Decode different confirmation types. Works for PUBACK, PUBREC, PUBREL and PUBCOMP
     fn decode<R: io::Read>(
         reader: &mut ByteReader<R>,
-        fixed: FixedHeader,
+        _: FixedHeader,
         length: u32,
-        protocol_version: u8,
-    ) -> Res<ConfirmationPacket> {
-        let message_id = reader.read_u16()?;
-        let mut packet = ConfirmationPacket {
-            cmd: fixed.cmd,
-            pubcomp_reason_code: None,
-            puback_reason_code: None,
-            properties: None,
+        protocol_version: ProtocolVersion,
+    ) -> Res<PubcompPacket> {
+        let (message_id, code, properties) = decode_confirmation(reader, length, protocol_version)?;
+        Ok(PubcompPacket {
             message_id,
-        };
-        if protocol_version == 5 {
-            let reason_code = if length > 2 {
-                // response code
-                reader.read_u8()?
-            } else {
-                0
-            };
-            // set correct reason code with either read code or
-            // from 0 = Success
-            match packet.cmd {
-                PacketType::Puback | PacketType::Pubrec => {
-                    packet.puback_reason_code = Some(PubackPubrecCode::from_byte(reason_code)?);
-                }
-                PacketType::Pubrel | PacketType::Pubcomp => {
-                    packet.pubcomp_reason_code = Some(PubcompPubrelCode::from_byte(reason_code)?);
-                }
-                t => {
-                    return Err(format!(
-                        "Something went horribly wrong. Trying to decode confirmation from {:?}",
-                        t
-                    ))
-                }
-            }
+            reason_code: code.map(PubcompPubrelCode::from_byte).transpose()?,
+            properties,
+        })
+    }
+}
 
-            if length > 3 {
-                // properies mqtt 5
-                packet.properties = match reader.read_properties()? {
-                    None => None,
-                    Some(props) => Some(ConfirmationProperties::from_properties(props)?),
-                };
-            }
+// conversions from the former shared ConfirmationPacket. They fail if the
+// cmd does not match the target type or the wrong reason code is set
+
+impl TryFrom<ConfirmationPacket> for PubackPacket {
+    type Error = String;
+
+    fn try_from(packet: ConfirmationPacket) -> Res<PubackPacket> {
+        match packet {
+            ConfirmationPacket {
+                cmd: PacketType::Puback,
+                message_id,
+                puback_reason_code,
+                pubcomp_reason_code: None,
+                properties,
+            } => Ok(PubackPacket {
+                message_id,
+                reason_code: puback_reason_code,
+                properties,
+            }),
+            p => Err(invalid_conversion(&p, PacketType::Puback)),
+        }
+    }
+}
+
+impl TryFrom<ConfirmationPacket> for PubrecPacket {
+    type Error = String;
+
+    fn try_from(packet: ConfirmationPacket) -> Res<PubrecPacket> {
+        match packet {
+            ConfirmationPacket {
+                cmd: PacketType::Pubrec,
+                message_id,
+                puback_reason_code,
+                pubcomp_reason_code: None,
+                properties,
+            } => Ok(PubrecPacket {
+                message_id,
+                reason_code: puback_reason_code,
+                properties,
+            }),
+            p => Err(invalid_conversion(&p, PacketType::Pubrec)),
+        }
+    }
+}
+
+impl TryFrom<ConfirmationPacket> for PubrelPacket {
+    type Error = String;
+
+    fn try_from(packet: ConfirmationPacket) -> Res<PubrelPacket> {
+        match packet {
+            ConfirmationPacket {
+                cmd: PacketType::Pubrel,
+                message_id,
+                puback_reason_code: None,
+                pubcomp_reason_code,
+                properties,
+            } => Ok(PubrelPacket {
+                message_id,
+                reason_code: pubcomp_reason_code,
+                properties,
+            }),
+            p => Err(invalid_conversion(&p, PacketType::Pubrel)),
         }
+    }
+}
 
-        // return true
-        Ok(packet)
+impl TryFrom<ConfirmationPacket> for PubcompPacket {
+    type Error = String;
+
+    fn try_from(packet: ConfirmationPacket) -> Res<PubcompPacket> {
+        match packet {
+            ConfirmationPacket {
+                cmd: PacketType::Pubcomp,
+                message_id,
+                puback_reason_code: None,
+                pubcomp_reason_code,
+                properties,
+            } => Ok(PubcompPacket {
+                message_id,
+                reason_code: pubcomp_reason_code,
+                properties,
+            }),
+            p => Err(invalid_conversion(&p, PacketType::Pubcomp)),
+        }
     }
 }
+
+impl TryFrom<ConfirmationPacket> for MqttPacket {
+    type Error = String;
+
+    /// Picks the packet type from `cmd`
+    fn try_from(packet: ConfirmationPacket) -> Res<MqttPacket> {
+        Ok(match packet.cmd {
+            PacketType::Puback => MqttPacket::Puback(PubackPacket::try_from(packet)?),
+            PacketType::Pubrec => MqttPacket::Pubrec(PubrecPacket::try_from(packet)?),
+            PacketType::Pubrel => MqttPacket::Pubrel(PubrelPacket::try_from(packet)?),
+            PacketType::Pubcomp => MqttPacket::Pubcomp(PubcompPacket::try_from(packet)?),
+            t => {
+                return Err(format!(
+                    "Cannot convert confirmation with type {:?} to a packet",
+                    t
+                ))
+            }
+        })
+    }
+}
+
+fn invalid_conversion(packet: &ConfirmationPacket, target: PacketType) -> String {
+    format!(
+        "Invalid combination of confirmation type {:?} and codes {:?} | {:?} for {:?}",
+        packet.cmd, packet.puback_reason_code, packet.pubcomp_reason_code, target
+    )
+}
@@ -3,19 +3,27 @@ use crate::mqtt_writer::MqttWriter;
 use crate::structure::*;
 use std::io;
 
+/// the MQTT 5 reason codes of the MQTT 3 return codes 0 to 5
+const RETURN_CODES: [u8; 6] = [0x00, 0x84, 0x85, 0x88, 0x86, 0x87];
+
+fn return_code(code: u8) -> Res<u8> {
+    match RETURN_CODES.iter().position(|c| *c == code) {
+        Some(rc) => Ok(rc as u8),
+        None => Err(format!(
+            "CONNACK reason code {:#04x} has no MQTT 3 return code",
+            code
+        )),
+    }
+}
+
 impl Packet for ConnackPacket {
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>> {
-        let rc = if protocol_version == 5 {
-            self.reason_code
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
+        let mut length = 2; // length of rc and sessionHeader
+        let rc = if protocol_version.has_reason_codes() {
+            self.code
         } else {
-            self.return_code
+            return_code(self.code)?
         };
-        let mut length = 2; // length of rc and sessionHeader
-                            // Check return code
-        if rc.is_none() {
-            return Err("Invalid return code".to_string());
-        }
-        let rc = rc.unwrap();
         // mqtt5 properties
         let (props_len, properties_data) =
             Properties::encode_option(self.properties.as_ref(), protocol_version)?;
@@ -23,7 +31,7 @@ impl Packet for ConnackPacket {
         let mut writer = MqttWriter::new(length);
         writer.write_u8(FixedHeader::for_type(PacketType::Connack).encode());
         // length
-        writer.write_variable_num(length as u32)?;
+        writer.write_length(length)?;
         writer.write_u8(if self.session_present { 0x01 } else { 0x0 });
         writer.write_u8(rc);
         writer.write_sized(&properties_data, &props_len)?;
@@ -35,7 +43,7 @@ impl Packet for ConnackPacket {
         reader: &mut ByteReader<R>,
         _: FixedHeader,
         length: u32,
-        protocol_version: u8,
+        protocol_version: ProtocolVersion,
     ) -> Res<ConnackPacket> {
         let flags = reader.read_u8()?;
         if flags > 1 {
@@ -48,23 +56,24 @@ impl Packet for ConnackPacket {
             ..ConnackPacket::default()
         };
 
-        if protocol_version == 5 {
-            packet.reason_code = if length >= 2 {
-                Some(reader.read_u8()?)
-            } else {
-                Some(0)
-            };
+        if protocol_version.has_reason_codes() {
+            if length >= 2 {
+                packet.code = reader.read_u8()?;
+            }
         } else {
             if length < 2 {
                 return Err("Packet too short".to_string());
             }
-            packet.return_code = Some(reader.read_u8()?);
+            let rc = reader.read_u8()?;
+            packet.code = *RETURN_CODES
+                .get(rc as usize)
+                .ok_or_else(|| format!("Invalid return code {}", rc))?;
         }
         // mqtt 5 properties
-        if protocol_version == 5 && reader.has_more() {
+        if protocol_version.has_properties() && reader.has_more() {
             packet.properties = match reader.read_properties()? {
                 None => None,
-                Some(props) => Some(ConnackProperties::from_properties(props)?),
+                Some(props) => Some(ConnackProperties::decode_properties(props)?),
             };
         }
         Ok(packet)
@@ -8,13 +8,25 @@ use std::io;
 const MQISDP_BUF: [u8; 6] = [b'M', b'Q', b'I', b's', b'd', b'p'];
 const MQTT_BUF: [u8; 4] = [b'M', b'Q', b'T', b'T'];
 
+impl ConnectPacket {
+    /// Protocol name that belongs to the announced version
+    pub fn protocol_id(&self) -> Protocol {
+        self.protocol_version.protocol()
+    }
+}
+
 impl Packet for ConnectPacket {
-    /// This
-    fn encode(&self, _: u8) -> Res<Vec<u8>> {
+    /// Encodes the packet for the given version, which is written as the
+    /// protocol level and has to match the `protocol_version` field
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
+        if self.protocol_version != protocol_version {
+            return Err(format!(
+                "CONNECT announces {:?} but is encoded as {:?}",
+                self.protocol_version, protocol_version
+            ));
+        }
         let ConnectPacket {
             properties,
-            protocol_id,
-            protocol_version,
             password,
             client_id,
             will,
@@ -23,7 +35,7 @@ impl Packet for ConnectPacket {
             user_name,
             ..
         } = self;
-        let protocol_version = *protocol_version;
+        let protocol_id = protocol_version.protocol();
         let mut length = 0;
 
         // add protocol length
@@ -32,20 +44,16 @@ impl Packet for ConnectPacket {
             Protocol::MQIsdp => 6,
         };
 
-        // Must be 3 or 4 or 5
-        if let 3 | 4 | 5 = protocol_version {
-            length += 1;
-        } else {
-            return Err("Invalid protocol version".to_string());
-        }
+        // protocol version
+        length += 1;
 
         // ClientId might be omitted in 3.1.1 and 5, but only if cleanSession is set to 1
-        if (client_id.is_empty() && protocol_version >= 4 && *clean_session)
+        if (client_id.is_empty() && protocol_version.allows_empty_client_id() && *clean_session)
             || !client_id.is_empty()
         {
             length += client_id.len() + 2;
         } else {
-            if protocol_version < 4 {
+            if !protocol_version.allows_empty_client_id() {
                 return Err("client_id must be supplied before 3.1.1".to_string());
             }
             if !clean_session {
@@ -80,7 +88,7 @@ impl Packet for ConnectPacket {
             } = will;
             has_will = true;
             will_retain = *retain;
-            will_qos = Some(qos);
+            will_qos = Some(*qos);
             // It must have non-empty topic
             // add topic length if any
             if let Some(t) = topic.as_ref() {
@@ -97,7 +105,7 @@ impl Packet for ConnectPacket {
                 length += data.len();
             }
             // will properties
-            if protocol_version == 5 {
+            if protocol_version.has_properties() {
                 let (l, w) = Properties::encode_option(properties.as_ref(), protocol_version)?;
                 will_properties = w;
                 will_props_len = l;
@@ -126,7 +134,7 @@ impl Packet for ConnectPacket {
         // write header
         writer.write_u8(FixedHeader::for_type(PacketType::Connect).encode());
         // length
-        writer.write_variable_num(length as u32)?;
+        writer.write_length(length)?;
         // protocol id and protocol version
         let proto_vec = match protocol_id {
             Protocol::MQIsdp => MQISDP_BUF.to_vec(),
@@ -134,13 +142,13 @@ impl Packet for ConnectPacket {
         };
         writer.write_u16(proto_vec.len() as u16);
         writer.write_vec(proto_vec);
-        writer.write_u8(protocol_version);
+        writer.write_u8(protocol_version.to_byte());
         // write connect flags
         writer.write_u8(
             ((has_username as u8) * 0x80) //user_name:  0x80 = (1 << 7)
             | ((has_password as u8) * 0x40) //password:  0x40 = (1 << 6)
             | ((will_retain as u8) * 0x20)  //will_retain:  0x20 = (1 << 5)
-            | ((*will_qos.unwrap_or(&0) << 3) & 0x18)     //will_qos:  0x18 = 24 = ((1 << 4) + (1 << 3)),
+            | ((will_qos.map_or(0, |q| q.to_byte()) << 3) & 0x18)     //will_qos:  0x18 = 24 = ((1 << 4) + (1 << 3)),
             | ((has_will as u8) * 0x4) //will:  0x4 = 1 << 2
             | ((*clean_session as u8) * 0x2), //clean_session:  0x2 = 1 << 2)
         );
@@ -149,55 +157,65 @@ impl Packet for ConnectPacket {
 
         writer.write_sized(&properties_data, &props_len)?;
         // client id
-        writer.write_utf8_str(client_id);
+        writer.write_utf8_str(client_id)?;
         // will properties
-        if protocol_version == 5 {
+        if protocol_version.has_properties() {
             writer.write_sized(&will_properties, &will_props_len)?;
         }
         // will topic and payload
         if has_will {
-            writer.write_utf8_str(will_topic);
-            writer.write_utf8_str(will_payload);
+            writer.write_utf8_str(will_topic)?;
+            writer.write_utf8_str(will_payload)?;
         }
 
         // username
         if let Some(u) = user_name {
-            writer.write_utf8_str(u);
+            writer.write_utf8_str(u)?;
         }
         // password
         if let Some(p) = password {
-            writer.write_utf8_str(p);
+            writer.write_utf8_str(p)?;
         }
         Ok(writer.into_vec())
     }
 
     /// Decode connect packet
-    fn decode<R: io::Read>(reader: &mut ByteReader<R>, _: FixedHeader, _: u32, _: u8) -> Res<Self> {
+    fn decode<R: io::Read>(
+        reader: &mut ByteReader<R>,
+        _: FixedHeader,
+        _: u32,
+        _: ProtocolVersion,
+    ) -> Res<Self> {
         // Parse protocolId
         let protocol_id = reader.read_utf8_string()?;
         let protocol_id = Protocol::from_source(&protocol_id)?;
         // Parse constants version number
-        let mut protocol_version = reader.read_u8()?;
+        let mut protocol_level = reader.read_u8()?;
         if !reader.has_more() {
             return Err("Packet too short".to_string());
         }
 
-        if protocol_version >= 128 {
+        if protocol_level >= 128 {
             //   packet.bridgeMode = true
-            protocol_version -= 128
+            protocol_level -= 128
         }
 
-        if protocol_version != 3 && protocol_version != 4 && protocol_version != 5 {
-            return Err("Invalid protocol version".to_string());
+        let protocol_version = ProtocolVersion::from_byte(protocol_level)?;
+        if protocol_version.protocol() != protocol_id {
+            return Err(format!(
+                "Protocol name {} does not match protocol version {}",
+                protocol_id.name(),
+                protocol_version
+            ));
         }
 
         let (connect_flags, last_will) = ConnectFlags::from_byte(reader.read_u8()?)?;
         // Parse keepalive
         let keep_alive = reader.read_u16()?;
-        let connect_properties = if protocol_version == 5 {
+        let connect_properties = if protocol_version.has_properties() {
             match reader.read_properties()? {
                 None => None,
-                Some(props) => Some(ConnectProperties::from_properties(props)?),
+                Some(props) => Some(ConnectProperties::decode_properties(props)?),
             }
         } else {
             None
@@ -206,14 +224,14 @@ impl Packet for ConnectPacket {
         // Parse client_id
         let client_id = reader.read_utf8_string()?;
         let last_will = if let (Some(mut will), true) = (last_will, connect_flags.will) {
-            if protocol_version == 5 {
+            if protocol_version.has_properties() {
                 will.properties = match reader.read_properties()? {
                     None => None,
-                    Some(props) => Some(WillProperties::from_properties(props)?),
+                    Some(props) => Some(WillProperties::decode_properties(props)?),
                 };
             }
             // Parse will topic
-            will.topic = Some(reader.read_utf8_string()?);
+            will.topic = Some(reader.read_topic()?);
             // Parse will payload
             will.payload = Some(reader.read_utf8_string()?);
             Some(will)
@@ -237,7 +255,6 @@ impl Packet for ConnectPacket {
         Ok(ConnectPacket {
             client_id,
             protocol_version,
-            protocol_id,
             clean_session: connect_flags.clean_session,
             keep_alive,
             properties: connect_properties,
@@ -254,21 +271,26 @@ pub struct ConnectFlags {
     pub user_name: bool,
     pub password: bool,
     pub will_retain: bool,
-    pub will_qos: u8,
+    pub will_qos: QoS,
     pub will: bool,
     pub clean_session: bool,
 }
 
 impl ConnectFlags {
-    pub fn new(byte: u8) -> ConnectFlags {
-        ConnectFlags {
+    pub fn new(byte: u8) -> Res<ConnectFlags> {
+        // The value of Will QoS MUST NOT be 3 [MQTT-3.1.2-12]
+        let will_qos = match QoS::from_byte((byte & 0x18) >> 3) {
+            Ok(qos) => qos,
+            Err(_) => return Err("Will QoS must not be 3".to_string()),
+        };
+        Ok(ConnectFlags {
             user_name: (byte & 0x80) != 0,    // 0x80 = (1 << 7)
             password: (byte & 0x40) != 0,     // 0x40 = (1 << 6)
             will_retain: (byte & 0x20) != 0,  // 0x20 = (1 << 5)
-            will_qos: (byte & 0x18) >> 3,     // 0x18 = 24 = ((1 << 4) + (1 << 3)),
+            will_qos,                         // 0x18 = 24 = ((1 << 4) + (1 << 3)),
             will: (byte & 0x4) != 0,          // 0x4 = 1 << 2
             clean_session: (byte & 0x2) != 0, // 0x2 = 1 << 2
-        }
+        })
     }
 
     pub fn from_byte(connect_flags: u8) -> Result<(ConnectFlags, Option<LastWill>), String> {
@@ -276,7 +298,7 @@ impl ConnectFlags {
             // The Server MUST validate that the reserved flag in the CONNECT Control Packet is set to zero and disconnect the Client if it is not zero [MQTT-3.1.2-3]
             return Err("Connect flag bit 0 must be 0, but got 1".to_string());
         }
-        let connect_flags = ConnectFlags::new(connect_flags);
+        let connect_flags = ConnectFlags::new(connect_flags)?;
 
         if !connect_flags.will {
             if connect_flags.will_retain {
@@ -284,7 +306,7 @@ impl ConnectFlags {
                     "Will Retain Flag must be set to zero when Will Flag is set to 0".to_string(),
                 );
             }
-            if connect_flags.will_qos != 0 {
+            if connect_flags.will_qos != QoS::QoS0 {
                 return Err("Will QoS must be set to zero when Will Flag is set to 0".to_string());
             }
         }
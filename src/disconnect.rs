@@ -8,34 +8,47 @@ impl Packet for DisconnectPacket {
         reader: &mut ByteReader<R>,
         _: FixedHeader,
         length: u32,
-        protocol_version: u8,
+        protocol_version: ProtocolVersion,
     ) -> Res<DisconnectPacket> {
         let mut packet = DisconnectPacket {
             reason_code: None,
             properties: None,
         };
-        if protocol_version == 5 {
-            // response code
-            if length > 0 {
-                let reason_code = reader.read_u8()?;
-                // validate disconnect code
-                let reason_code = DisconnectCode::from_byte(reason_code)?;
-                packet.reason_code = Some(reason_code);
-            } else {
+        if protocol_version.has_reason_codes() {
+            if length == 0 && reader.options().is_lenient() {
+                // some clients send an empty DISCONNECT even with MQTT 5
+                reader.warn(DecodeWarning::EmptyPacket(PacketType::Disconnect));
                 packet.reason_code = Some(DisconnectCode::NormalDisconnection);
+                return Ok(packet);
+            }
+            // response code
+            if length == 0 {
+                return Err("Missing disconnect reason code".to_string());
             }
-            // properies mqtt 5
-            packet.properties = match reader.read_properties()? {
-                None => None,
-                Some(props) => Some(DisconnectProperties::from_properties(props)?),
+            let reason_code = reader.read_u8()?;
+            // validate disconnect code
+            let reason_code = DisconnectCode::from_byte(reason_code)?;
+            packet.reason_code = Some(reason_code);
+            // properies mqtt 5, absent with a remaining length below 2
+            packet.properties = if length >= 2 {
+                match reader.read_properties()? {
+                    None => None,
+                    Some(props) => Some(DisconnectProperties::decode_properties(props)?),
+                }
+            } else {
+                None
             };
         }
 
         Ok(packet)
     }
 
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>> {
-        let mut length = if protocol_version == 5 { 1 } else { 0 };
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
+        let mut length = if protocol_version.has_reason_codes() {
+            1
+        } else {
+            0
+        };
         // properies mqtt 5
         let (props_len, properties_data) =
             Properties::encode_option(self.properties.as_ref(), protocol_version)?;
@@ -44,10 +57,13 @@ impl Packet for DisconnectPacket {
         // Header
         writer.write_header(FixedHeader::for_type(PacketType::Disconnect));
         // Length
-        writer.write_variable_num(length as u32)?;
+        writer.write_length(length)?;
         // reason code in header
-        if protocol_version == 5 && self.reason_code.is_some() {
-            writer.write_u8(self.reason_code.as_ref().unwrap().to_byte());
+        if let (true, Some(code)) = (
+            protocol_version.has_reason_codes(),
+            self.reason_code.as_ref(),
+        ) {
+            writer.write_u8(code.to_byte());
         }
         // properies mqtt 5
         writer.write_sized(&properties_data, &props_len)?;
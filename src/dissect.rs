@@ -0,0 +1,595 @@
+//! Splits raw bytes into named fields with their byte ranges, for debugging
+//! packets of other implementations without a full protocol analyzer
+//!
+//! # Examples
+//!
+//! ```
+//! use mqtt_packet_3_5::{dissect, ProtocolVersion};
+//! let dissection = dissect(&[48, 5, 0, 1, 97, 104, 105], ProtocolVersion::V311);
+//! assert_eq!(
+//!     dissection.to_string(),
+//!     "48, // Header: Packet type=PUBLISH (3), DUP=0, QoS=0, RETAIN=0\n\
+//!      5, // Remaining length: 5\n\
+//!      0, 1, 97, // Topic: \"a\"\n\
+//!      104, 105, // Payload: \"hi\"\n"
+//! );
+//! ```
+use crate::structure::*;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    pub name: String,
+    /// position of the field in the dissected bytes
+    pub range: Range<usize>,
+    /// decoded value, None for fields that only group other fields
+    pub value: Option<String>,
+    /// sub fields, which cover the same byte as their parent for bit fields
+    pub children: Vec<Field>,
+}
+
+/// Where and why the dissection stopped
+#[derive(Debug, PartialEq, Clone)]
+pub struct DissectError {
+    pub offset: usize,
+    /// name of the field that could not be read
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Dissection {
+    pub bytes: Vec<u8>,
+    /// one field per packet, in the order they appear
+    pub packets: Vec<Field>,
+    pub error: Option<DissectError>,
+}
+
+/// Dissects all packets in buf. If a packet can't be read the fields up to
+/// the failure are kept and the location of the failure is returned in `error`
+pub fn dissect(buf: &[u8], protocol_version: ProtocolVersion) -> Dissection {
+    let mut dissector = Dissector {
+        buf,
+        pos: 0,
+        end: buf.len(),
+        fields: vec![],
+        version: protocol_version,
+    };
+    let mut error = None;
+    while dissector.pos < buf.len() {
+        if let Err(e) = dissector.packet() {
+            error = Some(e);
+            break;
+        }
+    }
+    Dissection {
+        bytes: buf.to_vec(),
+        packets: dissector.fields,
+        error,
+    }
+}
+
+type Step<T> = Result<T, DissectError>;
+
+/// how the value of a property is encoded
+enum PropertyKind {
+    Byte,
+    U16,
+    U32,
+    VarInt,
+    Str,
+    Binary,
+    Pair,
+}
+
+fn property_kind(id: u8) -> Option<PropertyKind> {
+    Some(match id {
+        0x01 | 0x17 | 0x19 | 0x24 | 0x25 | 0x28 | 0x29 | 0x2A => PropertyKind::Byte,
+        0x13 | 0x21 | 0x22 | 0x23 => PropertyKind::U16,
+        0x02 | 0x11 | 0x18 | 0x27 => PropertyKind::U32,
+        0x0B => PropertyKind::VarInt,
+        0x03 | 0x08 | 0x12 | 0x15 | 0x1A | 0x1C | 0x1F => PropertyKind::Str,
+        0x09 | 0x16 => PropertyKind::Binary,
+        0x26 => PropertyKind::Pair,
+        _ => return None,
+    })
+}
+
+fn packet_name(cmd: PacketType) -> String {
+    format!("{:?}", cmd).to_uppercase()
+}
+
+fn bit(byte: u8, mask: u8) -> String {
+    if byte & mask != 0 { "1" } else { "0" }.to_string()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// a quoted string if the bytes are UTF-8, hex otherwise
+fn text_or_hex(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => format!("{:?}", s),
+        Err(_) => format!("0x{}", hex(bytes)),
+    }
+}
+
+struct Dissector<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    /// end of the packet or properties that are currently read
+    end: usize,
+    /// fields of the group that is currently read
+    fields: Vec<Field>,
+    version: ProtocolVersion,
+}
+
+impl<'a> Dissector<'a> {
+    fn error(&self, field: &str, message: String) -> DissectError {
+        DissectError {
+            offset: self.pos,
+            field: field.to_string(),
+            message,
+        }
+    }
+
+    fn has_more(&self) -> bool {
+        self.pos < self.end
+    }
+
+    fn read(&mut self, field: &str, len: usize) -> Step<&'a [u8]> {
+        if self.end - self.pos < len {
+            return Err(self.error(
+                field,
+                format!("needs {} bytes, only {} left", len, self.end - self.pos),
+            ));
+        }
+        let bytes = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self, field: &str) -> Step<u8> {
+        Ok(self.read(field, 1)?[0])
+    }
+
+    fn read_u16(&mut self, field: &str) -> Step<u16> {
+        let b = self.read(field, 2)?;
+        Ok(((b[0] as u16) << 8) + b[1] as u16)
+    }
+
+    fn read_u32(&mut self, field: &str) -> Step<u32> {
+        let b = self.read(field, 4)?;
+        Ok(((b[0] as u32) << 24) + ((b[1] as u32) << 16) + ((b[2] as u32) << 8) + b[3] as u32)
+    }
+
+    fn read_varint(&mut self, field: &str) -> Step<u32> {
+        let start = self.pos;
+        let mut num = 0u32;
+        for i in 0..4 {
+            let next = self.read_u8(field)? as u32;
+            num += (next & 0x7F) << (7 * i);
+            if next & 0x80 == 0 {
+                return Ok(num);
+            }
+        }
+        self.pos = start;
+        Err(self.error(
+            field,
+            "variable byte integer longer than 4 bytes".to_string(),
+        ))
+    }
+
+    fn read_binary(&mut self, field: &str) -> Step<&'a [u8]> {
+        let start = self.pos;
+        let len = self.read_u16(field)?;
+        self.read(field, len as usize).map_err(|e| {
+            self.pos = start;
+            DissectError { offset: start, ..e }
+        })
+    }
+
+    fn read_string(&mut self, field: &str) -> Step<&'a str> {
+        let start = self.pos;
+        let bytes = self.read_binary(field)?;
+        std::str::from_utf8(bytes).map_err(|e| {
+            self.pos = start;
+            self.error(field, format!("invalid UTF-8: {}", e))
+        })
+    }
+
+    fn push(&mut self, name: &str, start: usize, value: String, children: Vec<Field>) {
+        self.fields.push(Field {
+            name: name.to_string(),
+            range: start..self.pos,
+            value: Some(value),
+            children,
+        });
+    }
+
+    fn u8(&mut self, name: &str, describe: impl Fn(u8) -> String) -> Step<u8> {
+        let start = self.pos;
+        let byte = self.read_u8(name)?;
+        self.push(name, start, describe(byte), vec![]);
+        Ok(byte)
+    }
+
+    fn u16(&mut self, name: &str) -> Step<u16> {
+        let start = self.pos;
+        let num = self.read_u16(name)?;
+        self.push(name, start, num.to_string(), vec![]);
+        Ok(num)
+    }
+
+    fn varint(&mut self, name: &str) -> Step<u32> {
+        let start = self.pos;
+        let num = self.read_varint(name)?;
+        self.push(name, start, num.to_string(), vec![]);
+        Ok(num)
+    }
+
+    fn string(&mut self, name: &str) -> Step<()> {
+        let start = self.pos;
+        let s = self.read_string(name)?;
+        self.push(name, start, format!("{:?}", s), vec![]);
+        Ok(())
+    }
+
+    /// a byte that consists of several bit fields
+    fn bits(
+        &mut self,
+        name: &str,
+        describe: impl Fn(u8) -> Vec<(&'static str, String)>,
+    ) -> Step<u8> {
+        let start = self.pos;
+        let byte = self.read_u8(name)?;
+        let children = describe(byte)
+            .into_iter()
+            .map(|(name, value)| Field {
+                name: name.to_string(),
+                range: start..self.pos,
+                value: Some(value),
+                children: vec![],
+            })
+            .collect();
+        self.fields.push(Field {
+            name: name.to_string(),
+            range: start..self.pos,
+            value: None,
+            children,
+        });
+        Ok(byte)
+    }
+
+    /// everything that's left, e.g. the payload of a PUBLISH
+    fn rest(&mut self, name: &str) {
+        let start = self.pos;
+        let bytes = &self.buf[self.pos..self.end];
+        self.pos = self.end;
+        self.push(name, start, text_or_hex(bytes), vec![]);
+    }
+
+    /// reads the fields created by f into a field of their own,
+    /// which is kept even if f fails
+    fn group(&mut self, name: &str, f: impl FnOnce(&mut Self) -> Step<()>) -> Step<()> {
+        let start = self.pos;
+        let outer = std::mem::take(&mut self.fields);
+        let res = f(self);
+        let children = std::mem::replace(&mut self.fields, outer);
+        self.fields.push(Field {
+            name: name.to_string(),
+            range: start..self.pos,
+            value: None,
+            children,
+        });
+        res
+    }
+
+    /// limits f to the next len bytes
+    fn limited(&mut self, len: usize, f: impl FnOnce(&mut Self) -> Step<()>) -> Step<()> {
+        let outer = self.end;
+        self.end = outer.min(self.pos + len);
+        let res = f(self);
+        self.end = outer;
+        res
+    }
+
+    fn packet(&mut self) -> Step<()> {
+        let first = self.buf[self.pos];
+        let cmd = PacketType::from_bits(first >> 4);
+        self.group(&packet_name(cmd), |d| {
+            d.bits("Header", |byte| {
+                let mut bits = vec![(
+                    "Packet type",
+                    format!("{} ({})", packet_name(cmd), byte >> 4),
+                )];
+                if cmd == PacketType::Publish {
+                    bits.push(("DUP", bit(byte, 0x08)));
+                    bits.push(("QoS", ((byte >> 1) & 0x03).to_string()));
+                    bits.push(("RETAIN", bit(byte, 0x01)));
+                } else {
+                    bits.push(("Reserved", format!("0x{:X}", byte & 0x0F)));
+                }
+                bits
+            })?;
+            let length = d.varint("Remaining length")? as usize;
+            let available = d.end - d.pos;
+            d.limited(length, |d| {
+                d.body(cmd, first)?;
+                if d.has_more() {
+                    d.rest("Unexpected bytes");
+                }
+                Ok(())
+            })?;
+            if available < length {
+                return Err(d.error(
+                    "Remaining length",
+                    format!("packet needs {} bytes, only {} left", length, available),
+                ));
+            }
+            Ok(())
+        })
+    }
+
+    fn body(&mut self, cmd: PacketType, header: u8) -> Step<()> {
+        let version = self.version;
+        match cmd {
+            PacketType::Connect => self.connect(),
+            PacketType::Connack => {
+                self.bits("Connect acknowledge flags", |byte| {
+                    vec![("Session present", bit(byte, 0x01))]
+                })?;
+                if version.has_reason_codes() {
+                    self.u8("Reason code", |c| format!("0x{:02X}", c))?;
+                    if self.has_more() {
+                        self.properties("Properties")?;
+                    }
+                } else {
+                    self.u8("Return code", |c| c.to_string())?;
+                }
+                Ok(())
+            }
+            PacketType::Publish => {
+                self.string("Topic")?;
+                if header & 0x06 != 0 {
+                    self.u16("Packet identifier")?;
+                }
+                if version.has_properties() {
+                    self.properties("Properties")?;
+                }
+                self.rest("Payload");
+                Ok(())
+            }
+            PacketType::Puback | PacketType::Pubrec | PacketType::Pubrel | PacketType::Pubcomp => {
+                self.u16("Packet identifier")?;
+                if version.has_reason_codes() && self.has_more() {
+                    self.u8("Reason code", |c| format!("0x{:02X}", c))?;
+                    if self.has_more() {
+                        self.properties("Properties")?;
+                    }
+                }
+                Ok(())
+            }
+            PacketType::Subscribe => {
+                self.u16("Packet identifier")?;
+                if version.has_properties() {
+                    self.properties("Properties")?;
+                }
+                while self.has_more() {
+                    self.group("Subscription", |d| {
+                        d.string("Topic filter")?;
+                        d.bits("Options", |byte| {
+                            let mut bits = vec![("QoS", (byte & 0x03).to_string())];
+                            if version.has_subscription_options() {
+                                bits.push(("No local", bit(byte, 0x04)));
+                                bits.push(("Retain as published", bit(byte, 0x08)));
+                                bits.push(("Retain handling", ((byte >> 4) & 0x03).to_string()));
+                            }
+                            bits
+                        })?;
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            }
+            PacketType::Unsubscribe => {
+                self.u16("Packet identifier")?;
+                if version.has_properties() {
+                    self.properties("Properties")?;
+                }
+                while self.has_more() {
+                    self.string("Topic filter")?;
+                }
+                Ok(())
+            }
+            PacketType::Suback | PacketType::Unsuback => {
+                self.u16("Packet identifier")?;
+                if version.has_properties() {
+                    self.properties("Properties")?;
+                }
+                while self.has_more() {
+                    self.u8("Reason code", |c| format!("0x{:02X}", c))?;
+                }
+                Ok(())
+            }
+            PacketType::Disconnect | PacketType::Auth => {
+                if version.has_reason_codes() && self.has_more() {
+                    self.u8("Reason code", |c| format!("0x{:02X}", c))?;
+                    if self.has_more() {
+                        self.properties("Properties")?;
+                    }
+                }
+                Ok(())
+            }
+            PacketType::Pingreq | PacketType::Pingresp => Ok(()),
+            PacketType::Reserved => Err(self.error("Header", "reserved packet type".to_string())),
+        }
+    }
+
+    fn connect(&mut self) -> Step<()> {
+        self.string("Protocol name")?;
+        let level = self.u8(
+            "Protocol version",
+            |level| match ProtocolVersion::from_byte(level & 0x7F) {
+                Ok(version) => format!("{} (MQTT {})", level, version),
+                Err(_) => format!("{} (unknown)", level),
+            },
+        )?;
+        // the rest of the packet follows the version of the CONNECT
+        if let Ok(version) = ProtocolVersion::from_byte(level & 0x7F) {
+            self.version = version;
+        }
+        let flags = self.bits("Connect flags", |byte| {
+            vec![
+                ("User name", bit(byte, 0x80)),
+                ("Password", bit(byte, 0x40)),
+                ("Will retain", bit(byte, 0x20)),
+                ("Will QoS", ((byte >> 3) & 0x03).to_string()),
+                ("Will", bit(byte, 0x04)),
+                ("Clean session", bit(byte, 0x02)),
+                ("Reserved", bit(byte, 0x01)),
+            ]
+        })?;
+        self.u16("Keep alive")?;
+        let has_properties = self.version.has_properties();
+        if has_properties {
+            self.properties("Properties")?;
+        }
+        self.string("Client ID")?;
+        if flags & 0x04 != 0 {
+            if has_properties {
+                self.properties("Will properties")?;
+            }
+            self.string("Will topic")?;
+            let start = self.pos;
+            let payload = self.read_binary("Will payload")?;
+            self.push("Will payload", start, text_or_hex(payload), vec![]);
+        }
+        if flags & 0x80 != 0 {
+            self.string("User name")?;
+        }
+        if flags & 0x40 != 0 {
+            let start = self.pos;
+            let password = self.read_binary("Password")?;
+            self.push("Password", start, text_or_hex(password), vec![]);
+        }
+        Ok(())
+    }
+
+    fn properties(&mut self, name: &str) -> Step<()> {
+        self.group(name, |d| {
+            let length = d.varint("Properties length")? as usize;
+            let available = d.end - d.pos;
+            d.limited(length, |d| {
+                while d.has_more() {
+                    d.property()?;
+                }
+                Ok(())
+            })?;
+            if available < length {
+                return Err(d.error(
+                    "Properties length",
+                    "properties end after the packet".to_string(),
+                ));
+            }
+            Ok(())
+        })
+    }
+
+    fn property(&mut self) -> Step<()> {
+        let start = self.pos;
+        let id = self.read_u8("Property identifier")?;
+        let (name, kind) = match (property_name(id), property_kind(id)) {
+            (Some(name), Some(kind)) => (format!("{} (0x{:02X})", name, id), kind),
+            _ => {
+                self.pos = start;
+                return Err(self.error(
+                    "Property identifier",
+                    format!("unknown property 0x{:02X}", id),
+                ));
+            }
+        };
+        let value = match kind {
+            PropertyKind::Byte => self.read_u8(&name)?.to_string(),
+            PropertyKind::U16 => self.read_u16(&name)?.to_string(),
+            PropertyKind::U32 => self.read_u32(&name)?.to_string(),
+            PropertyKind::VarInt => self.read_varint(&name)?.to_string(),
+            PropertyKind::Str => format!("{:?}", self.read_string(&name)?),
+            PropertyKind::Binary => text_or_hex(self.read_binary(&name)?),
+            PropertyKind::Pair => {
+                let key = self.read_string(&name)?;
+                let value = self.read_string(&name)?;
+                format!("{:?}={:?}", key, value)
+            }
+        };
+        self.push(&name, start, value, vec![]);
+        Ok(())
+    }
+}
+
+impl Field {
+    /// the value of the field, including its bit fields
+    fn label(&self) -> String {
+        match &self.value {
+            Some(value) => format!("{}: {}", self.name, value),
+            None => {
+                let bits: Vec<String> = self
+                    .children
+                    .iter()
+                    .map(|c| format!("{}={}", c.name, c.value.as_deref().unwrap_or("")))
+                    .collect();
+                format!("{}: {}", self.name, bits.join(", "))
+            }
+        }
+    }
+
+    fn is_line(&self) -> bool {
+        self.children.is_empty() || self.children.iter().all(|c| c.range == self.range)
+    }
+}
+
+fn write_bytes(f: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    for b in bytes {
+        write!(f, "{}, ", b)?;
+    }
+    Ok(())
+}
+
+impl Dissection {
+    fn write_field(&self, f: &mut fmt::Formatter, field: &Field) -> fmt::Result {
+        if field.value.is_none() && field.children.is_empty() {
+            return Ok(());
+        }
+        if field.is_line() {
+            write_bytes(f, &self.bytes[field.range.clone()])?;
+            return writeln!(f, "// {}", field.label());
+        }
+        for child in field.children.iter() {
+            self.write_field(f, child)?;
+        }
+        Ok(())
+    }
+}
+
+/// Annotated bytes in the style of the test fixtures, one field per line
+impl fmt::Display for Dissection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for packet in self.packets.iter() {
+            self.write_field(f, packet)?;
+        }
+        if let Some(error) = &self.error {
+            if error.offset < self.bytes.len() {
+                write_bytes(f, &self.bytes[error.offset..])?;
+                writeln!(f, "// not dissected")?;
+            }
+            writeln!(
+                f,
+                "// error at byte {} in {}: {}",
+                error.offset, error.field, error.message
+            )?;
+        }
+        Ok(())
+    }
+}
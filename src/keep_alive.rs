@@ -0,0 +1,174 @@
+//! Keep alive (chapter 3.1.2.10 of the MQTT 5 spec). A client has to send a
+//! control packet at least every Keep Alive seconds, a PINGREQ if it has
+//! nothing else to send, and considers the connection dead if the PINGRESP
+//! does not arrive. A server closes the connection if nothing arrived for one
+//! and a half times the Keep Alive. A Keep Alive of 0 turns this off.
+//!
+//! The trackers are sans-I/O: the connection reports the packets it sends or
+//! receives and calls `poll` when the `deadline` is reached
+use crate::clock::Clock;
+use crate::packet::MqttPacket;
+use crate::structure::*;
+use std::time::{Duration, Instant};
+
+/// What the connection has to do when a keep alive deadline is reached
+#[derive(Debug, PartialEq, Clone)]
+pub enum KeepAliveAction {
+    /// send `MqttPacket::Pingreq`
+    SendPingreq,
+    /// close the network connection, after sending the DISCONNECT if given
+    Close(Option<DisconnectPacket>),
+}
+
+fn interval(keep_alive: u16) -> Option<Duration> {
+    match keep_alive {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds as u64)),
+    }
+}
+
+/// Keep alive of the client side of a connection
+#[derive(Debug, Clone)]
+pub struct ClientKeepAlive {
+    interval: Option<Duration>,
+    ping_timeout: Option<Duration>,
+    last_sent: Instant,
+    pingreq_sent: Option<Instant>,
+}
+
+impl ClientKeepAlive {
+    /// uses the Keep Alive of the CONNECT, which is sent now
+    pub fn new(connect: &ConnectPacket, clock: &dyn Clock) -> ClientKeepAlive {
+        ClientKeepAlive {
+            interval: interval(connect.keep_alive),
+            ping_timeout: None,
+            last_sent: clock.now(),
+            pingreq_sent: None,
+        }
+    }
+
+    /// how long to wait for a PINGRESP, the Keep Alive by default
+    pub fn with_ping_timeout(mut self, timeout: Duration) -> ClientKeepAlive {
+        self.ping_timeout = Some(timeout);
+        self
+    }
+
+    /// the Keep Alive in use, None if turned off
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// A Server Keep Alive in the CONNACK replaces the Keep Alive of the
+    /// CONNECT [MQTT-3.1.2-21]
+    pub fn connack(&mut self, connack: &ConnackPacket) {
+        if let Some(keep_alive) = connack
+            .properties
+            .as_ref()
+            .and_then(|props| props.server_keep_alive)
+        {
+            self.interval = interval(keep_alive);
+        }
+    }
+
+    /// any control packet that is sent resets the Keep Alive
+    pub fn packet_sent(&mut self, clock: &dyn Clock) {
+        self.last_sent = clock.now();
+    }
+
+    /// a PINGRESP answers the outstanding PINGREQ
+    pub fn packet_received(&mut self, packet: &MqttPacket) {
+        if *packet == MqttPacket::Pingresp {
+            self.pingreq_sent = None;
+        }
+    }
+
+    /// when `poll` has something to do, None if keep alive is turned off
+    pub fn deadline(&self) -> Option<Instant> {
+        let interval = self.interval?;
+        Some(match self.pingreq_sent {
+            Some(sent) => sent + self.ping_timeout.unwrap_or(interval),
+            None => self.last_sent + interval,
+        })
+    }
+
+    /// A PINGREQ is due once nothing was sent for the Keep Alive, which
+    /// counts as sent with this call. The connection is dead if its PINGRESP
+    /// did not arrive in time
+    pub fn poll(&mut self, clock: &dyn Clock) -> Option<KeepAliveAction> {
+        let now = clock.now();
+        if self.deadline().is_none_or(|deadline| now < deadline) {
+            return None;
+        }
+        if self.pingreq_sent.is_some() {
+            return Some(KeepAliveAction::Close(None));
+        }
+        self.pingreq_sent = Some(now);
+        self.last_sent = now;
+        Some(KeepAliveAction::SendPingreq)
+    }
+}
+
+/// Keep alive of the server side of a connection
+#[derive(Debug, Clone)]
+pub struct ServerKeepAlive {
+    protocol_version: ProtocolVersion,
+    keep_alive: u16,
+    last_received: Instant,
+}
+
+impl ServerKeepAlive {
+    /// Uses the Keep Alive of the CONNECT, which arrived now, or
+    /// `server_keep_alive` if the server overrides it. That is only possible
+    /// in MQTT 5, where it is sent as the Server Keep Alive of the CONNACK
+    pub fn new(
+        connect: &ConnectPacket,
+        server_keep_alive: Option<u16>,
+        clock: &dyn Clock,
+    ) -> ServerKeepAlive {
+        let keep_alive = match server_keep_alive {
+            Some(keep_alive) if connect.protocol_version == ProtocolVersion::V5 => keep_alive,
+            _ => connect.keep_alive,
+        };
+        ServerKeepAlive {
+            protocol_version: connect.protocol_version,
+            keep_alive,
+            last_received: clock.now(),
+        }
+    }
+
+    /// the Keep Alive in use, 0 if turned off
+    pub fn keep_alive(&self) -> u16 {
+        self.keep_alive
+    }
+
+    /// any control packet that arrives resets the Keep Alive
+    pub fn packet_received(&mut self, clock: &dyn Clock) {
+        self.last_received = clock.now();
+    }
+
+    /// one and a half times the Keep Alive after the last packet arrived
+    /// [MQTT-3.1.2-22], None if keep alive is turned off
+    pub fn deadline(&self) -> Option<Instant> {
+        let interval = interval(self.keep_alive)?;
+        Some(self.last_received + interval * 3 / 2)
+    }
+
+    /// Closes the connection once the deadline has passed, in MQTT 5 with a
+    /// DISCONNECT with Keep Alive timeout
+    pub fn poll(&mut self, clock: &dyn Clock) -> Option<KeepAliveAction> {
+        if self
+            .deadline()
+            .is_none_or(|deadline| clock.now() < deadline)
+        {
+            return None;
+        }
+        let disconnect = match self.protocol_version {
+            ProtocolVersion::V5 => Some(DisconnectPacket {
+                reason_code: Some(DisconnectCode::KeepAliveTimeout),
+                properties: None,
+            }),
+            _ => None,
+        };
+        Some(KeepAliveAction::Close(disconnect))
+    }
+}
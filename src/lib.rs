@@ -2,7 +2,7 @@
 //!
 //! This is a library designed to be used for creating mqtt clients or mqtt brokers.
 //! As many things as were reasonable are encoded in the type system, e.g.
-//! - packets have their own types, except for PUBCOMP/PUBREC/PUBREL/PUBACK, since they are essentially the same
+//! - packets have their own types, including PUBACK/PUBREC/PUBREL/PUBCOMP which only accept their own reason codes
 //! - reason codes are enums and it's not possible to build a packet with an invalid reason code
 //! - properties are defined for every single packet type and therefore only valid property codes can be written into the packet
 //!
@@ -38,24 +38,39 @@
 //! - [x] A better command building API?
 //! - [ ] Make only necessary code public
 //! - [ ] Support for Maximum Packet Size (MQTTv5). Should not send certain properties if they "bloat" the packet
-//! - [ ] Ensure all properties have the correct Optionality set in their types
-//! - [ ] Add some fuzzing tests to prevent unwanted panic! calls
+//! - [x] Ensure all properties have the correct Optionality set in their types
+//! - [x] Add some fuzzing tests to prevent unwanted panic! calls
 //! - [ ] Improve documentation
 
 pub mod auth;
 pub mod byte_reader;
+pub mod clock;
 pub mod confirmation;
 pub mod connack;
 pub mod connect;
 pub mod disconnect;
+pub mod dissect;
+pub mod keep_alive;
 pub mod mqtt_writer;
+pub mod negotiate;
 pub mod packet;
+pub mod pcap;
 pub mod publish;
+pub mod retained;
+pub mod shared;
+pub mod stored;
 pub mod structure;
+#[cfg(feature = "proptest")]
+pub mod strategies;
 pub mod suback;
 pub mod subscribe;
+pub mod subscription;
+pub mod text;
+pub mod topic;
 pub mod unsuback;
 pub mod unsubscribe;
+pub mod validate;
+pub mod will;
 
 /// Library for encoding/decoding MQTTv3 and MQTTv5 messages
 /// Many more examples are currently in the test folder
@@ -66,7 +81,7 @@ pub mod unsubscribe;
 /// let packet = MqttPacket::Pingreq;
 /// assert_eq!(Ok(vec![
 ///     192, 0, // Header
-/// ]), packet.encode(5));
+/// ]), packet.encode(ProtocolVersion::V5));
 ///
 ///
 /// ```
@@ -76,10 +91,12 @@ pub mod unsubscribe;
 /// use std::io;
 /// let mut decoder = mqtt_packet_3_5::PacketDecoder::from_stream(io::Cursor::new(vec![192, 0])); // pingreq
 /// while decoder.has_more() {
-///     decoder.decode_packet(5); // will parse packets of version 5
+///     decoder.decode_packet(mqtt_packet_3_5::ProtocolVersion::V5); // will parse packets of version 5
 /// }
 ///
 ///
 /// ```
-pub use packet::{MqttPacket, PacketDecoder};
+pub use dissect::{dissect, Dissection};
+pub use packet::{DecodedPacket, MqttPacket, PacketDecoder};
 pub use structure::*;
+pub use validate::{validate, Violation};
@@ -34,42 +34,36 @@ impl MqttWriter {
                 }
                 (code, PropType::String(v)) => {
                     self.write_u8(code);
-                    self.write_utf8_string(v)
+                    self.write_utf8_string(v)?;
                 }
                 (code, PropType::Str(v)) => {
                     self.write_u8(code);
-                    self.write_utf8_str(v)
+                    self.write_utf8_str(v)?;
                 }
                 (code, PropType::Binary(v)) => {
                     self.write_u8(code);
-                    self.write_binary(v)
+                    self.write_binary(v)?;
                 }
                 (code, PropType::BinaryRef(v)) => {
                     self.write_u8(code);
-                    self.write_binary_ref(v)
+                    self.write_binary_ref(v)?;
                 }
                 // should never happen actually
                 (_, PropType::Pair(_, _)) => {}
-                // write code code and two strings for each key-value
-                // pair
+                // write code and two strings for each key-value
+                // pair in the order they were added
                 (code, PropType::Map(map)) => {
                     for (k, v) in map.into_iter() {
-                        // split into pairs
-                        for val in v {
-                            self.write_u8(code);
-                            self.write_utf8_str(&k);
-                            self.write_utf8_str(&val);
-                        }
+                        self.write_u8(code);
+                        self.write_utf8_str(&k)?;
+                        self.write_utf8_str(&v)?;
                     }
                 }
                 (code, PropType::MapRef(map)) => {
                     for (k, v) in map.iter() {
-                        // split into pairs
-                        for val in v {
-                            self.write_u8(code);
-                            self.write_utf8_str(k);
-                            self.write_utf8_str(val);
-                        }
+                        self.write_u8(code);
+                        self.write_utf8_str(k)?;
+                        self.write_utf8_str(v)?;
                     }
                 }
                 (code, PropType::VarInt(num)) => {
@@ -121,29 +115,50 @@ impl MqttWriter {
         Ok(())
     }
 
-    pub fn write_utf8_string(&mut self, s: String) {
-        self.write_u16(s.len() as u16);
-        for b in s.bytes() {
-            self.buf.push(b);
-        }
+    pub fn write_utf8_string(&mut self, s: String) -> Res<()> {
+        self.write_utf8_str(&s)
     }
 
-    pub fn write_utf8_str(&mut self, s: &str) {
-        self.write_u16(s.len() as u16);
-        for b in s.bytes() {
-            self.buf.push(b);
+    pub fn write_utf8_str(&mut self, s: &str) -> Res<()> {
+        if s.len() > u16::MAX as usize {
+            return Err(format!(
+                "String of {} bytes exceeds the maximum of {}",
+                s.len(),
+                u16::MAX
+            ));
         }
+        self.write_u16(s.len() as u16);
+        self.write_slice(s.as_bytes());
+        Ok(())
     }
 
     /// a Binary vector should never be empty
-    pub fn write_binary(&mut self, s: Vec<u8>) {
-        self.write_u16(s.len() as u16);
-        self.write_vec(s);
+    pub fn write_binary(&mut self, s: Vec<u8>) -> Res<()> {
+        self.write_binary_ref(&s)
     }
 
-    pub fn write_binary_ref(&mut self, s: &[u8]) {
+    pub fn write_binary_ref(&mut self, s: &[u8]) -> Res<()> {
+        if s.len() > u16::MAX as usize {
+            return Err(format!(
+                "Binary data of {} bytes exceeds the maximum of {}",
+                s.len(),
+                u16::MAX
+            ));
+        }
         self.write_u16(s.len() as u16);
         self.write_slice(s);
+        Ok(())
+    }
+
+    /// writes the remaining length of a packet, which has to fit into a variable byte integer
+    pub fn write_length(&mut self, length: usize) -> Res<()> {
+        if length > VARBYTEINT_MAX as usize {
+            return Err(format!(
+                "Packet length {} exceeds the maximum of {}",
+                length, VARBYTEINT_MAX
+            ));
+        }
+        self.write_variable_num(length as u32)
     }
 
     pub fn write_u16(&mut self, length: u16) {
@@ -0,0 +1,323 @@
+//! The connection handshake: the server checks the CONNECT of a client
+//! against what it supports and answers it with a CONNACK
+//! (chapter 3.1.4 and 3.2 of the MQTT 5 spec). MQTT 3 refuses a connection
+//! with a return code, MQTT 5 with a reason code and announces the
+//! capabilities of the server in the CONNACK properties, which the client
+//! checks its packets against with `NegotiatedLimits`
+use crate::structure::*;
+use crate::topic;
+
+/// longest client id a MQTT 3.1 server has to accept
+const V31_MAX_CLIENT_ID_LENGTH: usize = 23;
+
+/// What a server supports, the default supports everything except topic aliases
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ServerConfig {
+    /// versions that are accepted
+    pub protocol_versions: Vec<ProtocolVersion>,
+    pub maximum_qos: QoS,
+    pub retain_available: bool,
+    pub wildcard_subscription_available: bool,
+    pub subscription_identifiers_available: bool,
+    pub shared_subscription_available: bool,
+    /// number of topic aliases the client may use, 0 turns them off
+    pub topic_alias_maximum: u16,
+    /// number of QoS 1 and 2 PUBLISH the client may send unacknowledged
+    pub receive_maximum: u16,
+    /// None if the size of packets is only limited by the spec
+    pub maximum_packet_size: Option<u32>,
+    /// replaces the Keep Alive of MQTT 5 clients
+    pub server_keep_alive: Option<u16>,
+    /// cap of the Session Expiry Interval of MQTT 5 clients
+    pub maximum_session_expiry_interval: Option<u32>,
+    /// Authentication Methods of MQTT 5 clients that are accepted
+    pub authentication_methods: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            protocol_versions: vec![
+                ProtocolVersion::V31,
+                ProtocolVersion::V311,
+                ProtocolVersion::V5,
+            ],
+            maximum_qos: QoS::QoS2,
+            retain_available: true,
+            wildcard_subscription_available: true,
+            subscription_identifiers_available: true,
+            shared_subscription_available: true,
+            topic_alias_maximum: 0,
+            receive_maximum: 0xffff,
+            maximum_packet_size: None,
+            server_keep_alive: None,
+            maximum_session_expiry_interval: None,
+            authentication_methods: vec![],
+        }
+    }
+}
+
+/// What was agreed on for an accepted connection
+#[derive(Debug, PartialEq, Clone)]
+pub struct NegotiatedSession {
+    pub protocol_version: ProtocolVersion,
+    /// the client id of the CONNECT or the one assigned by the server
+    pub client_id: String,
+    pub assigned_client_id: bool,
+    /// Clean Session in MQTT 3, Clean Start in MQTT 5
+    pub clean_start: bool,
+    /// Seconds the session outlives the network connection, u32::MAX never
+    /// ends. In MQTT 3 it is 0 with Clean Session and u32::MAX without
+    pub session_expiry_interval: u32,
+    /// the Keep Alive in use, the Server Keep Alive if the server sent one
+    pub keep_alive: u16,
+    /// limits of the client for the PUBLISH the server sends
+    pub receive_maximum: u16,
+    pub maximum_packet_size: Option<u32>,
+    pub topic_alias_maximum: u16,
+    pub user_name: Option<String>,
+    pub will: Option<LastWill>,
+}
+
+impl ServerConfig {
+    /// Answers `connect` with the CONNACK to send. For an accepted connection
+    /// the agreed session parameters are returned as well, for a refused one
+    /// only the CONNACK, after which the network connection is closed
+    /// [MQTT-3.2.2-5]. Session Present is never set, it is up to the caller
+    /// to set it when an existing session is resumed. User name and password
+    /// as well as the AUTH exchange of an accepted Authentication Method are
+    /// left to the caller too.
+    ///
+    /// `assign_client_id` is only called for an empty client id that may be
+    /// assigned one. It has to return an id no other session uses, including
+    /// persisted ones [MQTT-3.1.3-6], None refuses the client
+    // the CONNACK of a refusal is sent as it is, boxing it gains nothing
+    #[allow(clippy::result_large_err)]
+    pub fn negotiate(
+        &self,
+        connect: &ConnectPacket,
+        assign_client_id: impl FnOnce() -> Option<String>,
+    ) -> Result<(ConnackPacket, NegotiatedSession), ConnackPacket> {
+        let version = connect.protocol_version;
+        if !self.protocol_versions.contains(&version) {
+            // 0x84 Unsupported Protocol Version
+            return Err(connack(0x84, None));
+        }
+        let (client_id, assigned_client_id) = self
+            .client_id(connect, assign_client_id)
+            // 0x85 Client Identifier not valid
+            .ok_or_else(|| connack(0x85, None))?;
+        if version != ProtocolVersion::V5 {
+            let session = NegotiatedSession {
+                protocol_version: version,
+                client_id,
+                assigned_client_id,
+                clean_start: connect.clean_session,
+                session_expiry_interval: if connect.clean_session { 0 } else { u32::MAX },
+                keep_alive: connect.keep_alive,
+                receive_maximum: 0xffff,
+                maximum_packet_size: None,
+                topic_alias_maximum: 0,
+                user_name: connect.user_name.clone(),
+                will: connect.will.clone(),
+            };
+            return Ok((connack(0x00, None), session));
+        }
+
+        let props = connect.properties.clone().unwrap_or_default();
+        if let Some(method) = &props.authentication_method {
+            if !self.authentication_methods.contains(method) {
+                // 0x8C Bad authentication method
+                return Err(connack(0x8C, None));
+            }
+        }
+        if let Some(will) = &connect.will {
+            if will.qos > self.maximum_qos {
+                // 0x9B QoS not supported [MQTT-3.2.2-12]
+                return Err(connack(0x9B, None));
+            }
+            if will.retain && !self.retain_available {
+                // 0x9A Retain not supported [MQTT-3.2.2-13]
+                return Err(connack(0x9A, None));
+            }
+        }
+
+        let requested = props.session_expiry_interval();
+        let session_expiry_interval =
+            requested.min(self.maximum_session_expiry_interval.unwrap_or(u32::MAX));
+        let connack_props = ConnackProperties {
+            session_expiry_interval: Some(session_expiry_interval)
+                .filter(|interval| *interval != requested),
+            assigned_client_identifier: Some(client_id.clone()).filter(|_| assigned_client_id),
+            server_keep_alive: self.server_keep_alive,
+            receive_maximum: Some(self.receive_maximum).filter(|max| *max != 0xffff),
+            topic_alias_maximum: Some(self.topic_alias_maximum).filter(|max| *max != 0),
+            maximum_qos: Some(self.maximum_qos).filter(|qos| *qos != QoS::QoS2),
+            retain_available: Some(false).filter(|_| !self.retain_available),
+            maximum_packet_size: self.maximum_packet_size,
+            wildcard_subscription_available: Some(false)
+                .filter(|_| !self.wildcard_subscription_available),
+            subscription_identifiers_available: Some(false)
+                .filter(|_| !self.subscription_identifiers_available),
+            shared_subscription_available: Some(false)
+                .filter(|_| !self.shared_subscription_available),
+            authentication_method: props.authentication_method.clone(),
+            ..Default::default()
+        };
+        let session = NegotiatedSession {
+            protocol_version: version,
+            client_id,
+            assigned_client_id,
+            clean_start: connect.clean_session,
+            session_expiry_interval,
+            keep_alive: self.server_keep_alive.unwrap_or(connect.keep_alive),
+            receive_maximum: props.receive_maximum(),
+            maximum_packet_size: props.maximum_packet_size,
+            topic_alias_maximum: props.topic_alias_maximum(),
+            user_name: connect.user_name.clone(),
+            will: connect.will.clone(),
+        };
+        let connack_props = Some(connack_props).filter(|props| *props != Default::default());
+        Ok((connack(0x00, connack_props), session))
+    }
+
+    /// The client id to use and whether it was assigned, None if the client
+    /// id of `connect` is refused. MQTT 3.1 requires 1 to 23 characters,
+    /// since 3.1.1 an empty client id is allowed with Clean Session
+    /// [MQTT-3.1.3-7], in MQTT 5 always [MQTT-3.1.3-6]
+    fn client_id(
+        &self,
+        connect: &ConnectPacket,
+        assign_client_id: impl FnOnce() -> Option<String>,
+    ) -> Option<(String, bool)> {
+        let version = connect.protocol_version;
+        if !connect.client_id.is_empty() {
+            let too_long = version == ProtocolVersion::V31
+                && connect.client_id.chars().count() > V31_MAX_CLIENT_ID_LENGTH;
+            return Some((connect.client_id.clone(), false)).filter(|_| !too_long);
+        }
+        if !version.allows_empty_client_id()
+            || (version == ProtocolVersion::V311 && !connect.clean_session)
+        {
+            return None;
+        }
+        Some((assign_client_id()?, true))
+    }
+}
+
+fn connack(code: u8, properties: Option<ConnackProperties>) -> ConnackPacket {
+    ConnackPacket {
+        code,
+        session_present: false,
+        properties,
+    }
+}
+
+/// What a MQTT 5 server announced in its CONNACK, for the client to check the
+/// packets it sends. A violation is answered by the server with a DISCONNECT,
+/// its reason code is what the checks return. MQTT 3 has no limits
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NegotiatedLimits {
+    pub protocol_version: ProtocolVersion,
+    pub maximum_qos: QoS,
+    pub retain_available: bool,
+    pub wildcard_subscription_available: bool,
+    pub subscription_identifiers_available: bool,
+    pub shared_subscription_available: bool,
+    pub topic_alias_maximum: u16,
+    /// None if the size of packets is only limited by the spec
+    pub maximum_packet_size: Option<u32>,
+}
+
+impl NegotiatedLimits {
+    /// the limits of the CONNACK of a connection with `protocol_version`,
+    /// absent properties allow everything except topic aliases
+    pub fn new(connack: &ConnackPacket, protocol_version: ProtocolVersion) -> NegotiatedLimits {
+        let props = connack.properties.clone().unwrap_or_default();
+        NegotiatedLimits {
+            protocol_version,
+            maximum_qos: props.maximum_qos(),
+            retain_available: props.retain_available(),
+            wildcard_subscription_available: props.wildcard_subscription_available(),
+            subscription_identifiers_available: props.subscription_identifiers_available(),
+            shared_subscription_available: props.shared_subscription_available(),
+            topic_alias_maximum: props.topic_alias_maximum(),
+            maximum_packet_size: props.maximum_packet_size,
+        }
+    }
+
+    /// Whether the server accepts `publish`: its QoS [MQTT-3.2.2-11], RETAIN
+    /// [MQTT-3.2.2-14], Topic Alias [MQTT-3.2.2-17] and size [MQTT-3.1.2-24]
+    pub fn check_publish(&self, publish: &PublishPacket) -> Result<(), DisconnectCode> {
+        if publish.qos.qos() > self.maximum_qos {
+            return Err(DisconnectCode::QoSNotSupported);
+        }
+        if publish.retain && !self.retain_available {
+            return Err(DisconnectCode::RetainNotSupported);
+        }
+        if let Some(alias) = publish.properties.as_ref().and_then(|p| p.topic_alias) {
+            if alias == 0 || alias > self.topic_alias_maximum {
+                return Err(DisconnectCode::TopicAliasInvalid);
+            }
+        }
+        self.check_size(publish)
+    }
+
+    /// `publish` made acceptable where possible: the QoS is lowered to the
+    /// Maximum QoS and RETAIN is cleared if retain is not available. Other
+    /// violations are errors like for `check_publish`
+    pub fn downgrade_publish(
+        &self,
+        mut publish: PublishPacket,
+    ) -> Result<PublishPacket, DisconnectCode> {
+        publish.qos = match publish.qos {
+            PublishQoS::QoS2(id) if self.maximum_qos == QoS::QoS1 => PublishQoS::QoS1(id),
+            qos if qos.qos() > self.maximum_qos => PublishQoS::QoS0,
+            qos => qos,
+        };
+        publish.retain = publish.retain && self.retain_available;
+        self.check_publish(&publish)?;
+        Ok(publish)
+    }
+
+    /// Whether the server accepts the subscriptions of `subscribe`: wildcards,
+    /// shared subscriptions and a Subscription Identifier need to be available
+    /// [MQTT-3.2.2-18]. A QoS above the Maximum QoS is accepted, the server
+    /// grants a lower one
+    pub fn check_subscribe(&self, subscribe: &SubscribePacket) -> Result<(), DisconnectCode> {
+        if !self.subscription_identifiers_available
+            && subscribe
+                .properties
+                .as_ref()
+                .is_some_and(|props| props.subscription_identifier.is_some())
+        {
+            return Err(DisconnectCode::SubscriptionIdentifiersNotSupported);
+        }
+        for subscription in &subscribe.subscriptions {
+            let filter = match topic::shared(&subscription.topic) {
+                Some(_) if !self.shared_subscription_available => {
+                    return Err(DisconnectCode::SharedSubscriptionsNotSupported)
+                }
+                Some((_, filter)) => filter,
+                None => &subscription.topic,
+            };
+            if !self.wildcard_subscription_available && filter.contains(['+', '#']) {
+                return Err(DisconnectCode::WildcardSubscriptionsNotSupported);
+            }
+        }
+        self.check_size(subscribe)
+    }
+
+    fn check_size<P: Packet>(&self, packet: &P) -> Result<(), DisconnectCode> {
+        let Some(maximum) = self.maximum_packet_size else {
+            return Ok(());
+        };
+        let encoded = packet
+            .encode(self.protocol_version)
+            .map_err(|_| DisconnectCode::MalformedPacket)?;
+        if encoded.len() as u64 > maximum as u64 {
+            return Err(DisconnectCode::PacketTooLarge);
+        }
+        Ok(())
+    }
+}
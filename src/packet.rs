@@ -4,18 +4,25 @@ use crate::structure::*;
 use serde::{Deserialize, Serialize};
 use std::io;
 
+/// Serialized as an object with the packet type in `type`, e.g.
+/// `{"type": "puback", "message_id": 1}`, see `JSON_SCHEMA`
 #[derive(Debug, PartialEq, Clone)]
-#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum MqttPacket {
     Connect(ConnectPacket),
     Connack(ConnackPacket),
     Subscribe(SubscribePacket),
     Suback(SubackPacket),
     Publish(PublishPacket),
-    Puback(ConfirmationPacket),
-    Pubrec(ConfirmationPacket),
-    Pubrel(ConfirmationPacket),
-    Pubcomp(ConfirmationPacket),
+    Puback(PubackPacket),
+    Pubrec(PubrecPacket),
+    Pubrel(PubrelPacket),
+    Pubcomp(PubcompPacket),
     Unsubscribe(UnsubscribePacket),
     Unsuback(UnsubackPacket),
     Pingreq,
@@ -34,7 +41,7 @@ impl MqttPacket {
     /// let packet = MqttPacket::Pingreq;
     /// assert_eq!(Ok(vec![
     ///     192, 0, // Header
-    /// ]), packet.encode(5));
+    /// ]), packet.encode(ProtocolVersion::V5));
     ///
     ///
     /// ```
@@ -43,10 +50,9 @@ impl MqttPacket {
     ///
     /// ```
     /// // need to import Packet trait in order to get .encode() to work
-    /// use mqtt_packet_3_5::{ConnectPacket, Packet, Protocol};
+    /// use mqtt_packet_3_5::{ConnectPacket, Packet, ProtocolVersion};
     /// let packet = ConnectPacket {
-    ///     protocol_id: Protocol::MQIsdp,
-    ///     protocol_version: 3,
+    ///     protocol_version: ProtocolVersion::V31,
     ///     keep_alive: 30,
     ///     clean_session: false,
     ///     user_name: None,
@@ -65,15 +71,15 @@ impl MqttPacket {
     ///     0, 4, // Client ID length
     ///     116, 101, 115, 116, // Client ID
     /// ];
-    /// assert_eq!(Ok(buf), packet.encode(3)); // encode as v3
+    /// assert_eq!(Ok(buf), packet.encode(ProtocolVersion::V31)); // encode as v3.1
     /// ```
     ///
-    pub fn encode(self, protocol_version: u8) -> Res<Vec<u8>> {
+    pub fn encode(self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
         match self {
-            MqttPacket::Puback(packet)
-            | MqttPacket::Pubrec(packet)
-            | MqttPacket::Pubrel(packet)
-            | MqttPacket::Pubcomp(packet) => packet.encode(protocol_version),
+            MqttPacket::Puback(packet) => packet.encode(protocol_version),
+            MqttPacket::Pubrec(packet) => packet.encode(protocol_version),
+            MqttPacket::Pubrel(packet) => packet.encode(protocol_version),
+            MqttPacket::Pubcomp(packet) => packet.encode(protocol_version),
             MqttPacket::Suback(packet) => packet.encode(protocol_version),
             MqttPacket::Subscribe(packet) => packet.encode(protocol_version),
             MqttPacket::Publish(packet) => packet.encode(protocol_version),
@@ -89,13 +95,25 @@ impl MqttPacket {
     }
 }
 
+/// A packet together with everything that was tolerated while decoding it
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct DecodedPacket {
+    pub packet: MqttPacket,
+    pub warnings: Vec<DecodeWarning>,
+}
+
 pub struct PacketDecoder<R: io::Read> {
     pub reader: ByteReader<R>,
+    poisoned: bool,
 }
 
 impl<R: io::Read> PacketDecoder<R> {
     pub fn new(reader: ByteReader<R>) -> PacketDecoder<R> {
-        PacketDecoder { reader }
+        PacketDecoder {
+            reader,
+            poisoned: false,
+        }
     }
 
     /// Creates a new decoder and binds it to a stream
@@ -106,7 +124,7 @@ impl<R: io::Read> PacketDecoder<R> {
     /// use std::io;
     /// let mut decoder = mqtt_packet_3_5::PacketDecoder::from_stream(io::Cursor::new(vec![192, 0])); // pingreq
     /// while decoder.has_more() {
-    ///     decoder.decode_packet(5); // will parse packets of version 5
+    ///     decoder.decode_packet(mqtt_packet_3_5::ProtocolVersion::V5); // will parse packets of version 5
     /// }
     ///
     ///
@@ -124,7 +142,7 @@ impl<R: io::Read> PacketDecoder<R> {
     /// let buf = io::BufReader::new(io::Cursor::new(vec![192, 0])); // pingreq
     /// let mut decoder = mqtt_packet_3_5::PacketDecoder::from_bufreader(buf);
     /// while decoder.has_more() {
-    ///     decoder.decode_packet(5); // will parse packets of version 5
+    ///     decoder.decode_packet(mqtt_packet_3_5::ProtocolVersion::V5); // will parse packets of version 5
     /// }
     ///
     ///
@@ -133,11 +151,33 @@ impl<R: io::Read> PacketDecoder<R> {
         PacketDecoder::new(ByteReader::new(buf))
     }
 
+    /// Sets the options used for all following packets
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io;
+    /// use mqtt_packet_3_5::{DecodeWarning, DecoderOptions, PacketDecoder, PacketType, ProtocolVersion};
+    /// // pingreq with reserved bits set
+    /// let mut decoder = PacketDecoder::from_stream(io::Cursor::new(vec![193, 0]))
+    ///     .with_options(DecoderOptions::lenient());
+    /// let decoded = decoder.decode_packet_with_warnings(ProtocolVersion::V5).unwrap();
+    /// assert_eq!(
+    ///     vec![DecodeWarning::ReservedHeaderBits { cmd: PacketType::Pingreq, flags: 1 }],
+    ///     decoded.warnings
+    /// );
+    /// ```
+    pub fn with_options(mut self, options: DecoderOptions) -> PacketDecoder<R> {
+        self.reader.set_options(options);
+        self
+    }
+
     /// Decodes MQTT messages from an underlying readable
     ///
     /// If an error happens the decoder tries to get the packet length (variable length in in position 1-4)
-    /// and discard `length` bytes. It's up to the user of this crate to close connections/streams
-    /// that deliver invalid data if necessary
+    /// and discard `length` bytes, unless `RecoveryPolicy::FailFast` is set. If the length itself can't
+    /// be read the decoder is poisoned and all later calls fail. It's up to the user of this crate to
+    /// close connections/streams that deliver invalid data if necessary, see `try_decode_packet`
     ///
     /// # Examples
     ///
@@ -156,7 +196,7 @@ impl<R: io::Read> PacketDecoder<R> {
     ///        192, 0
     /// ])); // pingreq
     /// let mut decoder = mqtt_packet_3_5::PacketDecoder::from_bufreader(buf);
-    /// let mut protocol_version = 5;
+    /// let mut protocol_version = mqtt_packet_3_5::ProtocolVersion::V5;
     /// while decoder.has_more() {
     ///     let msg = decoder.decode_packet(protocol_version); // will parse packets of version 5
     ///     // set the protocol version for a client on a stream
@@ -166,33 +206,143 @@ impl<R: io::Read> PacketDecoder<R> {
     /// }
     ///
     /// ```
-    pub fn decode_packet(&mut self, protocol_version: u8) -> Res<MqttPacket> {
-        let (length, fixed) = self.reader.read_header()?;
-        let dec = self.decode_by_type(fixed, length, protocol_version);
-        if dec.is_err() {
-            // TODO: this should probably return an Error that indicates some
-            // critical failure
-            self.reader.consume()?;
+    pub fn decode_packet(&mut self, protocol_version: ProtocolVersion) -> Res<MqttPacket> {
+        Ok(self.decode_packet_with_warnings(protocol_version)?.packet)
+    }
+
+    /// Same as decode_packet, but also returns the warnings recorded in lenient mode
+    pub fn decode_packet_with_warnings(
+        &mut self,
+        protocol_version: ProtocolVersion,
+    ) -> Res<DecodedPacket> {
+        Ok(self.try_decode_packet(protocol_version)?)
+    }
+
+    /// Same as decode_packet_with_warnings, but the error tells packets that went over
+    /// one of the `DecoderLimits` apart from malformed ones, and whether the stream
+    /// is still framed correctly after the failed packet
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io;
+    /// use mqtt_packet_3_5::*;
+    /// let options = DecoderOptions {
+    ///     limits: DecoderLimits {
+    ///         max_packet_size: 1024,
+    ///         ..DecoderLimits::default()
+    ///     },
+    ///     ..DecoderOptions::default()
+    /// };
+    /// // publish claiming to be 256MB
+    /// let mut decoder = PacketDecoder::from_stream(io::Cursor::new(vec![48, 255, 255, 255, 127]))
+    ///     .with_options(options);
+    /// let err = decoder.try_decode_packet(ProtocolVersion::V5).unwrap_err();
+    /// assert_eq!(
+    ///     DecodeErrorKind::LimitExceeded(LimitExceeded::PacketSize {
+    ///         size: 268435460,
+    ///         max: 1024
+    ///     }),
+    ///     err.kind
+    /// );
+    /// // the rest of the packet was not read, so the stream can't be used anymore
+    /// assert!(!err.framed);
+    /// assert!(decoder.is_poisoned());
+    /// ```
+    pub fn try_decode_packet(
+        &mut self,
+        protocol_version: ProtocolVersion,
+    ) -> Result<DecodedPacket, DecodeError> {
+        if self.poisoned {
+            return Err(DecodeError {
+                kind: DecodeErrorKind::Poisoned,
+                framed: false,
+                discarded: 0,
+            });
+        }
+        self.reader.take_warnings();
+        self.reader.take_limit_exceeded();
+        let start = self.reader.position();
+        let msg = match self.decode_next(protocol_version) {
+            Ok(_) if !self.reader.at_packet_end() => return Err(self.skip_trailing_bytes()),
+            Ok(packet) => return Ok(packet),
+            Err(msg) => msg,
+        };
+        let recovery = self.reader.options().recovery;
+        if recovery == RecoveryPolicy::SkipPacket {
+            // if the stream itself fails the end of the packet isn't reached,
+            // which is reported through `framed` below
+            let _ = self.reader.skip_packet();
         }
+        let framed = self.reader.position() == start || self.reader.at_packet_end();
+        if !framed || recovery == RecoveryPolicy::FailFast {
+            self.poisoned = true;
+        }
+        let kind = match self.reader.take_limit_exceeded() {
+            Some(limit) => DecodeErrorKind::LimitExceeded(limit),
+            None => DecodeErrorKind::Malformed(msg),
+        };
+        Err(DecodeError {
+            kind,
+            framed,
+            discarded: self.reader.position() - start,
+        })
+    }
+
+    fn decode_next(&mut self, protocol_version: ProtocolVersion) -> Res<DecodedPacket> {
+        let (length, fixed) = self.reader.read_header()?;
+        let packet = self.decode_by_type(fixed, length, protocol_version)?;
         self.reader.reset_limit();
-        dec
+        Ok(DecodedPacket {
+            packet,
+            warnings: self.reader.take_warnings(),
+        })
+    }
+
+    /// The fields of the packet ended before its remaining length. The rest is
+    /// discarded with every policy, otherwise it would be read as the next packet
+    fn skip_trailing_bytes(&mut self) -> DecodeError {
+        let start = self.reader.position();
+        let framed = self.reader.skip_packet().is_ok();
+        if !framed || self.reader.options().recovery == RecoveryPolicy::FailFast {
+            self.poisoned = true;
+        }
+        let discarded = self.reader.position() - start;
+        DecodeError {
+            kind: DecodeErrorKind::Malformed(format!(
+                "Unexpected {} bytes at the end of the packet",
+                discarded
+            )),
+            framed,
+            discarded,
+        }
+    }
+
+    /// true once an error stopped the decoder, see `RecoveryPolicy`
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
     }
 
     pub fn has_more(&mut self) -> bool {
-        self.reader.has_more()
+        !self.poisoned && self.reader.has_more()
     }
 
     fn decode_by_type(
         &mut self,
         fixed: FixedHeader,
         length: u32,
-        protocol_version: u8,
+        protocol_version: ProtocolVersion,
     ) -> Res<MqttPacket> {
         // let reader = self.reader.take(length);
         Ok(match fixed.cmd {
             PacketType::Connect => {
                 // passing protocol_version is unnecessary here
-                MqttPacket::Connect(ConnectPacket::decode(&mut self.reader, fixed, length, 5)?)
+                MqttPacket::Connect(ConnectPacket::decode(
+                    &mut self.reader,
+                    fixed,
+                    length,
+                    ProtocolVersion::V5,
+                )?)
             }
             PacketType::Connack => MqttPacket::Connack(ConnackPacket::decode(
                 &mut self.reader,
@@ -218,25 +368,25 @@ impl<R: io::Read> PacketDecoder<R> {
                 length,
                 protocol_version,
             )?),
-            PacketType::Puback => MqttPacket::Puback(ConfirmationPacket::decode(
+            PacketType::Puback => MqttPacket::Puback(PubackPacket::decode(
                 &mut self.reader,
                 fixed,
                 length,
                 protocol_version,
             )?),
-            PacketType::Pubrec => MqttPacket::Pubrec(ConfirmationPacket::decode(
+            PacketType::Pubrec => MqttPacket::Pubrec(PubrecPacket::decode(
                 &mut self.reader,
                 fixed,
                 length,
                 protocol_version,
             )?),
-            PacketType::Pubrel => MqttPacket::Pubrel(ConfirmationPacket::decode(
+            PacketType::Pubrel => MqttPacket::Pubrel(PubrelPacket::decode(
                 &mut self.reader,
                 fixed,
                 length,
                 protocol_version,
             )?),
-            PacketType::Pubcomp => MqttPacket::Pubcomp(ConfirmationPacket::decode(
+            PacketType::Pubcomp => MqttPacket::Pubcomp(PubcompPacket::decode(
                 &mut self.reader,
                 fixed,
                 length,
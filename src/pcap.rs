@@ -0,0 +1,531 @@
+//! Extracts MQTT conversations from pcap and pcapng captures, e.g. taken with tcpdump
+//!
+//! TCP streams to the MQTT port are reassembled and each direction is decoded with a
+//! `PacketDecoder`. The protocol version is taken from the CONNECT of the client.
+//!
+//! ```no_run
+//! use mqtt_packet_3_5::{pcap, ProtocolVersion};
+//! let capture = std::fs::read("capture.pcap").unwrap();
+//! for conversation in pcap::extract(&capture, 1883, ProtocolVersion::V5).unwrap() {
+//!     print!("{}", conversation);
+//! }
+//! ```
+use crate::packet::{MqttPacket, PacketDecoder};
+use crate::structure::*;
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Cursor;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// A captured link layer frame
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    /// time since the unix epoch
+    pub timestamp: Duration,
+    /// LINKTYPE_ value of the interface the frame was captured on
+    pub link_type: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct LogEntry {
+    /// time of the TCP segment that completed the packet
+    pub timestamp: Duration,
+    pub direction: Direction,
+    /// the decoded packet, or why the stream could not be decoded from here on
+    pub packet: Result<MqttPacket, String>,
+}
+
+/// All packets of one TCP connection, in the order they were completed
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct Conversation {
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+    /// version announced in the CONNECT, None if the capture doesn't contain it
+    pub version: Option<ProtocolVersion>,
+    pub entries: Vec<LogEntry>,
+}
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const TCP_SYN: u8 = 0x02;
+const TCP_ACK: u8 = 0x10;
+
+/// Reads the conversations with a server on port from a pcap or pcapng capture.
+/// default_version is used for connections whose CONNECT was not captured
+pub fn extract(
+    capture: &[u8],
+    port: u16,
+    default_version: ProtocolVersion,
+) -> Res<Vec<Conversation>> {
+    let mut connections: Vec<Connection> = vec![];
+    let mut open: HashMap<(SocketAddr, SocketAddr), usize> = HashMap::new();
+    for frame in read_frames(capture)? {
+        let segment = match parse_frame(&frame) {
+            Some(segment) => segment,
+            None => continue,
+        };
+        let (client, server, direction) = if segment.dst.port() == port {
+            (segment.src, segment.dst, Direction::ClientToServer)
+        } else if segment.src.port() == port {
+            (segment.dst, segment.src, Direction::ServerToClient)
+        } else {
+            continue;
+        };
+        let is_connect = segment.flags & (TCP_SYN | TCP_ACK) == TCP_SYN;
+        let index = match open.get(&(client, server)) {
+            // a new SYN on the same addresses is a new connection
+            Some(&index) if !(is_connect && connections[index].client.has_data()) => index,
+            _ => {
+                connections.push(Connection::new(client, server));
+                open.insert((client, server), connections.len() - 1);
+                connections.len() - 1
+            }
+        };
+        let connection = &mut connections[index];
+        let half = match direction {
+            Direction::ClientToServer => &mut connection.client,
+            Direction::ServerToClient => &mut connection.server,
+        };
+        half.add(&segment, frame.timestamp);
+    }
+    Ok(connections
+        .into_iter()
+        .map(|c| c.decode(default_version))
+        .collect())
+}
+
+struct Connection {
+    client_addr: SocketAddr,
+    server_addr: SocketAddr,
+    client: HalfStream,
+    server: HalfStream,
+}
+
+impl Connection {
+    fn new(client_addr: SocketAddr, server_addr: SocketAddr) -> Connection {
+        Connection {
+            client_addr,
+            server_addr,
+            client: HalfStream::default(),
+            server: HalfStream::default(),
+        }
+    }
+
+    fn decode(self, default_version: ProtocolVersion) -> Conversation {
+        let (mut entries, version) =
+            self.client
+                .decode(Direction::ClientToServer, default_version, true);
+        let (server_entries, _) = self.server.decode(
+            Direction::ServerToClient,
+            version.unwrap_or(default_version),
+            false,
+        );
+        entries.extend(server_entries);
+        // stable, so packets completed by the same segment keep their order
+        entries.sort_by_key(|e| e.timestamp);
+        Conversation {
+            client: self.client_addr,
+            server: self.server_addr,
+            version,
+            entries,
+        }
+    }
+}
+
+/// The segments sent in one direction of a TCP connection
+#[derive(Default)]
+struct HalfStream {
+    /// sequence number of the first data byte
+    start: Option<u32>,
+    /// offset from start, capture time and payload
+    segments: Vec<(u64, Duration, Vec<u8>)>,
+}
+
+impl HalfStream {
+    fn has_data(&self) -> bool {
+        !self.segments.is_empty()
+    }
+
+    fn add(&mut self, segment: &Segment, timestamp: Duration) {
+        if segment.flags & TCP_SYN != 0 {
+            self.start = Some(segment.seq.wrapping_add(1));
+            return;
+        }
+        if segment.payload.is_empty() {
+            return;
+        }
+        let start = *self.start.get_or_insert(segment.seq);
+        let offset = segment.seq.wrapping_sub(start);
+        // data from before the first captured segment
+        if offset > u32::MAX / 2 {
+            return;
+        }
+        self.segments
+            .push((offset as u64, timestamp, segment.payload.to_vec()));
+    }
+
+    /// the contiguous bytes of the stream, the time at which each chunk of them
+    /// was available and the number of bytes missing after them
+    fn reassemble(mut self) -> (Vec<u8>, Vec<(usize, Duration)>, Option<u64>) {
+        self.segments.sort_by_key(|(offset, _, _)| *offset);
+        let mut bytes: Vec<u8> = vec![];
+        let mut chunks = vec![];
+        let mut available = Duration::ZERO;
+        for (offset, timestamp, data) in self.segments {
+            let len = bytes.len() as u64;
+            if offset > len {
+                return (bytes, chunks, Some(offset - len));
+            }
+            let end = offset + data.len() as u64;
+            // retransmission
+            if end <= len {
+                continue;
+            }
+            available = available.max(timestamp);
+            chunks.push((bytes.len(), available));
+            bytes.extend(&data[(len - offset) as usize..]);
+        }
+        (bytes, chunks, None)
+    }
+
+    /// decodes all packets, switching to the version of a CONNECT if detect is set
+    fn decode(
+        self,
+        direction: Direction,
+        mut version: ProtocolVersion,
+        detect: bool,
+    ) -> (Vec<LogEntry>, Option<ProtocolVersion>) {
+        let (bytes, chunks, missing) = self.reassemble();
+        // the time at which the byte at offset was captured
+        let time_at = |offset: usize| {
+            let i = chunks.partition_point(|(start, _)| *start <= offset);
+            chunks[i.saturating_sub(1)].1
+        };
+        let mut entries = vec![];
+        let mut detected = None;
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(&bytes[..]));
+        while decoder.has_more() {
+            let res = decoder.try_decode_packet(version);
+            let end = decoder.reader.position() as usize;
+            let packet = match res {
+                Ok(decoded) => {
+                    if let (true, MqttPacket::Connect(connect)) = (detect, &decoded.packet) {
+                        version = connect.protocol_version;
+                        detected = Some(version);
+                    }
+                    Ok(decoded.packet)
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            entries.push(LogEntry {
+                timestamp: time_at(end.saturating_sub(1)),
+                direction,
+                packet,
+            });
+        }
+        if let Some(missing) = missing {
+            entries.push(LogEntry {
+                timestamp: chunks.last().map(|c| c.1).unwrap_or_default(),
+                direction,
+                packet: Err(format!("{} bytes are missing from the capture", missing)),
+            });
+        }
+        (entries, detected)
+    }
+}
+
+/// Reads all frames of a pcap or pcapng capture
+pub fn read_frames(capture: &[u8]) -> Res<Vec<Frame>> {
+    if capture.len() < 4 {
+        return Err("Capture is too short".to_string());
+    }
+    match capture[0..4] {
+        [0x0A, 0x0D, 0x0D, 0x0A] => read_pcapng(capture),
+        _ => read_pcap(capture),
+    }
+}
+
+/// Reads integers in the byte order of the capture
+#[derive(Clone, Copy)]
+struct Endian {
+    big: bool,
+}
+
+impl Endian {
+    fn u16(&self, data: &[u8], at: usize) -> Res<u16> {
+        let b: [u8; 2] = data
+            .get(at..at + 2)
+            .and_then(|b| b.try_into().ok())
+            .ok_or("Capture is truncated")?;
+        Ok(match self.big {
+            true => u16::from_be_bytes(b),
+            false => u16::from_le_bytes(b),
+        })
+    }
+
+    fn u32(&self, data: &[u8], at: usize) -> Res<u32> {
+        let b: [u8; 4] = data
+            .get(at..at + 4)
+            .and_then(|b| b.try_into().ok())
+            .ok_or("Capture is truncated")?;
+        Ok(match self.big {
+            true => u32::from_be_bytes(b),
+            false => u32::from_le_bytes(b),
+        })
+    }
+}
+
+fn slice(data: &[u8], start: usize, len: usize) -> Res<&[u8]> {
+    data.get(start..start.saturating_add(len))
+        .ok_or_else(|| "Capture is truncated".to_string())
+}
+
+fn read_pcap(capture: &[u8]) -> Res<Vec<Frame>> {
+    let magic = u32::from_le_bytes([capture[0], capture[1], capture[2], capture[3]]);
+    let (endian, nanos) = match magic {
+        0xa1b2c3d4 => (Endian { big: false }, false),
+        0xa1b23c4d => (Endian { big: false }, true),
+        0xd4c3b2a1 => (Endian { big: true }, false),
+        0x4d3cb2a1 => (Endian { big: true }, true),
+        _ => return Err("Not a pcap or pcapng capture".to_string()),
+    };
+    let link_type = endian.u32(capture, 20)? & 0x0FFF_FFFF;
+    let mut frames = vec![];
+    let mut pos = 24;
+    while pos < capture.len() {
+        let secs = endian.u32(capture, pos)? as u64;
+        let frac = endian.u32(capture, pos + 4)?;
+        let len = endian.u32(capture, pos + 8)? as usize;
+        let timestamp = match nanos {
+            true => Duration::new(secs, frac),
+            false => Duration::new(secs, 0) + Duration::from_micros(frac as u64),
+        };
+        frames.push(Frame {
+            timestamp,
+            link_type,
+            data: slice(capture, pos + 16, len)?.to_vec(),
+        });
+        pos += 16 + len;
+    }
+    Ok(frames)
+}
+
+/// link type and timestamp resolution of a pcapng interface
+struct Interface {
+    link_type: u32,
+    /// ticks per second
+    resolution: u64,
+}
+
+fn read_pcapng(capture: &[u8]) -> Res<Vec<Frame>> {
+    let mut endian = Endian { big: false };
+    let mut interfaces: Vec<Interface> = vec![];
+    let mut frames = vec![];
+    let mut pos = 0;
+    while pos < capture.len() {
+        let block_type = endian.u32(capture, pos)?;
+        if block_type == 0x0A0D0D0A {
+            // section header, which sets the byte order of the section
+            endian.big = match slice(capture, pos + 8, 4)? {
+                [0x1A, 0x2B, 0x3C, 0x4D] => true,
+                [0x4D, 0x3C, 0x2B, 0x1A] => false,
+                _ => return Err("Invalid pcapng byte order magic".to_string()),
+            };
+            interfaces.clear();
+        }
+        let len = endian.u32(capture, pos + 4)? as usize;
+        if len < 12 || !len.is_multiple_of(4) {
+            return Err(format!("Invalid pcapng block length {}", len));
+        }
+        let body = slice(capture, pos + 8, len - 12)?;
+        match block_type {
+            // interface description
+            1 => interfaces.push(Interface {
+                link_type: endian.u16(body, 0)? as u32,
+                resolution: interface_resolution(endian, body)?,
+            }),
+            // enhanced packet
+            6 => {
+                let interface = endian.u32(body, 0)? as usize;
+                let interface = interfaces
+                    .get(interface)
+                    .ok_or_else(|| format!("Unknown pcapng interface {}", interface))?;
+                let ticks = ((endian.u32(body, 4)? as u64) << 32) + endian.u32(body, 8)? as u64;
+                let captured = endian.u32(body, 12)? as usize;
+                let timestamp = Duration::from_secs(ticks / interface.resolution)
+                    + Duration::from_nanos(
+                        ((ticks % interface.resolution) as u128 * 1_000_000_000
+                            / interface.resolution as u128) as u64,
+                    );
+                frames.push(Frame {
+                    timestamp,
+                    link_type: interface.link_type,
+                    data: slice(body, 20, captured)?.to_vec(),
+                });
+            }
+            _ => {}
+        }
+        pos += len;
+    }
+    Ok(frames)
+}
+
+/// reads the if_tsresol option of an interface description block
+fn interface_resolution(endian: Endian, body: &[u8]) -> Res<u64> {
+    let mut pos = 8;
+    while pos + 4 <= body.len() {
+        let code = endian.u16(body, pos)?;
+        let len = endian.u16(body, pos + 2)? as usize;
+        if code == 0 {
+            break;
+        }
+        if code == 9 && len == 1 {
+            let tsresol = slice(body, pos + 4, 1)?[0];
+            let exponent = (tsresol & 0x7F) as u32;
+            let base: u64 = if tsresol & 0x80 == 0 { 10 } else { 2 };
+            return base
+                .checked_pow(exponent)
+                .ok_or_else(|| format!("Invalid pcapng timestamp resolution {}", tsresol));
+        }
+        pos += 4 + len.div_ceil(4) * 4;
+    }
+    Ok(1_000_000)
+}
+
+struct Segment<'a> {
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    flags: u8,
+    payload: &'a [u8],
+}
+
+/// the TCP segment in frame, None for anything else
+fn parse_frame(frame: &Frame) -> Option<Segment<'_>> {
+    let data = &frame.data[..];
+    let ip = match frame.link_type {
+        LINKTYPE_ETHERNET => {
+            let mut pos = 12;
+            // VLAN tags
+            while matches!(data.get(pos..pos + 2)?, [0x81, 0x00] | [0x88, 0xA8]) {
+                pos += 4;
+            }
+            match data.get(pos..pos + 2)? {
+                [0x08, 0x00] | [0x86, 0xDD] => &data[pos + 2..],
+                _ => return None,
+            }
+        }
+        LINKTYPE_NULL | LINKTYPE_LOOP => data.get(4..)?,
+        LINKTYPE_LINUX_SLL => data.get(16..)?,
+        LINKTYPE_LINUX_SLL2 => data.get(20..)?,
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => data,
+        _ => return None,
+    };
+    let (src, dst, tcp) = match ip.first()? >> 4 {
+        4 => parse_ipv4(ip)?,
+        6 => parse_ipv6(ip)?,
+        _ => return None,
+    };
+    let offset = ((tcp.get(12)? >> 4) as usize) * 4;
+    Some(Segment {
+        src: SocketAddr::new(src, u16::from_be_bytes([tcp[0], tcp[1]])),
+        dst: SocketAddr::new(dst, u16::from_be_bytes([tcp[2], tcp[3]])),
+        seq: u32::from_be_bytes([tcp[4], tcp[5], tcp[6], tcp[7]]),
+        flags: *tcp.get(13)?,
+        payload: tcp.get(offset..)?,
+    })
+}
+
+fn parse_ipv4(ip: &[u8]) -> Option<(IpAddr, IpAddr, &[u8])> {
+    let header = ((ip[0] & 0x0F) as usize) * 4;
+    let total = u16::from_be_bytes([*ip.get(2)?, *ip.get(3)?]) as usize;
+    // fragments are not reassembled
+    let fragment = u16::from_be_bytes([*ip.get(6)?, *ip.get(7)?]);
+    if *ip.get(9)? != 6 || fragment & 0x3FFF != 0 {
+        return None;
+    }
+    let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+    let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+    // total excludes ethernet padding
+    let tcp = ip.get(header..total.min(ip.len()))?;
+    Some((Ipv4Addr::from(src).into(), Ipv4Addr::from(dst).into(), tcp))
+}
+
+fn parse_ipv6(ip: &[u8]) -> Option<(IpAddr, IpAddr, &[u8])> {
+    let payload = u16::from_be_bytes([*ip.get(4)?, *ip.get(5)?]) as usize;
+    let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+    let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+    let end = (40 + payload).min(ip.len());
+    let mut next = *ip.get(6)?;
+    let mut pos = 40;
+    // hop-by-hop, routing and destination options
+    while matches!(next, 0 | 43 | 60) {
+        next = *ip.get(pos)?;
+        pos += (*ip.get(pos + 1)? as usize + 1) * 8;
+    }
+    if next != 6 {
+        return None;
+    }
+    let tcp = ip.get(pos..end)?;
+    Some((Ipv6Addr::from(src).into(), Ipv6Addr::from(dst).into(), tcp))
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Direction::ClientToServer => "client",
+            Direction::ServerToClient => "server",
+        })
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:06} {} ",
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_micros(),
+            self.direction
+        )?;
+        match &self.packet {
+            Ok(packet) => write!(f, "{:?}", packet),
+            Err(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+/// A header line for the connection followed by one line per packet
+impl fmt::Display for Conversation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.client, self.server)?;
+        match self.version {
+            Some(version) => writeln!(f, " MQTT {}", version)?,
+            None => writeln!(f, " (no CONNECT)")?,
+        }
+        for entry in self.entries.iter() {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
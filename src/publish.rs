@@ -9,29 +9,31 @@ impl Packet for PublishPacket {
         reader: &mut ByteReader<R>,
         fixed: FixedHeader,
         _: u32,
-        protocol_version: u8,
+        protocol_version: ProtocolVersion,
     ) -> Res<PublishPacket> {
-        let topic = reader.read_utf8_string()?;
+        let topic = reader.read_topic()?;
+
+        // Parse messageId
+        let qos = match fixed.qos {
+            QoS::QoS0 => PublishQoS::QoS0,
+            QoS::QoS1 => PublishQoS::QoS1(reader.read_u16()?),
+            QoS::QoS2 => PublishQoS::QoS2(reader.read_u16()?),
+        };
 
         let mut packet = PublishPacket {
             dup: fixed.dup,
-            qos: fixed.qos,
+            qos,
             retain: fixed.retain,
             topic,
             properties: None,
             payload: vec![],
-            message_id: None,
         };
-        // Parse messageId
-        if fixed.qos > 0 {
-            packet.message_id = Some(reader.read_u16()?);
-        }
 
         // Properties mqtt 5
-        if protocol_version == 5 {
+        if protocol_version.has_properties() {
             packet.properties = match reader.read_properties()? {
                 None => None,
-                Some(props) => Some(PublishProperties::from_properties(props)?),
+                Some(props) => Some(PublishProperties::decode_properties(props)?),
             };
         }
 
@@ -40,12 +42,11 @@ impl Packet for PublishPacket {
         Ok(packet)
     }
 
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>> {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
         let mut length = 0;
         let PublishPacket {
             topic,
             qos,
-            message_id,
             properties,
             payload,
             dup,
@@ -61,11 +62,8 @@ impl Packet for PublishPacket {
         // Get the payload length
         length += payload.len();
 
-        // Message ID must a number if qos > 0
-        if *qos > 0 {
-            if message_id.is_none() {
-                return Err("Invalid messageId".to_string());
-            }
+        // Message ID is present if qos > 0
+        if qos.message_id().is_some() {
             length += 2;
         }
 
@@ -77,20 +75,20 @@ impl Packet for PublishPacket {
         // Header
         writer.write_header(FixedHeader {
             cmd: PacketType::Publish,
-            qos: *qos,
+            qos: qos.qos(),
             dup: *dup,
             retain: *retain,
         });
 
         // Remaining length
-        writer.write_variable_num(length as u32)?;
+        writer.write_length(length)?;
 
         // Topic
-        writer.write_utf8_str(topic);
+        writer.write_utf8_str(topic)?;
 
         // Message ID
-        if *qos > 0 {
-            writer.write_u16(message_id.unwrap());
+        if let Some(message_id) = qos.message_id() {
+            writer.write_u16(message_id);
         }
 
         // Properties
@@ -0,0 +1,120 @@
+//! Retained messages (chapter 3.3.1.3 of the MQTT 5 spec). A broker keeps the
+//! last PUBLISH with the RETAIN flag of every topic and sends the matching ones
+//! to a client when it subscribes, as directed by the Retain Handling option.
+//!
+//! The store is sans-I/O, the current time comes from the `Clock` passed in
+//! by the caller. Retained messages expire like any other `StoredMessage`
+use crate::clock::Clock;
+use crate::stored::StoredMessage;
+use crate::structure::*;
+use crate::subscription::forward;
+use crate::topic;
+use std::collections::BTreeMap;
+
+/// Storage of retained messages, one per topic
+pub trait RetainedStore {
+    /// stores the retained message of its topic, returns the one it replaces
+    fn insert(&mut self, message: StoredMessage) -> Option<StoredMessage>;
+    fn remove(&mut self, topic: &str) -> Option<StoredMessage>;
+    fn get(&self, topic: &str) -> Option<&StoredMessage>;
+    /// all messages whose topic matches `filter`, which must not be a shared
+    /// subscription, ordered by topic
+    fn matching(&self, filter: &str) -> Vec<&StoredMessage>;
+    /// removes the expired messages and returns their number
+    fn remove_expired(&mut self, clock: &dyn Clock) -> usize;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Handles a received PUBLISH: with the RETAIN flag it replaces the
+    /// retained message of the topic [MQTT-3.3.1-5], or deletes it if the
+    /// payload is empty [MQTT-3.3.1-6]. Returns whether the store changed
+    fn publish(&mut self, publish: &PublishPacket, clock: &dyn Clock) -> bool {
+        if !publish.retain {
+            false
+        } else if publish.payload.is_empty() {
+            self.remove(&publish.topic).is_some()
+        } else {
+            let mut publish = publish.clone();
+            publish.dup = false;
+            self.insert(StoredMessage::new(publish, clock));
+            true
+        }
+    }
+
+    /// Retained messages to send for a subscription of a SUBSCRIBE, `exists`
+    /// tells whether the session already had a subscription with the same
+    /// filter. Retain Handling 0 sends them, 1 only for a new subscription and
+    /// 2 never [MQTT-3.3.1-9], [MQTT-3.3.1-10], [MQTT-3.3.1-11]. They are sent
+    /// with RETAIN set, but not for shared subscriptions (chapter 3.3.1.3)
+    fn subscribe(
+        &self,
+        subscription: &Subscription,
+        exists: bool,
+        clock: &dyn Clock,
+    ) -> Vec<PublishPacket> {
+        let send = match subscription.rh {
+            RetainHandling::SendAtSubscribe => true,
+            RetainHandling::SendAtSubscribeIfNew => !exists,
+            RetainHandling::DoNotSend => false,
+        };
+        if !send || topic::shared(&subscription.topic).is_some() {
+            return vec![];
+        }
+        self.matching(&subscription.topic)
+            .into_iter()
+            .filter_map(|message| message.forward(clock))
+            .map(|publish| {
+                let mut publish = forward(&publish, subscription);
+                publish.retain = true;
+                publish
+            })
+            .collect()
+    }
+}
+
+/// In-memory `RetainedStore`, lookups go through all stored topics
+#[derive(Debug, Default)]
+pub struct MemoryRetainedStore {
+    messages: BTreeMap<String, StoredMessage>,
+}
+
+impl MemoryRetainedStore {
+    pub fn new() -> MemoryRetainedStore {
+        MemoryRetainedStore::default()
+    }
+}
+
+impl RetainedStore for MemoryRetainedStore {
+    fn insert(&mut self, message: StoredMessage) -> Option<StoredMessage> {
+        self.messages.insert(message.publish.topic.clone(), message)
+    }
+
+    fn remove(&mut self, topic: &str) -> Option<StoredMessage> {
+        self.messages.remove(topic)
+    }
+
+    fn get(&self, topic: &str) -> Option<&StoredMessage> {
+        self.messages.get(topic)
+    }
+
+    fn matching(&self, filter: &str) -> Vec<&StoredMessage> {
+        self.messages
+            .iter()
+            .filter(|(topic, _)| topic::matches(filter, topic))
+            .map(|(_, message)| message)
+            .collect()
+    }
+
+    fn remove_expired(&mut self, clock: &dyn Clock) -> usize {
+        let before = self.messages.len();
+        self.messages.retain(|_, m| !m.is_expired(clock));
+        before - self.messages.len()
+    }
+
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+}
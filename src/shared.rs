@@ -0,0 +1,359 @@
+//! Shared subscriptions (chapter 4.8.2 of the MQTT 5 spec). Every PUBLISH
+//! matching a `$share/{group}/{filter}` subscription is sent to only one of
+//! the clients subscribed to it, the members of the group.
+//!
+//! `SharedGroupDispatcher` chooses the member with a `Strategy` and tracks the
+//! QoS 1 and 2 deliveries until they are acknowledged, so that a QoS 1 message
+//! can be sent to another member when its delivery fails. A QoS 2 message has
+//! to be completed by the member it was sent to and is never moved
+use crate::structure::*;
+use crate::subscription::forward;
+use crate::topic;
+use std::collections::{BTreeMap, HashMap};
+
+/// How the member receiving a message is chosen
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Strategy {
+    /// each member in turn
+    RoundRobin,
+    /// a random member, the sequence is determined by the seed
+    Random { seed: u64 },
+    /// all messages of a topic go to the same member for as long as it is
+    /// connected, new topics are assigned round robin
+    StickyByTopic,
+    /// the member with the fewest unacknowledged deliveries, round robin
+    /// among those with the same number
+    LeastInflight,
+}
+
+/// A PUBLISH to send to one member of a group
+#[derive(Debug, PartialEq, Clone)]
+pub struct Delivery {
+    /// identifies the delivery for `completed`, `failed` and `rejected`
+    pub id: u64,
+    pub client_id: String,
+    /// the shared subscription, `$share/{group}/{filter}`
+    pub filter: String,
+    /// the PUBLISH with the QoS and RETAIN of the subscription of the member
+    pub publish: PublishPacket,
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+    client_id: String,
+    subscription: Subscription,
+    connected: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Group {
+    members: Vec<Member>,
+    /// round robin position, the index of the next member
+    next: usize,
+    /// topic -> client id for `Strategy::StickyByTopic`
+    sticky: HashMap<String, String>,
+}
+
+/// a QoS 1 or 2 delivery that is not acknowledged yet
+#[derive(Debug, Clone)]
+struct Inflight {
+    client_id: String,
+    filter: String,
+    /// QoS of the delivery, the subscription may have lowered it
+    qos: QoS,
+    /// the PUBLISH as it arrived, to forward it to another member
+    publish: PublishPacket,
+}
+
+/// Chooses the receiving member of the shared subscriptions a PUBLISH matches
+#[derive(Debug, Clone)]
+pub struct SharedGroupDispatcher {
+    strategy: Strategy,
+    /// by shared subscription
+    groups: BTreeMap<String, Group>,
+    inflight: BTreeMap<u64, Inflight>,
+    next_id: u64,
+    /// state of the random number generator
+    random: u64,
+}
+
+impl SharedGroupDispatcher {
+    pub fn new(strategy: Strategy) -> SharedGroupDispatcher {
+        let random = match strategy {
+            Strategy::Random { seed } => seed,
+            _ => 0,
+        };
+        SharedGroupDispatcher {
+            strategy,
+            groups: BTreeMap::new(),
+            inflight: BTreeMap::new(),
+            next_id: 1,
+            random,
+        }
+    }
+
+    /// Adds the connected client to the group of a shared subscription, a
+    /// subscription of a member replaces its previous one [MQTT-3.8.4-3].
+    /// Fails for other subscriptions
+    pub fn subscribe(&mut self, client_id: &str, subscription: &Subscription) -> Res<()> {
+        if topic::shared(&subscription.topic).is_none() {
+            return Err(format!("Not a shared subscription: {}", subscription.topic));
+        }
+        let group = self.groups.entry(subscription.topic.clone()).or_default();
+        match group.members.iter_mut().find(|m| m.client_id == client_id) {
+            Some(member) => {
+                member.subscription = subscription.clone();
+                member.connected = true;
+            }
+            None => group.members.push(Member {
+                client_id: client_id.to_string(),
+                subscription: subscription.clone(),
+                connected: true,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Removes the client from the group of `filter`, returns whether it was
+    /// a member. Messages already sent to it stay with it [MQTT-3.10.4-3]
+    pub fn unsubscribe(&mut self, client_id: &str, filter: &str) -> bool {
+        let Some(group) = self.groups.get_mut(filter) else {
+            return false;
+        };
+        let before = group.members.len();
+        group.members.retain(|m| m.client_id != client_id);
+        group.sticky.retain(|_, member| member != client_id);
+        let removed = group.members.len() != before;
+        if group.members.is_empty() {
+            self.groups.remove(filter);
+        }
+        removed
+    }
+
+    /// The network connection of the client was closed. It receives no more
+    /// messages while other members are connected and its unacknowledged
+    /// QoS 1 messages are sent to other members. A member that is not
+    /// connected is only chosen if no member is, the message then waits in
+    /// its session
+    pub fn disconnected(&mut self, client_id: &str) -> Vec<Delivery> {
+        for group in self.groups.values_mut() {
+            for member in group.members.iter_mut() {
+                if member.client_id == client_id {
+                    member.connected = false;
+                }
+            }
+            group.sticky.retain(|_, member| member != client_id);
+        }
+        let ids = self.inflight_of(client_id);
+        self.redeliver(ids)
+    }
+
+    /// The client connected again and resumed its session
+    pub fn connected(&mut self, client_id: &str) {
+        for group in self.groups.values_mut() {
+            for member in group.members.iter_mut() {
+                if member.client_id == client_id {
+                    member.connected = true;
+                }
+            }
+        }
+    }
+
+    /// The session of the client ended, it leaves all groups. Its
+    /// unacknowledged QoS 1 messages are sent to other members, the QoS 2
+    /// ones are dropped since they can't go anywhere else
+    pub fn session_ended(&mut self, client_id: &str) -> Vec<Delivery> {
+        let filters: Vec<String> = self.groups.keys().cloned().collect();
+        for filter in filters {
+            self.unsubscribe(client_id, &filter);
+        }
+        let ids = self.inflight_of(client_id);
+        let deliveries = self.redeliver(ids);
+        self.inflight.retain(|_, i| i.client_id != client_id);
+        deliveries
+    }
+
+    /// one delivery for every shared subscription matching the topic of
+    /// `publish`, ordered by subscription
+    pub fn dispatch(&mut self, publish: &PublishPacket) -> Vec<Delivery> {
+        let filters: Vec<String> = self
+            .groups
+            .keys()
+            .filter(|filter| {
+                topic::shared(filter)
+                    .is_some_and(|(_, filter)| topic::matches(filter, &publish.topic))
+            })
+            .cloned()
+            .collect();
+        filters
+            .into_iter()
+            .filter_map(|filter| self.deliver(&filter, publish, None))
+            .collect()
+    }
+
+    /// The delivery was acknowledged, by PUBACK for QoS 1 and PUBCOMP for
+    /// QoS 2. Returns false for unknown or QoS 0 deliveries
+    pub fn completed(&mut self, id: u64) -> bool {
+        self.inflight.remove(&id).is_some()
+    }
+
+    /// The delivery could not be made, the connection was lost or it timed
+    /// out. A QoS 1 message is sent to another member, if there is one, while
+    /// a QoS 2 message stays with its member
+    pub fn failed(&mut self, id: u64) -> Option<Delivery> {
+        self.redeliver(vec![id]).pop()
+    }
+
+    /// The member refused the delivery with a PUBACK or PUBREC reason code of
+    /// 0x80 or above. The message is discarded, it is not sent to another
+    /// member [MQTT-4.8.2-6]. Returns false for unknown or QoS 0 deliveries
+    pub fn rejected(&mut self, id: u64) -> bool {
+        self.inflight.remove(&id).is_some()
+    }
+
+    /// number of deliveries to the client that are not acknowledged yet
+    pub fn inflight(&self, client_id: &str) -> usize {
+        self.inflight
+            .values()
+            .filter(|inflight| inflight.client_id == client_id)
+            .count()
+    }
+
+    /// the members of the shared subscription `filter` in the order they subscribed
+    pub fn members(&self, filter: &str) -> Vec<&str> {
+        self.groups.get(filter).map_or(vec![], |group| {
+            group.members.iter().map(|m| m.client_id.as_str()).collect()
+        })
+    }
+
+    fn inflight_of(&self, client_id: &str) -> Vec<u64> {
+        self.inflight
+            .iter()
+            .filter(|(_, inflight)| inflight.client_id == client_id)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// sends the QoS 1 deliveries `ids` to other members
+    fn redeliver(&mut self, ids: Vec<u64>) -> Vec<Delivery> {
+        let mut deliveries = vec![];
+        for id in ids {
+            let Some(inflight) = self
+                .inflight
+                .get(&id)
+                .filter(|inflight| inflight.qos == QoS::QoS1)
+                .cloned()
+            else {
+                continue;
+            };
+            // nobody else to send it to keeps it with the member
+            if let Some(delivery) = self.deliver(
+                &inflight.filter,
+                &inflight.publish,
+                Some(&inflight.client_id),
+            ) {
+                self.inflight.remove(&id);
+                deliveries.push(delivery);
+            }
+        }
+        deliveries
+    }
+
+    /// sends `publish` to a member of the group of `filter` other than `except`
+    fn deliver(
+        &mut self,
+        filter: &str,
+        publish: &PublishPacket,
+        except: Option<&str>,
+    ) -> Option<Delivery> {
+        let index = self.choose(filter, &publish.topic, except)?;
+        let member = &self.groups[filter].members[index];
+        let client_id = member.client_id.clone();
+        let forwarded = forward(publish, &member.subscription);
+        let id = self.next_id;
+        self.next_id += 1;
+        if forwarded.qos != PublishQoS::QoS0 {
+            self.inflight.insert(
+                id,
+                Inflight {
+                    client_id: client_id.clone(),
+                    filter: filter.to_string(),
+                    qos: forwarded.qos.qos(),
+                    publish: publish.clone(),
+                },
+            );
+        }
+        Some(Delivery {
+            id,
+            client_id,
+            filter: filter.to_string(),
+            publish: forwarded,
+        })
+    }
+
+    /// index of the member of the group of `filter` that receives a message
+    /// of `topic`, preferring connected members
+    fn choose(&mut self, filter: &str, topic: &str, except: Option<&str>) -> Option<usize> {
+        let group = self.groups.get(filter)?;
+        let eligible: Vec<usize> = (0..group.members.len())
+            .filter(|i| except.is_none_or(|c| group.members[*i].client_id != c))
+            .collect();
+        let connected: Vec<usize> = eligible
+            .iter()
+            .copied()
+            .filter(|i| group.members[*i].connected)
+            .collect();
+        let candidates = if connected.is_empty() {
+            eligible
+        } else {
+            connected
+        };
+        if candidates.is_empty() {
+            return None;
+        }
+        // candidates in round robin order, starting at the next member
+        let mut ordered = candidates;
+        ordered.sort_by_key(|i| (*i < group.next, *i));
+        let sticky = group.sticky.get(topic).and_then(|client_id| {
+            ordered
+                .iter()
+                .copied()
+                .find(|i| group.members[*i].client_id == *client_id)
+        });
+        let least_inflight = ordered
+            .iter()
+            .copied()
+            .min_by_key(|i| self.inflight(&group.members[*i].client_id));
+
+        let (index, advance) = match self.strategy {
+            Strategy::RoundRobin => (ordered[0], true),
+            Strategy::Random { .. } => {
+                let random = self.next_random() % ordered.len() as u64;
+                (ordered[random as usize], false)
+            }
+            Strategy::StickyByTopic => match sticky {
+                Some(index) => (index, false),
+                None => (ordered[0], true),
+            },
+            Strategy::LeastInflight => (least_inflight?, true),
+        };
+        let group = self.groups.get_mut(filter)?;
+        if advance {
+            group.next = index + 1;
+        }
+        if self.strategy == Strategy::StickyByTopic {
+            let client_id = group.members[index].client_id.clone();
+            group.sticky.insert(topic.to_string(), client_id);
+        }
+        Some(index)
+    }
+
+    /// splitmix64
+    fn next_random(&mut self) -> u64 {
+        self.random = self.random.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.random;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
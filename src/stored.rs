@@ -0,0 +1,105 @@
+//! Messages a broker keeps before forwarding them, i.e. retained messages and
+//! the queue of a disconnected session. The Message Expiry Interval counts
+//! from the arrival of the PUBLISH: an expired message must not be forwarded
+//! and a forwarded one carries the remaining lifetime (chapter 3.3.2.3.3 of
+//! the MQTT 5 spec)
+use crate::clock::Clock;
+use crate::structure::*;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// A PUBLISH together with the time it arrived
+#[derive(Debug, PartialEq, Clone)]
+pub struct StoredMessage {
+    pub publish: PublishPacket,
+    pub received: Instant,
+}
+
+impl StoredMessage {
+    /// stores `publish`, which arrives now
+    pub fn new(publish: PublishPacket, clock: &dyn Clock) -> StoredMessage {
+        StoredMessage {
+            publish,
+            received: clock.now(),
+        }
+    }
+
+    /// None if the message has no Message Expiry Interval
+    pub fn expires_at(&self) -> Option<Instant> {
+        let interval = self.publish.properties.as_ref()?.message_expiry_interval?;
+        Some(self.received + Duration::from_secs(interval as u64))
+    }
+
+    pub fn is_expired(&self, clock: &dyn Clock) -> bool {
+        self.expires_at().is_some_and(|at| at <= clock.now())
+    }
+
+    /// Copy of the PUBLISH to forward now, its Message Expiry Interval is
+    /// reduced by the time the message has been waiting [MQTT-3.3.2-6].
+    /// None if the message has expired [MQTT-3.3.2-5]
+    pub fn forward(&self, clock: &dyn Clock) -> Option<PublishPacket> {
+        if self.is_expired(clock) {
+            return None;
+        }
+        let mut publish = self.publish.clone();
+        if let Some(props) = publish.properties.as_mut() {
+            if let Some(interval) = props.message_expiry_interval {
+                let waited = clock.now().saturating_duration_since(self.received);
+                props.message_expiry_interval = Some(interval - waited.as_secs() as u32);
+            }
+        }
+        Some(publish)
+    }
+}
+
+/// Messages queued for a session while its client is disconnected, in the
+/// order they arrived. Expired messages are dropped
+#[derive(Debug, Default, Clone)]
+pub struct OfflineQueue {
+    messages: VecDeque<StoredMessage>,
+}
+
+impl OfflineQueue {
+    pub fn new() -> OfflineQueue {
+        OfflineQueue::default()
+    }
+
+    /// queues `publish`, which arrives now
+    pub fn push(&mut self, publish: PublishPacket, clock: &dyn Clock) {
+        self.messages.push_back(StoredMessage::new(publish, clock));
+    }
+
+    /// the next message that has not expired, ready to be forwarded
+    pub fn pop(&mut self, clock: &dyn Clock) -> Option<PublishPacket> {
+        while let Some(message) = self.messages.pop_front() {
+            if let Some(publish) = message.forward(clock) {
+                return Some(publish);
+            }
+        }
+        None
+    }
+
+    /// all messages that have not expired, ready to be forwarded
+    pub fn drain(&mut self, clock: &dyn Clock) -> Vec<PublishPacket> {
+        self.messages
+            .drain(..)
+            .filter_map(|message| message.forward(clock))
+            .collect()
+    }
+
+    /// removes the expired messages and returns their number
+    pub fn remove_expired(&mut self, clock: &dyn Clock) -> usize {
+        let before = self.messages.len();
+        self.messages.retain(|message| !message.is_expired(clock));
+        before - self.messages.len()
+    }
+
+    /// number of queued messages, including expired ones that were not removed yet
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
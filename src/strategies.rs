@@ -0,0 +1,646 @@
+//! proptest strategies for all packet and properties types
+//!
+//! Every generated value is canonical for the given protocol version, which means
+//! it decodes to exactly the same value after it was encoded:
+//! - properties sections without any property are `None`
+//! - fields that don't exist in a version are left empty
+//!
+//! # Examples
+//!
+//! ```
+//! use mqtt_packet_3_5::strategies;
+//! use mqtt_packet_3_5::{PacketDecoder, ProtocolVersion};
+//! use proptest::prelude::*;
+//!
+//! proptest!(|(packet in strategies::packet(ProtocolVersion::V5))| {
+//!     let buf = packet.clone().encode(ProtocolVersion::V5).unwrap();
+//!     let mut decoder = PacketDecoder::from_stream(std::io::Cursor::new(buf));
+//!     prop_assert_eq!(packet, decoder.decode_packet(ProtocolVersion::V5).unwrap());
+//! });
+//! ```
+use crate::packet::MqttPacket;
+use crate::structure::*;
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+use proptest::sample::select;
+use std::fmt::Debug;
+
+fn string() -> impl Strategy<Value = String> {
+    "\\PC{0,12}"
+}
+
+fn topic() -> impl Strategy<Value = String> {
+    "[a-z0-9]{1,6}(/[a-z0-9]{0,6}){0,3}"
+}
+
+fn topic_filter() -> impl Strategy<Value = String> {
+    prop_oneof![
+        topic(),
+        Just("#".to_string()),
+        topic().prop_map(|t| format!("{}/+/#", t)),
+        topic().prop_map(|t| format!("$share/group/{}", t)),
+    ]
+}
+
+fn binary() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..16)
+}
+
+/// every value of a reason code enum
+fn code<T: MqttCode<T> + Clone + Debug + 'static>() -> impl Strategy<Value = T> {
+    select(
+        (0..=255)
+            .filter_map(|b| T::from_byte(b).ok())
+            .collect::<Vec<T>>(),
+    )
+}
+
+fn qos() -> impl Strategy<Value = QoS> {
+    select(vec![QoS::QoS0, QoS::QoS1, QoS::QoS2])
+}
+
+fn message_id() -> impl Strategy<Value = u16> {
+    1..=u16::MAX
+}
+
+/// None for properties that would be encoded as an empty properties section
+fn non_empty<T: Default + PartialEq>(props: T) -> Option<T> {
+    if props == T::default() {
+        None
+    } else {
+        Some(props)
+    }
+}
+
+/// properties only exist in MQTT 5
+fn properties<T: Default + PartialEq + Clone + Debug + 'static>(
+    props: impl Strategy<Value = T> + 'static,
+    version: ProtocolVersion,
+) -> BoxedStrategy<Option<T>> {
+    if version.has_properties() {
+        props.prop_map(non_empty).boxed()
+    } else {
+        Just(None).boxed()
+    }
+}
+
+pub fn user_properties() -> impl Strategy<Value = UserProperties> {
+    vec((string(), string()), 0..3).prop_map(UserProperties::from)
+}
+
+pub fn auth_properties() -> impl Strategy<Value = AuthProperties> {
+    (
+        string(),
+        option::of(string()),
+        option::of(string()),
+        user_properties(),
+    )
+        .prop_map(
+            |(authentication_method, authentication_data, reason_string, user_properties)| {
+                AuthProperties {
+                    authentication_method,
+                    authentication_data,
+                    reason_string,
+                    user_properties,
+                }
+            },
+        )
+}
+
+pub fn publish_properties() -> impl Strategy<Value = PublishProperties> {
+    (
+        option::of(any::<bool>()),
+        option::of(any::<u32>()),
+        option::of(string()),
+        option::of(topic()),
+        option::of(binary()),
+        vec(1..=VARBYTEINT_MAX, 0..3),
+        option::of(1..=u16::MAX),
+        user_properties(),
+    )
+        .prop_map(
+            |(
+                payload_format_indicator,
+                message_expiry_interval,
+                content_type,
+                response_topic,
+                correlation_data,
+                subscription_identifiers,
+                topic_alias,
+                user_properties,
+            )| PublishProperties {
+                payload_format_indicator,
+                message_expiry_interval,
+                content_type,
+                response_topic,
+                correlation_data,
+                subscription_identifiers,
+                topic_alias,
+                user_properties,
+            },
+        )
+}
+
+pub fn subscribe_properties() -> impl Strategy<Value = SubscribeProperties> {
+    (option::of(1..=VARBYTEINT_MAX), user_properties()).prop_map(
+        |(subscription_identifier, user_properties)| SubscribeProperties {
+            subscription_identifier,
+            user_properties,
+        },
+    )
+}
+
+pub fn disconnect_properties() -> impl Strategy<Value = DisconnectProperties> {
+    (
+        option::of(any::<u32>()),
+        option::of(string()),
+        option::of(string()),
+        user_properties(),
+    )
+        .prop_map(
+            |(session_expiry_interval, server_reference, reason_string, user_properties)| {
+                DisconnectProperties {
+                    session_expiry_interval,
+                    server_reference,
+                    reason_string,
+                    user_properties,
+                }
+            },
+        )
+}
+
+pub fn confirmation_properties() -> impl Strategy<Value = ConfirmationProperties> {
+    (option::of(string()), user_properties()).prop_map(|(reason_string, user_properties)| {
+        ConfirmationProperties {
+            reason_string,
+            user_properties,
+        }
+    })
+}
+
+pub fn unsubscribe_properties() -> impl Strategy<Value = UnsubscribeProperties> {
+    user_properties().prop_map(|user_properties| UnsubscribeProperties { user_properties })
+}
+
+pub fn will_properties() -> impl Strategy<Value = WillProperties> {
+    (
+        option::of(any::<bool>()),
+        option::of(any::<u32>()),
+        option::of(string()),
+        option::of(topic()),
+        option::of(binary()),
+        option::of(any::<u32>()),
+        user_properties(),
+    )
+        .prop_map(
+            |(
+                payload_format_indicator,
+                message_expiry_interval,
+                content_type,
+                response_topic,
+                correlation_data,
+                will_delay_interval,
+                user_properties,
+            )| WillProperties {
+                payload_format_indicator,
+                message_expiry_interval,
+                content_type,
+                response_topic,
+                correlation_data,
+                will_delay_interval,
+                user_properties,
+            },
+        )
+}
+
+pub fn connack_properties() -> impl Strategy<Value = ConnackProperties> {
+    (
+        (
+            option::of(any::<u32>()),
+            option::of(string()),
+            option::of(any::<u16>()),
+            option::of(string()),
+            option::of(string()),
+            option::of(string()),
+            option::of(string()),
+            option::of(string()),
+        ),
+        (
+            option::of(1..=u16::MAX),
+            option::of(any::<u16>()),
+            option::of(qos()),
+            option::of(any::<bool>()),
+            user_properties(),
+            option::of(1..=u32::MAX),
+            option::of(any::<bool>()),
+            option::of(any::<bool>()),
+            option::of(any::<bool>()),
+        ),
+    )
+        .prop_map(
+            |(
+                (
+                    session_expiry_interval,
+                    assigned_client_identifier,
+                    server_keep_alive,
+                    authentication_method,
+                    authentication_data,
+                    response_information,
+                    server_reference,
+                    reason_string,
+                ),
+                (
+                    receive_maximum,
+                    topic_alias_maximum,
+                    maximum_qos,
+                    retain_available,
+                    user_properties,
+                    maximum_packet_size,
+                    wildcard_subscription_available,
+                    subscription_identifiers_available,
+                    shared_subscription_available,
+                ),
+            )| ConnackProperties {
+                session_expiry_interval,
+                assigned_client_identifier,
+                server_keep_alive,
+                authentication_method,
+                authentication_data,
+                response_information,
+                server_reference,
+                reason_string,
+                receive_maximum,
+                topic_alias_maximum,
+                maximum_qos,
+                retain_available,
+                user_properties,
+                maximum_packet_size,
+                wildcard_subscription_available,
+                subscription_identifiers_available,
+                shared_subscription_available,
+            },
+        )
+}
+
+pub fn connect_properties() -> impl Strategy<Value = ConnectProperties> {
+    (
+        option::of(any::<u32>()),
+        option::of(1..=u16::MAX),
+        option::of(1..=u32::MAX),
+        option::of(any::<u16>()),
+        option::of(any::<bool>()),
+        option::of(any::<bool>()),
+        user_properties(),
+        option::of(string()),
+        option::of(string()),
+    )
+        .prop_map(
+            |(
+                session_expiry_interval,
+                receive_maximum,
+                maximum_packet_size,
+                topic_alias_maximum,
+                request_response_information,
+                request_problem_information,
+                user_properties,
+                authentication_method,
+                authentication_data,
+            )| ConnectProperties {
+                session_expiry_interval,
+                receive_maximum,
+                maximum_packet_size,
+                topic_alias_maximum,
+                request_response_information,
+                request_problem_information,
+                user_properties,
+                authentication_method,
+                authentication_data,
+            },
+        )
+}
+
+pub fn last_will(version: ProtocolVersion) -> impl Strategy<Value = LastWill> {
+    (
+        topic(),
+        string(),
+        qos(),
+        any::<bool>(),
+        properties(will_properties(), version),
+    )
+        .prop_map(|(topic, payload, qos, retain, properties)| LastWill {
+            topic: Some(topic),
+            payload: Some(payload),
+            qos,
+            retain,
+            properties,
+        })
+}
+
+pub fn connect(version: ProtocolVersion) -> impl Strategy<Value = ConnectPacket> {
+    (
+        "[a-zA-Z0-9]{1,23}",
+        any::<bool>(),
+        any::<u16>(),
+        // the encoder only writes a password together with a user name
+        option::of((string(), option::of(string()))),
+        option::of(last_will(version)),
+        properties(connect_properties(), version),
+    )
+        .prop_map(
+            move |(client_id, clean_session, keep_alive, credentials, will, properties)| {
+                let (user_name, password) = match credentials {
+                    Some((user_name, password)) => (Some(user_name), password),
+                    None => (None, None),
+                };
+                ConnectPacket {
+                    client_id,
+                    protocol_version: version,
+                    clean_session,
+                    keep_alive,
+                    user_name,
+                    password,
+                    will,
+                    properties,
+                }
+            },
+        )
+}
+
+pub fn connack(version: ProtocolVersion) -> BoxedStrategy<ConnackPacket> {
+    if version.has_reason_codes() {
+        (
+            any::<u8>(),
+            any::<bool>(),
+            properties(connack_properties(), version),
+        )
+            .prop_map(|(code, session_present, properties)| ConnackPacket {
+                code,
+                session_present,
+                properties,
+            })
+            .boxed()
+    } else {
+        // the reason codes of the MQTT 3 return codes
+        (
+            select(vec![0x00, 0x84, 0x85, 0x88, 0x86, 0x87]),
+            any::<bool>(),
+        )
+            .prop_map(|(code, session_present)| ConnackPacket {
+                code,
+                session_present,
+                properties: None,
+            })
+            .boxed()
+    }
+}
+
+fn publish_qos() -> impl Strategy<Value = PublishQoS> {
+    prop_oneof![
+        Just(PublishQoS::QoS0),
+        message_id().prop_map(PublishQoS::QoS1),
+        message_id().prop_map(PublishQoS::QoS2),
+    ]
+}
+
+pub fn publish(version: ProtocolVersion) -> impl Strategy<Value = PublishPacket> {
+    (
+        publish_qos(),
+        any::<bool>(),
+        any::<bool>(),
+        topic(),
+        binary(),
+        properties(publish_properties(), version),
+    )
+        .prop_map(
+            |(qos, dup, retain, topic, payload, properties)| PublishPacket {
+                // DUP must be 0 for QoS 0
+                dup: dup && qos != PublishQoS::QoS0,
+                qos,
+                retain,
+                topic,
+                payload,
+                properties,
+            },
+        )
+}
+
+/// reason code and properties of a PUBACK/PUBREC/PUBREL/PUBCOMP
+fn confirmation<T: MqttCode<T> + Clone + Debug + 'static>(
+    version: ProtocolVersion,
+) -> BoxedStrategy<(u16, Option<T>, Option<ConfirmationProperties>)> {
+    if version.has_reason_codes() {
+        (
+            message_id(),
+            code::<T>(),
+            properties(confirmation_properties(), version),
+        )
+            .prop_map(|(id, code, properties)| (id, Some(code), properties))
+            .boxed()
+    } else {
+        message_id().prop_map(|id| (id, None, None)).boxed()
+    }
+}
+
+pub fn puback(version: ProtocolVersion) -> impl Strategy<Value = PubackPacket> {
+    confirmation(version).prop_map(|(message_id, reason_code, properties)| PubackPacket {
+        message_id,
+        reason_code,
+        properties,
+    })
+}
+
+pub fn pubrec(version: ProtocolVersion) -> impl Strategy<Value = PubrecPacket> {
+    confirmation(version).prop_map(|(message_id, reason_code, properties)| PubrecPacket {
+        message_id,
+        reason_code,
+        properties,
+    })
+}
+
+pub fn pubrel(version: ProtocolVersion) -> impl Strategy<Value = PubrelPacket> {
+    confirmation(version).prop_map(|(message_id, reason_code, properties)| PubrelPacket {
+        message_id,
+        reason_code,
+        properties,
+    })
+}
+
+pub fn pubcomp(version: ProtocolVersion) -> impl Strategy<Value = PubcompPacket> {
+    confirmation(version).prop_map(|(message_id, reason_code, properties)| PubcompPacket {
+        message_id,
+        reason_code,
+        properties,
+    })
+}
+
+pub fn subscription(version: ProtocolVersion) -> BoxedStrategy<Subscription> {
+    if version.has_subscription_options() {
+        (
+            topic_filter(),
+            qos(),
+            any::<bool>(),
+            any::<bool>(),
+            select(vec![
+                RetainHandling::SendAtSubscribe,
+                RetainHandling::SendAtSubscribeIfNew,
+                RetainHandling::DoNotSend,
+            ]),
+        )
+            .prop_map(|(topic, qos, nl, rap, rh)| Subscription {
+                topic,
+                qos,
+                nl,
+                rap,
+                rh,
+            })
+            .boxed()
+    } else {
+        (topic_filter(), qos())
+            .prop_map(|(topic, qos)| Subscription {
+                topic,
+                qos,
+                nl: false,
+                rap: false,
+                rh: RetainHandling::SendAtSubscribe,
+            })
+            .boxed()
+    }
+}
+
+pub fn subscribe(version: ProtocolVersion) -> impl Strategy<Value = SubscribePacket> {
+    (
+        message_id(),
+        vec(subscription(version), 1..4),
+        properties(subscribe_properties(), version),
+    )
+        .prop_map(|(message_id, subscriptions, properties)| SubscribePacket {
+            qos: QoS::QoS1,
+            subscriptions,
+            properties,
+            message_id,
+        })
+}
+
+pub fn suback(version: ProtocolVersion) -> impl Strategy<Value = SubackPacket> {
+    let granted = if version.has_reason_codes() {
+        code::<SubscriptionReasonCode>().boxed()
+    } else {
+        // MQTT 3 only knows the granted QoS and a single failure code
+        select(vec![
+            SubscriptionReasonCode::GrantedQoS0,
+            SubscriptionReasonCode::GrantedQoS1,
+            SubscriptionReasonCode::GrantedQoS2,
+            SubscriptionReasonCode::UnspecifiedError,
+        ])
+        .boxed()
+    };
+    (
+        message_id(),
+        vec(granted, 1..4),
+        properties(confirmation_properties(), version),
+    )
+        .prop_map(|(message_id, granted, properties)| SubackPacket {
+            message_id,
+            properties,
+            granted,
+        })
+}
+
+pub fn unsubscribe(version: ProtocolVersion) -> impl Strategy<Value = UnsubscribePacket> {
+    (
+        message_id(),
+        vec(topic_filter(), 1..4),
+        properties(unsubscribe_properties(), version),
+    )
+        .prop_map(
+            |(message_id, unsubscriptions, properties)| UnsubscribePacket {
+                qos: QoS::QoS1,
+                message_id,
+                properties,
+                unsubscriptions,
+            },
+        )
+}
+
+pub fn unsuback(version: ProtocolVersion) -> BoxedStrategy<UnsubackPacket> {
+    if version.has_reason_codes() {
+        (
+            message_id(),
+            vec(code::<UnsubackCode>(), 1..4),
+            properties(confirmation_properties(), version),
+        )
+            .prop_map(|(message_id, granted, properties)| UnsubackPacket {
+                message_id,
+                granted,
+                properties,
+            })
+            .boxed()
+    } else {
+        message_id()
+            .prop_map(|message_id| UnsubackPacket {
+                message_id,
+                granted: vec![],
+                properties: None,
+            })
+            .boxed()
+    }
+}
+
+pub fn disconnect(version: ProtocolVersion) -> BoxedStrategy<DisconnectPacket> {
+    if version.has_reason_codes() {
+        (
+            code::<DisconnectCode>(),
+            properties(disconnect_properties(), version),
+        )
+            .prop_map(|(reason_code, properties)| DisconnectPacket {
+                reason_code: Some(reason_code),
+                properties,
+            })
+            .boxed()
+    } else {
+        Just(DisconnectPacket {
+            reason_code: None,
+            properties: None,
+        })
+        .boxed()
+    }
+}
+
+/// AUTH packets only exist in MQTT 5
+pub fn auth() -> impl Strategy<Value = AuthPacket> {
+    (
+        code::<AuthCode>(),
+        // the authentication method is required, so there's always a property
+        option::of(auth_properties()),
+    )
+        .prop_map(|(reason_code, properties)| AuthPacket {
+            reason_code,
+            properties,
+        })
+}
+
+/// any packet that can be sent with the given version
+pub fn packet(version: ProtocolVersion) -> BoxedStrategy<MqttPacket> {
+    let packets = prop_oneof![
+        connect(version).prop_map(MqttPacket::Connect),
+        connack(version).prop_map(MqttPacket::Connack),
+        publish(version).prop_map(MqttPacket::Publish),
+        puback(version).prop_map(MqttPacket::Puback),
+        pubrec(version).prop_map(MqttPacket::Pubrec),
+        pubrel(version).prop_map(MqttPacket::Pubrel),
+        pubcomp(version).prop_map(MqttPacket::Pubcomp),
+        subscribe(version).prop_map(MqttPacket::Subscribe),
+        suback(version).prop_map(MqttPacket::Suback),
+        unsubscribe(version).prop_map(MqttPacket::Unsubscribe),
+        unsuback(version).prop_map(MqttPacket::Unsuback),
+        Just(MqttPacket::Pingreq),
+        Just(MqttPacket::Pingresp),
+        disconnect(version).prop_map(MqttPacket::Disconnect),
+    ];
+    if version.has_auth() {
+        prop_oneof![packets, auth().prop_map(MqttPacket::Auth)].boxed()
+    } else {
+        packets.boxed()
+    }
+}
@@ -13,6 +13,7 @@ pub trait MqttCode<T> {
 
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// Use an enum to make setting the reason code easier and safer
 pub enum SubscriptionReasonCode {
     /// 0x00  The subscription is accepted and the maximum QoS sent will be QoS 0. This might be a lower QoS than was requested.
@@ -81,6 +82,7 @@ impl MqttCode<SubscriptionReasonCode> for SubscriptionReasonCode {
 
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum DisconnectCode {
     NormalDisconnection,                 //0x00
     DisconnectWithWillMessage,           //0x04
@@ -186,6 +188,7 @@ impl MqttCode<DisconnectCode> for DisconnectCode {
 
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum UnsubackCode {
     Success,
     NoSubscriptionExisted,
@@ -226,6 +229,7 @@ impl MqttCode<UnsubackCode> for UnsubackCode {
 
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum AuthCode {
     Success,                // 0x0
     ContinueAuthentication, // 0x18
@@ -253,6 +257,7 @@ impl MqttCode<AuthCode> for AuthCode {
 
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// PUMBCOMP/PUBREL codes enum
 pub enum PubcompPubrelCode {
     Success,                  // 0x0
@@ -278,6 +283,7 @@ impl MqttCode<PubcompPubrelCode> for PubcompPubrelCode {
 
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// PUBACK/PUBREC codes enum
 pub enum PubackPubrecCode {
     Success,
@@ -1,12 +1,12 @@
+use super::codes::SubscriptionReasonCode;
 #[cfg(feature = "serde_support")]
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
-pub type UserProperties = HashMap<String, Vec<String>>;
 pub type Res<T> = Result<T, String>;
 
 #[derive(PartialEq, Debug, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Protocol {
     Mqtt,
     MQIsdp,
@@ -20,18 +20,213 @@ impl Protocol {
             s => return Err(format!("Invalid protocolId {}", s)),
         })
     }
+
+    /// Protocol name as written into a CONNECT packet
+    pub fn name(&self) -> &'static str {
+        match self {
+            Protocol::Mqtt => "MQTT",
+            Protocol::MQIsdp => "MQIsdp",
+        }
+    }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+/// Supported MQTT versions. Every version belongs to exactly one protocol name:
+/// 3.1 is announced as "MQIsdp" with level 3, 3.1.1 and 5 as "MQTT" with level 4 and 5
+/// Serialized as the protocol level of the CONNECT, i.e. 3, 4 or 5
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u8", into = "u8")
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum ProtocolVersion {
+    V31,
+    V311,
+    V5,
+}
+
+impl ProtocolVersion {
+    /// protocol level as used in the CONNECT packet
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            ProtocolVersion::V31 => 3,
+            ProtocolVersion::V311 => 4,
+            ProtocolVersion::V5 => 5,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Res<ProtocolVersion> {
+        Ok(match byte {
+            3 => ProtocolVersion::V31,
+            4 => ProtocolVersion::V311,
+            5 => ProtocolVersion::V5,
+            _ => return Err("Invalid protocol version".to_string()),
+        })
+    }
+
+    /// The protocol name that has to be sent together with this version
+    pub fn protocol(&self) -> Protocol {
+        match self {
+            ProtocolVersion::V31 => Protocol::MQIsdp,
+            ProtocolVersion::V311 | ProtocolVersion::V5 => Protocol::Mqtt,
+        }
+    }
+
+    /// Packets carry a properties section (MQTT 5 only)
+    pub fn has_properties(&self) -> bool {
+        *self == ProtocolVersion::V5
+    }
+
+    /// Acknowledgements and DISCONNECT carry reason codes, CONNACK
+    /// uses reason codes instead of return codes (MQTT 5 only)
+    pub fn has_reason_codes(&self) -> bool {
+        *self == ProtocolVersion::V5
+    }
+
+    /// Subscriptions carry No Local, Retain As Published and
+    /// Retain Handling options (MQTT 5 only)
+    pub fn has_subscription_options(&self) -> bool {
+        *self == ProtocolVersion::V5
+    }
+
+    /// AUTH packets exist (MQTT 5 only)
+    pub fn has_auth(&self) -> bool {
+        *self == ProtocolVersion::V5
+    }
+
+    /// A client may connect with an empty client id if it requests
+    /// a clean session (since 3.1.1)
+    pub fn allows_empty_client_id(&self) -> bool {
+        *self >= ProtocolVersion::V311
+    }
+}
+
+impl TryFrom<u8> for ProtocolVersion {
+    type Error = String;
+
+    fn try_from(byte: u8) -> Res<ProtocolVersion> {
+        ProtocolVersion::from_byte(byte)
+    }
+}
+
+impl From<ProtocolVersion> for u8 {
+    fn from(version: ProtocolVersion) -> u8 {
+        version.to_byte()
+    }
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ProtocolVersion::V31 => "3.1",
+            ProtocolVersion::V311 => "3.1.1",
+            ProtocolVersion::V5 => "5",
+        })
+    }
+}
+
+/// Serialized as 0, 1 or 2
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u8", into = "u8")
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum QoS {
     QoS0,
     QoS1,
     QoS2,
 }
 
+/// QoS of a PUBLISH packet. A packet identifier is present
+/// if and only if the QoS is 1 or 2, so it is stored together with the QoS
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(Serialize, Deserialize),
+    serde(
+        try_from = "super::json::PublishQoSFields",
+        into = "super::json::PublishQoSFields"
+    )
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum PublishQoS {
+    QoS0,
+    QoS1(u16),
+    QoS2(u16),
+}
+
+impl PublishQoS {
+    /// combines a QoS and an optional packet identifier, fails if
+    /// a packet identifier is missing or given for QoS 0
+    pub fn from_parts(qos: QoS, message_id: Option<u16>) -> Res<PublishQoS> {
+        Ok(match (qos, message_id) {
+            (QoS::QoS0, None) => PublishQoS::QoS0,
+            (QoS::QoS1, Some(id)) => PublishQoS::QoS1(id),
+            (QoS::QoS2, Some(id)) => PublishQoS::QoS2(id),
+            (QoS::QoS0, Some(_)) => {
+                return Err("QoS 0 PUBLISH must not have a messageId".to_string())
+            }
+            (_, None) => return Err("Invalid messageId".to_string()),
+        })
+    }
+
+    pub fn qos(&self) -> QoS {
+        match self {
+            PublishQoS::QoS0 => QoS::QoS0,
+            PublishQoS::QoS1(_) => QoS::QoS1,
+            PublishQoS::QoS2(_) => QoS::QoS2,
+        }
+    }
+
+    pub fn message_id(&self) -> Option<u16> {
+        match self {
+            PublishQoS::QoS0 => None,
+            PublishQoS::QoS1(id) | PublishQoS::QoS2(id) => Some(*id),
+        }
+    }
+}
+
+/// Retain Handling option of a subscription (bits 4 and 5 of the
+/// subscription options). MQTT 3 has no such option and always behaves
+/// like SendAtSubscribe
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum RetainHandling {
+    /// 0 = Send retained messages at the time of the subscribe
+    #[default]
+    SendAtSubscribe,
+    /// 1 = Send retained messages at subscribe only if the subscription does not currently exist
+    SendAtSubscribeIfNew,
+    /// 2 = Do not send retained messages at the time of the subscribe
+    DoNotSend,
+}
+
+impl RetainHandling {
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            RetainHandling::SendAtSubscribe => 0,
+            RetainHandling::SendAtSubscribeIfNew => 1,
+            RetainHandling::DoNotSend => 2,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Res<RetainHandling> {
+        Ok(match byte {
+            0 => RetainHandling::SendAtSubscribe,
+            1 => RetainHandling::SendAtSubscribeIfNew,
+            2 => RetainHandling::DoNotSend,
+            _ => return Err("Invalid retain handling, must be <= 2".to_string()),
+        })
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Granted {
     QoS0,
     QoS1,
@@ -60,6 +255,43 @@ impl Granted {
     }
 }
 
+impl From<&SubscriptionReasonCode> for Granted {
+    /// every reason code >= 0x80 is a failure in MQTT 3
+    fn from(code: &SubscriptionReasonCode) -> Granted {
+        match code {
+            SubscriptionReasonCode::GrantedQoS0 => Granted::QoS0,
+            SubscriptionReasonCode::GrantedQoS1 => Granted::QoS1,
+            SubscriptionReasonCode::GrantedQoS2 => Granted::QoS2,
+            _ => Granted::Failure,
+        }
+    }
+}
+
+impl From<Granted> for SubscriptionReasonCode {
+    fn from(granted: Granted) -> SubscriptionReasonCode {
+        match granted {
+            Granted::QoS0 => SubscriptionReasonCode::GrantedQoS0,
+            Granted::QoS1 => SubscriptionReasonCode::GrantedQoS1,
+            Granted::QoS2 => SubscriptionReasonCode::GrantedQoS2,
+            Granted::Failure => SubscriptionReasonCode::UnspecifiedError,
+        }
+    }
+}
+
+impl TryFrom<u8> for QoS {
+    type Error = String;
+
+    fn try_from(byte: u8) -> Res<QoS> {
+        QoS::from_byte(byte)
+    }
+}
+
+impl From<QoS> for u8 {
+    fn from(qos: QoS) -> u8 {
+        qos.to_byte()
+    }
+}
+
 impl QoS {
     pub fn to_byte(&self) -> u8 {
         match self {
@@ -132,6 +364,7 @@ impl PacketType {
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum PacketType {
     Reserved,
     Connect,
@@ -159,7 +392,7 @@ pub enum PacketType {
 pub struct FixedHeader {
     pub cmd: PacketType,
     pub dup: bool,
-    pub qos: u8,
+    pub qos: QoS,
     pub retain: bool,
 }
 
@@ -189,9 +422,10 @@ impl FixedHeader {
             (flags & QOS_MASK) >> QOS_SHIFT,
             (flags & DUP_MASK) != 0,
         );
-        if qos > 2 {
-            return Err("Packet must not have both QoS bits set to 1".to_string());
-        }
+        let qos = match QoS::from_byte(qos) {
+            Ok(qos) => qos,
+            Err(_) => return Err("Packet must not have both QoS bits set to 1".to_string()),
+        };
         Ok(FixedHeader {
             cmd,
             dup,
@@ -200,11 +434,23 @@ impl FixedHeader {
         })
     }
 
+    /// Like from_byte, but reserved flag bits that don't have the value required
+    /// for the packet type are replaced with it. Also returns whether that happened
+    pub fn from_byte_lenient(byte: u8) -> Res<(FixedHeader, bool)> {
+        let expected = match PacketType::from_bits(byte >> 4) {
+            PacketType::Publish => return Ok((FixedHeader::from_byte(byte)?, false)),
+            PacketType::Pubrel | PacketType::Subscribe | PacketType::Unsubscribe => 2,
+            _ => 0,
+        };
+        let fixed = FixedHeader::from_byte((byte & 0xF0) | expected)?;
+        Ok((fixed, byte & 0x0F != expected))
+    }
+
     pub fn for_type(cmd: PacketType) -> FixedHeader {
         FixedHeader {
             cmd,
             dup: false,
-            qos: 0,
+            qos: QoS::QoS0,
             retain: false,
         }
     }
@@ -212,9 +458,12 @@ impl FixedHeader {
     pub fn encode(&self) -> u8 {
         let message_type = PacketType::to_bits(self.cmd);
         match self.cmd {
-            // PacketType::Unsuback => message_type | ((self.dup as u8) << 3) | (self.qos << 1),
+            // PacketType::Unsuback => message_type | ((self.dup as u8) << 3) | (self.qos.to_byte() << 1),
             PacketType::Publish => {
-                message_type | ((self.dup as u8) << 3) | (self.qos << 1) | self.retain as u8
+                message_type
+                    | ((self.dup as u8) << 3)
+                    | (self.qos.to_byte() << 1)
+                    | self.retain as u8
             }
             PacketType::Unsubscribe | PacketType::Subscribe => {
                 message_type | 2 // Bits 3,2,1 and 0 need to ALWAYS be set to 0, 0, 1, 0 respectively
@@ -0,0 +1,188 @@
+use super::common::*;
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+
+/// How the decoder reacts to input that breaks the spec in ways
+/// that are common with fielded devices
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DecodeMode {
+    /// every deviation from the spec is an error
+    #[default]
+    Strict,
+    /// the deviations listed in `DecodeWarning` are accepted and
+    /// reported next to the decoded packet
+    Lenient,
+}
+
+/// What the decoder does after a packet failed to decode
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum RecoveryPolicy {
+    /// discard the rest of the failed packet and continue with the next one
+    #[default]
+    SkipPacket,
+    /// stop at the first error, every later call fails as well
+    FailFast,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct DecoderOptions {
+    pub mode: DecodeMode,
+    pub limits: DecoderLimits,
+    pub recovery: RecoveryPolicy,
+}
+
+impl DecoderOptions {
+    pub fn strict() -> DecoderOptions {
+        DecoderOptions {
+            mode: DecodeMode::Strict,
+            ..DecoderOptions::default()
+        }
+    }
+
+    pub fn lenient() -> DecoderOptions {
+        DecoderOptions {
+            mode: DecodeMode::Lenient,
+            ..DecoderOptions::default()
+        }
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.mode == DecodeMode::Lenient
+    }
+}
+
+/// Something the decoder tolerated in lenient mode
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum DecodeWarning {
+    /// The reserved flag bits of the fixed header did not have the value
+    /// required for the packet type and were ignored
+    ReservedHeaderBits { cmd: PacketType, flags: u8 },
+    /// An AUTH or DISCONNECT packet had a remaining length of 0 and was
+    /// decoded as if it had reason code 0x00 and no properties
+    EmptyPacket(PacketType),
+    /// A property identifier unknown to MQTT 5. Since its length can't be known,
+    /// `bytes` holds everything after the identifier up to the end of the properties.
+    /// Properties that follow the unknown one are not decoded and are missing from
+    /// the packet, the properties before it are kept
+    UnknownProperty { id: u8, bytes: Vec<u8> },
+    /// Bits 7-2 of the options of a MQTT 3 subscription were set and ignored
+    SubscriptionOptionBits { index: usize, options: u8 },
+}
+
+/// Upper bounds for what a single packet may contain. They are checked before
+/// anything is allocated for the data. The default only enforces the limits of the spec
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DecoderLimits {
+    /// size of the whole packet, including the fixed header
+    pub max_packet_size: u32,
+    /// length of topic names and topic filters in bytes
+    pub max_topic_length: usize,
+    /// length of any other UTF-8 string in bytes
+    pub max_string_length: usize,
+    /// number of user properties in one properties section
+    pub max_user_properties: usize,
+    /// summed length of all names and values of the user properties in one properties section
+    pub max_user_property_bytes: usize,
+    /// number of subscriptions in a SUBSCRIBE or topic filters in an UNSUBSCRIBE
+    pub max_subscriptions: usize,
+}
+
+impl Default for DecoderLimits {
+    fn default() -> DecoderLimits {
+        DecoderLimits {
+            max_packet_size: VARBYTEINT_MAX + 5,
+            max_topic_length: u16::MAX as usize,
+            max_string_length: u16::MAX as usize,
+            max_user_properties: usize::MAX,
+            max_user_property_bytes: usize::MAX,
+            max_subscriptions: usize::MAX,
+        }
+    }
+}
+
+/// A packet went over one of the `DecoderLimits`
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum LimitExceeded {
+    PacketSize { size: u32, max: u32 },
+    TopicLength { length: usize, max: usize },
+    StringLength { length: usize, max: usize },
+    UserProperties { count: usize, max: usize },
+    UserPropertyBytes { bytes: usize, max: usize },
+    Subscriptions { count: usize, max: usize },
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LimitExceeded::PacketSize { size, max } => {
+                write!(f, "Packet size {} exceeds the maximum of {}", size, max)
+            }
+            LimitExceeded::TopicLength { length, max } => {
+                write!(f, "Topic length {} exceeds the maximum of {}", length, max)
+            }
+            LimitExceeded::StringLength { length, max } => {
+                write!(f, "String length {} exceeds the maximum of {}", length, max)
+            }
+            LimitExceeded::UserProperties { count, max } => {
+                write!(f, "More than {} user properties ({})", max, count)
+            }
+            LimitExceeded::UserPropertyBytes { bytes, max } => {
+                write!(
+                    f,
+                    "User properties size {} exceeds the maximum of {}",
+                    bytes, max
+                )
+            }
+            LimitExceeded::Subscriptions { count, max } => {
+                write!(f, "More than {} subscriptions ({})", max, count)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum DecodeErrorKind {
+    /// the packet does not follow the spec
+    Malformed(String),
+    LimitExceeded(LimitExceeded),
+    /// an earlier error stopped the decoder, see `RecoveryPolicy`
+    Poisoned,
+}
+
+/// Error of PacketDecoder::try_decode_packet
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct DecodeError {
+    pub kind: DecodeErrorKind,
+    /// true if the stream is positioned at the start of the next packet.
+    /// Otherwise the decoder is poisoned and the connection should be closed
+    pub framed: bool,
+    /// number of bytes read from the stream for the failed packet, only the
+    /// bytes after the last field for a packet that is longer than its fields
+    pub discarded: u64,
+}
+
+impl std::fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeErrorKind::Malformed(msg) => write!(f, "{}", msg),
+            DecodeErrorKind::LimitExceeded(limit) => write!(f, "{}", limit),
+            DecodeErrorKind::Poisoned => write!(f, "Decoder stopped after a previous error"),
+        }
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl From<DecodeError> for String {
+    fn from(e: DecodeError) -> String {
+        e.to_string()
+    }
+}
@@ -0,0 +1,118 @@
+//! Helpers for the serde representation of packets, which is described by
+//! the JSON Schema in `schema/mqtt-packet.schema.json`
+use super::common::*;
+use super::user_properties::UserProperties;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// JSON Schema of the serde representation of `MqttPacket`
+pub const JSON_SCHEMA: &str = include_str!("../../schema/mqtt-packet.schema.json");
+
+/// fields with their default value are left out
+pub(crate) fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// bytes as a string if they are valid UTF-8, as `{"base64": "..."}` otherwise
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Binary {
+    Text(String),
+    Base64 { base64: String },
+}
+
+impl Binary {
+    fn new(bytes: &[u8]) -> Binary {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Binary::Text(s.to_string()),
+            Err(_) => Binary::Base64 {
+                base64: STANDARD.encode(bytes),
+            },
+        }
+    }
+
+    fn into_bytes<E: serde::de::Error>(self) -> Result<Vec<u8>, E> {
+        match self {
+            Binary::Text(s) => Ok(s.into_bytes()),
+            Binary::Base64 { base64 } => STANDARD.decode(base64).map_err(E::custom),
+        }
+    }
+}
+
+pub(crate) mod binary {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        Binary::new(bytes).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Binary::deserialize(deserializer)?.into_bytes()
+    }
+}
+
+pub(crate) mod option_binary {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        bytes.as_deref().map(Binary::new).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<Binary>::deserialize(deserializer)?
+            .map(Binary::into_bytes)
+            .transpose()
+    }
+}
+
+/// `PublishQoS` is flattened into the PUBLISH as `qos` and `message_id`
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub(crate) struct PublishQoSFields {
+    qos: QoS,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message_id: Option<u16>,
+}
+
+impl From<PublishQoS> for PublishQoSFields {
+    fn from(qos: PublishQoS) -> PublishQoSFields {
+        PublishQoSFields {
+            qos: qos.qos(),
+            message_id: qos.message_id(),
+        }
+    }
+}
+
+impl TryFrom<PublishQoSFields> for PublishQoS {
+    type Error = String;
+
+    fn try_from(fields: PublishQoSFields) -> Res<PublishQoS> {
+        PublishQoS::from_parts(fields.qos, fields.message_id)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct UserProperty {
+    key: String,
+    value: String,
+}
+
+impl From<UserProperties> for Vec<UserProperty> {
+    fn from(properties: UserProperties) -> Vec<UserProperty> {
+        properties
+            .into_iter()
+            .map(|(key, value)| UserProperty { key, value })
+            .collect()
+    }
+}
+
+impl From<Vec<UserProperty>> for UserProperties {
+    fn from(properties: Vec<UserProperty>) -> UserProperties {
+        properties.into_iter().map(|p| (p.key, p.value)).collect()
+    }
+}
@@ -1,48 +1,73 @@
 mod codes;
 mod common;
+mod decoder_options;
+#[cfg(feature = "serde_support")]
+pub(crate) mod json;
 mod properties;
+mod property_table;
+mod user_properties;
 use crate::byte_reader::ByteReader;
 pub use codes::*;
 pub use common::*;
+pub use decoder_options::*;
+#[cfg(feature = "serde_support")]
+pub use json::JSON_SCHEMA;
 pub use properties::*;
+pub use property_table::*;
 #[cfg(feature = "serde_support")]
 use serde::{Deserialize, Serialize};
 use std::io;
+pub use user_properties::*;
 
 pub trait Packet: Sized {
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>>;
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>>;
     fn decode<R: io::Read>(
         reader: &mut ByteReader<R>,
         fixed: FixedHeader,
         length: u32,
-        protocol_version: u8,
+        protocol_version: ProtocolVersion,
     ) -> Res<Self>;
     // fn matches(t: PacketType) -> bool;
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct AuthPacket {
     pub reason_code: AuthCode,
     pub properties: Option<AuthProperties>,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(PartialEq, Debug, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct LastWill {
     pub topic: Option<String>,
     pub payload: Option<String>,
-    pub qos: u8,
+    pub qos: QoS,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub retain: bool,
     pub properties: Option<WillProperties>,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(PartialEq, Debug, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ConnectPacket {
     pub client_id: String,
-    pub protocol_version: u8,
-    pub protocol_id: Protocol,
+    /// Version announced by the client. The protocol name is implied by it,
+    /// see `ProtocolVersion::protocol`
+    pub protocol_version: ProtocolVersion,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub clean_session: bool,
     pub keep_alive: u16,
     pub user_name: Option<String>,
@@ -52,41 +77,134 @@ pub struct ConnectPacket {
     pub properties: Option<ConnectProperties>,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ConnackPacket {
-    pub return_code: Option<u8>,
-    pub reason_code: Option<u8>,
+    /// MQTT 5 reason code. The return codes of MQTT 3.1 and 3.1.1 are mapped
+    /// to the reason codes with the same meaning, encoding for those versions
+    /// fails for the reason codes without one
+    pub code: u8,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub session_present: bool,
     pub properties: Option<ConnackProperties>,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct DisconnectPacket {
     // only exists in MQTT 5
     pub reason_code: Option<DisconnectCode>,
     pub properties: Option<DisconnectProperties>,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// Captures value of published message
 pub struct PublishPacket {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub dup: bool,
-    pub qos: u8,
+    /// QoS together with the packet identifier for QoS 1 and 2
+    #[cfg_attr(feature = "serde_support", serde(flatten))]
+    pub qos: PublishQoS,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub retain: bool,
     pub topic: String,
-    pub message_id: Option<u16>,
     /// No assumptions are made about the structure
     /// and content of payload
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(with = "crate::structure::json::binary")
+    )]
     pub payload: Vec<u8>,
     /// Used in MQTT 5
     pub properties: Option<PublishProperties>,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+/// Acknowledgement of a QoS 1 PUBLISH (PUBACK)
+pub struct PubackPacket {
+    pub message_id: u16,
+    /// Reason code is always Success by default for MQTT 5
+    /// but absent for MQTT 3 and 4
+    pub reason_code: Option<PubackPubrecCode>,
+    pub properties: Option<ConfirmationProperties>,
+}
+
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+/// First acknowledgement of a QoS 2 PUBLISH (PUBREC)
+pub struct PubrecPacket {
+    pub message_id: u16,
+    /// Reason code is always Success by default for MQTT 5
+    /// but absent for MQTT 3 and 4
+    pub reason_code: Option<PubackPubrecCode>,
+    pub properties: Option<ConfirmationProperties>,
+}
+
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+/// Response to a PUBREC (PUBREL)
+pub struct PubrelPacket {
+    pub message_id: u16,
+    /// Reason code is always Success by default for MQTT 5
+    /// but absent for MQTT 3 and 4
+    pub reason_code: Option<PubcompPubrelCode>,
+    pub properties: Option<ConfirmationProperties>,
+}
+
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+/// Final acknowledgement of a QoS 2 PUBLISH (PUBCOMP)
+pub struct PubcompPacket {
+    pub message_id: u16,
+    /// Reason code is always Success by default for MQTT 5
+    /// but absent for MQTT 3 and 4
+    pub reason_code: Option<PubcompPubrelCode>,
+    pub properties: Option<ConfirmationProperties>,
+}
+
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+/// The former shared struct for PUBACK, PUBCOMP, PUBREL and PUBREC.
+/// It is no longer encoded or decoded directly, use `TryFrom` to turn it
+/// into `PubackPacket`, `PubrecPacket`, `PubrelPacket`, `PubcompPacket` or `MqttPacket`
+pub struct ConfirmationPacket {
+    pub cmd: PacketType,
+    pub puback_reason_code: Option<PubackPubrecCode>,
+    pub pubcomp_reason_code: Option<PubcompPubrelCode>,
+    pub properties: Option<ConfirmationProperties>,
+    pub message_id: u16,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Subscription {
     /// Name of topic or wildcard pattern to subscribe to
     pub topic: String,
@@ -98,6 +216,14 @@ pub struct Subscription {
     /// connection with a ClientID equal to the ClientID of the publishing
     /// connection [MQTT-3.8.3-3]. It is a Protocol Error to set the No Local
     /// bit to 1 on a Shared Subscription
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(
+            rename = "no_local",
+            default,
+            skip_serializing_if = "crate::structure::json::is_default"
+        )
+    )]
     pub nl: bool,
     /// Bit 3 of the Subscription Options represents the Retain As Published
     /// option. If 1, Application Messages forwarded using this subscription
@@ -105,6 +231,14 @@ pub struct Subscription {
     /// Messages forwarded using this subscription have the RETAIN flag
     /// set to 0. Retained messages sent when the subscription is established
     /// have the RETAIN flag set to 1.
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(
+            rename = "retain_as_published",
+            default,
+            skip_serializing_if = "crate::structure::json::is_default"
+        )
+    )]
     pub rap: bool,
     /// Bits 4 and 5 of the Subscription Options represent the Retain Handling
     /// option. This option specifies whether retained messages are sent when
@@ -118,60 +252,86 @@ pub struct Subscription {
     /// 1 = Send retained messages at subscribe only if the subscription does not currently exist
     ///
     /// 2 = Do not send retained messages at the time of the subscribe
-    pub rh: Option<u8>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(
+            rename = "retain_handling",
+            default,
+            skip_serializing_if = "crate::structure::json::is_default"
+        )
+    )]
+    pub rh: RetainHandling,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct SubscribePacket {
-    pub qos: u8,
+    pub qos: QoS,
     pub subscriptions: Vec<Subscription>,
     pub properties: Option<SubscribeProperties>,
     pub message_id: u16,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 /// Packet that holds information of subscription acknowledgement (SUBACK)
 pub struct SubackPacket {
-    pub reason_code: Option<u8>,
     pub message_id: u16,
-    pub properties: Option<ConfirmationProperties>,
     /// used in MQTT 5
-    pub granted_reason_codes: Vec<SubscriptionReasonCode>,
-    /// used in MQTT 3.1 and 4
-    pub granted: Vec<Granted>,
+    pub properties: Option<ConfirmationProperties>,
+    /// One result per subscription of the SUBSCRIBE. MQTT 3.1 and 4 only know
+    /// granted QoS 0-2 and 0x80 Failure, every failure code is written as 0x80
+    /// when encoding for those versions
+    pub granted: Vec<SubscriptionReasonCode>,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct UnsubscribePacket {
-    pub qos: u8,
+    pub qos: QoS,
     pub message_id: u16,
     pub properties: Option<UnsubscribeProperties>,
     pub unsubscriptions: Vec<String>,
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct UnsubackPacket {
     pub message_id: u16,
     /// used only in MQTT 5, will always empty if
     /// not MQTT 5
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub granted: Vec<UnsubackCode>,
     pub properties: Option<ConfirmationProperties>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct PingreqPacket;
 
 impl Packet for PingreqPacket {
-    fn decode<R: io::Read>(_: &mut ByteReader<R>, _: FixedHeader, _: u32, _: u8) -> Res<Self> {
+    fn decode<R: io::Read>(
+        _: &mut ByteReader<R>,
+        _: FixedHeader,
+        _: u32,
+        _: ProtocolVersion,
+    ) -> Res<Self> {
         Ok(PingreqPacket {})
     }
 
-    fn encode(&self, _: u8) -> Res<Vec<u8>> {
+    fn encode(&self, _: ProtocolVersion) -> Res<Vec<u8>> {
         Ok(vec![
             FixedHeader::encode(&FixedHeader::for_type(PacketType::Pingreq)),
             0,
@@ -181,14 +341,20 @@ impl Packet for PingreqPacket {
 
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct PingrespPacket;
 
 impl Packet for PingrespPacket {
-    fn decode<R: io::Read>(_: &mut ByteReader<R>, _: FixedHeader, _: u32, _: u8) -> Res<Self> {
+    fn decode<R: io::Read>(
+        _: &mut ByteReader<R>,
+        _: FixedHeader,
+        _: u32,
+        _: ProtocolVersion,
+    ) -> Res<Self> {
         Ok(PingrespPacket {})
     }
 
-    fn encode(&self, _: u8) -> Res<Vec<u8>> {
+    fn encode(&self, _: ProtocolVersion) -> Res<Vec<u8>> {
         Ok(vec![
             FixedHeader::encode(&FixedHeader::for_type(PacketType::Pingresp)),
             0,
@@ -1,5 +1,6 @@
-use super::codes::*;
 use super::common::*;
+use super::property_table::*;
+use super::user_properties::UserProperties;
 use crate::mqtt_writer::MqttWriter;
 #[cfg(feature = "serde_support")]
 use serde::{Deserialize, Serialize};
@@ -7,16 +8,26 @@ use serde::{Deserialize, Serialize};
 /// Turn any particular type of PropertiesObject
 /// to list of code - Value pairs
 pub(crate) trait Properties: Sized {
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>>;
+    /// property identifiers that are allowed for this packet type
+    const TABLE: &'static PropertyTable;
+
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>>;
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<Self>;
 
+    /// Checks the decoded properties against the property table
+    /// of this packet type before turning them into the properties object
+    fn decode_properties(props: Vec<(u8, PropType)>) -> Res<Self> {
+        Self::TABLE.check(&props)?;
+        Self::from_properties(props)
+    }
+
     /// packet.encode() will move the value since a packet is usually built to
     /// be encoded and sent anyway
     fn encode(&self) -> Res<Vec<u8>> {
         // Confirm should not add empty property length with no properties (rfc 3.4.2.2.1)
         let pairs = self.to_pairs()?;
         if pairs.is_empty() {
-            return Ok(vec![0]); // empty properties
+            return Ok(vec![]); // empty properties
         }
         // TODO: calculate size of properties before allocating buffer
         let mut writer = MqttWriter::new(100);
@@ -24,14 +35,22 @@ pub(crate) trait Properties: Sized {
         Ok(writer.into_vec())
     }
 
-    fn encode_option(props: Option<&Self>, protocol_version: u8) -> Res<(Vec<u8>, Vec<u8>)> {
+    fn encode_option(
+        props: Option<&Self>,
+        protocol_version: ProtocolVersion,
+    ) -> Res<(Vec<u8>, Vec<u8>)> {
         // Confirm should not add empty property length with no properties (rfc 3.4.2.2.1)
-        if protocol_version == 5 {
-            if let Some(p) = props {
-                let enc = p.encode()?;
-                Ok((MqttWriter::encode_variable_num(enc.len() as u32), enc))
-            } else {
-                Ok((vec![0], vec![])) // empty properties
+        if protocol_version.has_properties() {
+            match props.map(|p| p.encode()).transpose()? {
+                Some(enc) if enc.len() > VARBYTEINT_MAX as usize => Err(format!(
+                    "Properties length {} exceeds the maximum of {}",
+                    enc.len(),
+                    VARBYTEINT_MAX
+                )),
+                Some(enc) if !enc.is_empty() => {
+                    Ok((MqttWriter::encode_variable_num(enc.len() as u32), enc))
+                }
+                _ => Ok((vec![0], vec![])), // empty properties
             }
         } else {
             Ok((vec![], vec![])) // no properties exist in MQTT < 5
@@ -59,17 +78,26 @@ pub enum PropType<'a> {
     VarInt(u32),
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct AuthProperties {
+    /// It is a Protocol Error to omit the Authentication Method
     pub authentication_method: String,
     pub authentication_data: Option<String>,
     pub reason_string: Option<String>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
 }
 
 impl Properties for AuthProperties {
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
+    const TABLE: &'static PropertyTable = &AUTH_PROPERTIES;
+
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
         let mut out = vec![(0x15, PropType::Str(&self.authentication_method))];
         if let Some(s) = self.authentication_data.as_ref() {
             out.push((0x16, PropType::Str(s)));
@@ -78,7 +106,7 @@ impl Properties for AuthProperties {
             out.push((0x1F, PropType::Str(s)));
         }
         if !self.user_properties.is_empty() {
-            out.push((0x26, PropType::Map(self.user_properties.clone())));
+            out.push((0x26, PropType::MapRef(&self.user_properties)));
         }
         Ok(out)
     }
@@ -86,17 +114,26 @@ impl Properties for AuthProperties {
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<AuthProperties> {
         let mut reason_string = None;
         let mut user_properties = UserProperties::new();
-        let mut authentication_method = String::new();
+        let mut authentication_method = None;
         let mut authentication_data = None;
         for p in props {
             match p {
                 (0x1F, PropType::String(v)) => reason_string = Some(v),
                 (0x26, PropType::Map(v)) => user_properties = v,
-                (0x15, PropType::String(v)) => authentication_method = v,
+                (0x15, PropType::String(v)) => authentication_method = Some(v),
                 (0x16, PropType::String(v)) => authentication_data = Some(v),
                 s => return Err(format!("Failed to parse auth properties {:?}", s)),
             }
         }
+        let authentication_method = match authentication_method {
+            Some(m) => m,
+            None => {
+                return Err(
+                    "Protocol error: AUTH properties must contain an Authentication Method"
+                        .to_string(),
+                )
+            }
+        };
         Ok(AuthProperties {
             reason_string,
             user_properties,
@@ -106,58 +143,69 @@ impl Properties for AuthProperties {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct PublishProperties {
-    pub payload_format_indicator: bool,
+    /// None if absent, see payload_format_indicator() for the default
+    pub payload_format_indicator: Option<bool>,
     pub message_expiry_interval: Option<u32>,
     pub content_type: Option<String>,
     pub response_topic: Option<String>,
-    pub correlation_data: Vec<u8>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, with = "crate::structure::json::option_binary")
+    )]
+    pub correlation_data: Option<Vec<u8>>,
     // Can be multiple identifiers
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub subscription_identifiers: Vec<u32>,
     // topic alias is None if absent
     pub topic_alias: Option<u16>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
 }
 
+impl PublishProperties {
+    /// an absent payload format indicator means unspecified bytes (false)
+    pub fn payload_format_indicator(&self) -> bool {
+        self.payload_format_indicator.unwrap_or(false)
+    }
+}
+
 impl Properties for PublishProperties {
+    const TABLE: &'static PropertyTable = &PUBLISH_PROPERTIES;
+
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<PublishProperties> {
-        let mut user_properties = UserProperties::new();
-        let mut payload_format_indicator = false;
-        let mut message_expiry_interval = None;
-        let mut content_type = None;
-        let mut response_topic = None;
-        let mut correlation_data = vec![];
-        let mut subscription_identifiers = vec![];
-        let mut topic_alias = None;
+        let mut out = PublishProperties::default();
         for p in props {
             match p {
-                (0x26, PropType::Map(v)) => user_properties = v,
-                (0x01, PropType::Bool(v)) => payload_format_indicator = v,
-                (0x02, PropType::U32(v)) => message_expiry_interval = Some(v),
-                (0x03, PropType::String(v)) => content_type = Some(v),
-                (0x08, PropType::String(v)) => response_topic = Some(v),
-                (0x09, PropType::Binary(v)) => correlation_data = v,
-                (0x0B, PropType::VarInt(v)) => subscription_identifiers.push(v),
-                (0x23, PropType::U16(v)) => topic_alias = Some(v),
+                (0x26, PropType::Map(v)) => out.user_properties = v,
+                (0x01, PropType::Bool(v)) => out.payload_format_indicator = Some(v),
+                (0x02, PropType::U32(v)) => out.message_expiry_interval = Some(v),
+                (0x03, PropType::String(v)) => out.content_type = Some(v),
+                (0x08, PropType::String(v)) => out.response_topic = Some(v),
+                (0x09, PropType::Binary(v)) => out.correlation_data = Some(v),
+                (0x0B, PropType::VarInt(v)) => out.subscription_identifiers.push(v),
+                (0x23, PropType::U16(v)) => out.topic_alias = Some(v),
                 s => return Err(format!("Failed to parse publish properties {:?}", s)),
             }
         }
-        Ok(PublishProperties {
-            subscription_identifiers,
-            user_properties,
-            payload_format_indicator,
-            message_expiry_interval,
-            content_type,
-            response_topic,
-            correlation_data,
-            topic_alias,
-        })
+        Ok(out)
     }
 
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
-        let mut out = vec![(0x01, PropType::Bool(self.payload_format_indicator))];
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
+        let mut out = vec![];
+        if let Some(v) = self.payload_format_indicator {
+            out.push((0x01, PropType::Bool(v)));
+        }
         if let Some(v) = self.message_expiry_interval {
             out.push((0x02, PropType::U32(v)));
         }
@@ -167,8 +215,8 @@ impl Properties for PublishProperties {
         if let Some(v) = self.response_topic.as_ref() {
             out.push((0x08, PropType::Str(v)));
         }
-        if !self.correlation_data.is_empty() {
-            out.push((0x09, PropType::BinaryRef(&self.correlation_data)));
+        if let Some(v) = self.correlation_data.as_ref() {
+            out.push((0x09, PropType::BinaryRef(v)));
         }
         if !self.user_properties.is_empty() {
             out.push((0x26, PropType::MapRef(&self.user_properties)));
@@ -188,36 +236,39 @@ impl Properties for PublishProperties {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct SubscribeProperties {
-    /// subscription_identifiers is a variable length int
-    /// and is not allowed to be 0. If it is set to 0 it won't
-    /// encode/decode, therefore we're using 0 as None here
-    pub subscription_identifier: u32,
+    /// subscription_identifier is a variable length int
+    /// and is not allowed to be 0
+    pub subscription_identifier: Option<u32>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
 }
 
 impl Properties for SubscribeProperties {
+    const TABLE: &'static PropertyTable = &SUBSCRIBE_PROPERTIES;
+
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<SubscribeProperties> {
-        let mut subscription_identifier = 0;
-        let mut user_properties = UserProperties::new();
+        let mut out = SubscribeProperties::default();
         for p in props {
             match p {
-                (0x0B, PropType::VarInt(v)) => subscription_identifier = v,
-                (0x26, PropType::Map(v)) => user_properties = v,
+                (0x0B, PropType::VarInt(v)) => out.subscription_identifier = Some(v),
+                (0x26, PropType::Map(v)) => out.user_properties = v,
                 s => return Err(format!("Failed to parse subscribe properties {:?}", s)),
             }
         }
-        Ok(SubscribeProperties {
-            subscription_identifier,
-            user_properties,
-        })
+        Ok(out)
     }
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
         let mut out = vec![];
-        if self.subscription_identifier > 0 {
-            out.push((0x0B, PropType::VarInt(self.subscription_identifier)));
+        if let Some(v) = self.subscription_identifier {
+            out.push((0x0B, PropType::VarInt(v)));
         }
         if !self.user_properties.is_empty() {
             out.push((0x26, PropType::MapRef(&self.user_properties)));
@@ -226,23 +277,26 @@ impl Properties for SubscribeProperties {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct DisconnectProperties {
     pub session_expiry_interval: Option<u32>,
     pub server_reference: Option<String>,
     pub reason_string: Option<String>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
 }
 
 impl Properties for DisconnectProperties {
+    const TABLE: &'static PropertyTable = &DISCONNECT_PROPERTIES;
+
     fn from_properties(prop_list: Vec<(u8, PropType)>) -> Res<DisconnectProperties> {
-        let mut props = DisconnectProperties {
-            session_expiry_interval: None,
-            server_reference: None,
-            reason_string: None,
-            user_properties: UserProperties::new(),
-        };
+        let mut props = DisconnectProperties::default();
         for p in prop_list {
             match p {
                 (0x11, PropType::U32(v)) => props.session_expiry_interval = Some(v),
@@ -255,7 +309,7 @@ impl Properties for DisconnectProperties {
         Ok(props)
     }
 
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
         let mut out = vec![];
         if let Some(s) = self.session_expiry_interval {
             out.push((0x11, PropType::U32(s)));
@@ -273,20 +327,33 @@ impl Properties for DisconnectProperties {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ConfirmationProperties {
     pub reason_string: Option<String>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct UnsubscribeProperties {
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
 }
 
 impl Properties for UnsubscribeProperties {
+    const TABLE: &'static PropertyTable = &UNSUBSCRIBE_PROPERTIES;
+
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<UnsubscribeProperties> {
         let mut user_properties = UserProperties::new();
         for p in props {
@@ -298,7 +365,7 @@ impl Properties for UnsubscribeProperties {
         Ok(UnsubscribeProperties { user_properties })
     }
 
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
         let mut out = vec![];
         if !self.user_properties.is_empty() {
             out.push((0x26, PropType::MapRef(&self.user_properties)));
@@ -307,34 +374,57 @@ impl Properties for UnsubscribeProperties {
     }
 }
 
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
 #[derive(PartialEq, Debug, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct WillProperties {
-    /// default value is false, both if set to false
-    /// and when no value was provided
-    pub payload_format_indicator: bool,
+    /// None if absent, see payload_format_indicator() for the default
+    pub payload_format_indicator: Option<bool>,
     /// None if no value was given, because
     /// apparently 0 is a valid expiry
     pub message_expiry_interval: Option<u32>,
     pub content_type: Option<String>,
     pub response_topic: Option<String>,
-    pub correlation_data: Vec<u8>,
-    /// 0 is default when no value was provided
-    pub will_delay_interval: u32,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, with = "crate::structure::json::option_binary")
+    )]
+    pub correlation_data: Option<Vec<u8>>,
+    /// None if absent, see will_delay_interval() for the default
+    pub will_delay_interval: Option<u32>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
 }
 
+impl WillProperties {
+    /// an absent payload format indicator means unspecified bytes (false)
+    pub fn payload_format_indicator(&self) -> bool {
+        self.payload_format_indicator.unwrap_or(false)
+    }
+
+    /// an absent will delay means the will is published without delay
+    pub fn will_delay_interval(&self) -> u32 {
+        self.will_delay_interval.unwrap_or(0)
+    }
+}
+
 impl Properties for WillProperties {
+    const TABLE: &'static PropertyTable = &WILL_PROPERTIES;
+
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<WillProperties> {
         let mut out = WillProperties::default();
         for p in props {
             match p {
-                (0x01, PropType::Bool(v)) => out.payload_format_indicator = v,
+                (0x01, PropType::Bool(v)) => out.payload_format_indicator = Some(v),
                 (0x02, PropType::U32(v)) => out.message_expiry_interval = Some(v),
                 (0x03, PropType::String(v)) => out.content_type = Some(v),
                 (0x08, PropType::String(v)) => out.response_topic = Some(v),
-                (0x09, PropType::Binary(v)) => out.correlation_data = v,
-                (0x18, PropType::U32(v)) => out.will_delay_interval = v,
+                (0x09, PropType::Binary(v)) => out.correlation_data = Some(v),
+                (0x18, PropType::U32(v)) => out.will_delay_interval = Some(v),
                 (0x26, PropType::Map(v)) => out.user_properties = v,
                 s => return Err(format!("Failed to parse will properties {:?}", s)),
             }
@@ -342,11 +432,14 @@ impl Properties for WillProperties {
         Ok(out)
     }
 
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
-        let mut out = vec![
-            (0x18, PropType::U32(self.will_delay_interval)),
-            (0x01, PropType::Bool(self.payload_format_indicator)),
-        ];
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
+        let mut out = vec![];
+        if let Some(v) = self.will_delay_interval {
+            out.push((0x18, PropType::U32(v)));
+        }
+        if let Some(v) = self.payload_format_indicator {
+            out.push((0x01, PropType::Bool(v)));
+        }
         if let Some(v) = self.message_expiry_interval {
             out.push((0x02, PropType::U32(v)));
         }
@@ -356,20 +449,26 @@ impl Properties for WillProperties {
         if let Some(v) = self.response_topic.as_ref() {
             out.push((0x08, PropType::Str(v)));
         }
-        if !self.correlation_data.is_empty() {
-            out.push((0x09, PropType::BinaryRef(&self.correlation_data)));
+        if let Some(v) = self.correlation_data.as_ref() {
+            out.push((0x09, PropType::BinaryRef(v)));
         }
         if !self.user_properties.is_empty() {
-            out.push((0x26, PropType::Map(self.user_properties.clone())));
+            out.push((0x26, PropType::MapRef(&self.user_properties)));
         }
         Ok(out)
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Every property that is absent on the wire is None. Accessor methods
+/// with the same name as the field return the value the spec assumes
+/// when the property is absent
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(Debug, PartialEq, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ConnackProperties {
-    pub session_expiry_interval: u32,
+    /// if absent the value from CONNECT is used
+    pub session_expiry_interval: Option<u32>,
     pub assigned_client_identifier: Option<String>,
     pub server_keep_alive: Option<u16>,
     pub authentication_method: Option<String>,
@@ -377,52 +476,66 @@ pub struct ConnackProperties {
     pub response_information: Option<String>,
     pub server_reference: Option<String>,
     pub reason_string: Option<String>,
-    pub receive_maximum: u16,
-    pub topic_alias_maximum: u16,
-    pub maximum_qos: u8,
-    pub retain_available: bool,
+    pub receive_maximum: Option<u16>,
+    pub topic_alias_maximum: Option<u16>,
+    pub maximum_qos: Option<QoS>,
+    pub retain_available: Option<bool>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
     pub maximum_packet_size: Option<u32>,
-    pub wildcard_subscription_available: bool,
-    pub subscription_identifiers_available: bool,
-    pub shared_subscription_available: bool,
-    pub is_default: bool,
+    pub wildcard_subscription_available: Option<bool>,
+    pub subscription_identifiers_available: Option<bool>,
+    pub shared_subscription_available: Option<bool>,
 }
 
-impl Default for ConnackProperties {
-    fn default() -> ConnackProperties {
-        ConnackProperties {
-            is_default: true,
-            session_expiry_interval: 0,
-            receive_maximum: 0xffff,
-            maximum_packet_size: None,
-            topic_alias_maximum: 0,
-            response_information: None,
-            // request_problem_information: true,
-            user_properties: UserProperties::new(),
-            assigned_client_identifier: None,
-            server_keep_alive: None,
-            authentication_method: None,
-            authentication_data: None,
-            server_reference: None,
-            reason_string: None,
-            maximum_qos: 2,
-            // retained messages available by default
-            retain_available: true,
-            wildcard_subscription_available: false,
-            subscription_identifiers_available: false,
-            shared_subscription_available: false,
-        }
+impl ConnackProperties {
+    /// defaults to 65,535
+    pub fn receive_maximum(&self) -> u16 {
+        self.receive_maximum.unwrap_or(0xffff)
+    }
+
+    /// defaults to 0, i.e. no topic aliases are accepted
+    pub fn topic_alias_maximum(&self) -> u16 {
+        self.topic_alias_maximum.unwrap_or(0)
+    }
+
+    /// defaults to 2, i.e. all QoS levels are supported
+    pub fn maximum_qos(&self) -> QoS {
+        self.maximum_qos.unwrap_or(QoS::QoS2)
+    }
+
+    /// retained messages are available by default
+    pub fn retain_available(&self) -> bool {
+        self.retain_available.unwrap_or(true)
+    }
+
+    /// wildcard subscriptions are supported by default
+    pub fn wildcard_subscription_available(&self) -> bool {
+        self.wildcard_subscription_available.unwrap_or(true)
+    }
+
+    /// subscription identifiers are supported by default
+    pub fn subscription_identifiers_available(&self) -> bool {
+        self.subscription_identifiers_available.unwrap_or(true)
+    }
+
+    /// shared subscriptions are supported by default
+    pub fn shared_subscription_available(&self) -> bool {
+        self.shared_subscription_available.unwrap_or(true)
     }
 }
 
 impl Properties for ConnackProperties {
+    const TABLE: &'static PropertyTable = &CONNACK_PROPERTIES;
+
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<ConnackProperties> {
         let mut out = ConnackProperties::default();
         for p in props {
-            out.is_default = false;
             match p {
-                (0x11, PropType::U32(v)) => out.session_expiry_interval = v,
+                (0x11, PropType::U32(v)) => out.session_expiry_interval = Some(v),
                 (0x12, PropType::String(v)) => out.assigned_client_identifier = Some(v),
                 (0x13, PropType::U16(v)) => out.server_keep_alive = Some(v),
                 (0x15, PropType::String(v)) => out.authentication_method = Some(v),
@@ -430,30 +543,29 @@ impl Properties for ConnackProperties {
                 (0x1A, PropType::String(v)) => out.response_information = Some(v),
                 (0x1C, PropType::String(v)) => out.server_reference = Some(v),
                 (0x1F, PropType::String(v)) => out.reason_string = Some(v),
-                (0x21, PropType::U16(v)) => out.receive_maximum = v,
-                (0x22, PropType::U16(v)) => out.topic_alias_maximum = v,
-                (0x24, PropType::U8(v)) => out.maximum_qos = v,
-                (0x25, PropType::Bool(v)) => out.retain_available = v,
+                (0x21, PropType::U16(v)) => out.receive_maximum = Some(v),
+                (0x22, PropType::U16(v)) => out.topic_alias_maximum = Some(v),
+                (0x24, PropType::U8(v)) => out.maximum_qos = Some(QoS::from_byte(v)?),
+                (0x25, PropType::Bool(v)) => out.retain_available = Some(v),
                 (0x26, PropType::Map(v)) => out.user_properties = v,
                 (0x27, PropType::U32(v)) => out.maximum_packet_size = Some(v),
-                (0x28, PropType::Bool(v)) => out.wildcard_subscription_available = v,
-                (0x29, PropType::Bool(v)) => out.subscription_identifiers_available = v,
-                (0x2A, PropType::Bool(v)) => out.shared_subscription_available = v,
+                (0x28, PropType::Bool(v)) => out.wildcard_subscription_available = Some(v),
+                (0x29, PropType::Bool(v)) => out.subscription_identifiers_available = Some(v),
+                (0x2A, PropType::Bool(v)) => out.shared_subscription_available = Some(v),
                 v => return Err(format!("Failed to get connack properties {:?}", v)),
             }
         }
         Ok(out)
     }
 
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
         let mut out = vec![];
         if !self.user_properties.is_empty() {
-            out.push((0x26, PropType::Map(self.user_properties.clone())));
+            out.push((0x26, PropType::MapRef(&self.user_properties)));
+        }
+        if let Some(v) = self.session_expiry_interval {
+            out.push((0x11, PropType::U32(v)));
         }
-        // TODO remove irrefutable patterns, might need to change structure
-        // if let v = self.session_expiry_interval {
-        out.push((0x11, PropType::U32(self.session_expiry_interval)));
-        // }
         if let Some(v) = self.assigned_client_identifier.as_ref() {
             out.push((0x12, PropType::Str(v)));
         }
@@ -475,52 +587,37 @@ impl Properties for ConnackProperties {
         if let Some(v) = self.reason_string.as_ref() {
             out.push((0x1F, PropType::Str(v)));
         }
-        // TODO: check all properties and maybe change types to Option<value>
-        // if let v = self.receive_maximum {
-        out.push((0x21, PropType::U16(self.receive_maximum)));
-        // }
-        // if let v = self.topic_alias_maximum {
-        out.push((0x22, PropType::U16(self.topic_alias_maximum)));
-        // }
-        // if let v = self.maximum_qos {
-        out.push((0x24, PropType::U8(self.maximum_qos)));
-        // }
-        // if let v = self.retain_available {
-        out.push((0x25, PropType::Bool(self.retain_available)));
-        // }
+        if let Some(v) = self.receive_maximum {
+            out.push((0x21, PropType::U16(v)));
+        }
+        if let Some(v) = self.topic_alias_maximum {
+            out.push((0x22, PropType::U16(v)));
+        }
+        if let Some(v) = self.maximum_qos {
+            out.push((0x24, PropType::U8(v.to_byte())));
+        }
+        if let Some(v) = self.retain_available {
+            out.push((0x25, PropType::Bool(v)));
+        }
         if let Some(v) = self.maximum_packet_size {
             out.push((0x27, PropType::U32(v)));
         }
-        // if let v = self.wildcard_subscription_available {
-        out.push((0x28, PropType::Bool(self.wildcard_subscription_available)));
-        // }
-        // if let v = self.subscription_identifiers_available {
-        out.push((
-            0x29,
-            PropType::Bool(self.subscription_identifiers_available),
-        ));
-        // }
-        // if let v = self.shared_subscription_available {
-        out.push((0x2A, PropType::Bool(self.shared_subscription_available)));
-        // }
+        if let Some(v) = self.wildcard_subscription_available {
+            out.push((0x28, PropType::Bool(v)));
+        }
+        if let Some(v) = self.subscription_identifiers_available {
+            out.push((0x29, PropType::Bool(v)));
+        }
+        if let Some(v) = self.shared_subscription_available {
+            out.push((0x2A, PropType::Bool(v)));
+        }
         Ok(out)
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
-/// A struct for PUBACK, PUBCOMP, PUBREL and PUBREC
-pub struct ConfirmationPacket {
-    pub cmd: PacketType,
-    /// Reason code is always 0 by default for MQTT 5
-    /// but absent for MQTT 3 and 4
-    pub puback_reason_code: Option<PubackPubrecCode>,
-    pub pubcomp_reason_code: Option<PubcompPubrelCode>,
-    pub properties: Option<ConfirmationProperties>,
-    pub message_id: u16,
-}
-
 impl Properties for ConfirmationProperties {
+    const TABLE: &'static PropertyTable = &CONFIRMATION_PROPERTIES;
+
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<ConfirmationProperties> {
         let mut reason_string = None;
         let mut user_properties = UserProperties::new();
@@ -537,7 +634,7 @@ impl Properties for ConfirmationProperties {
         })
     }
 
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
         let mut out = vec![];
         if let Some(s) = self.reason_string.as_ref() {
             out.push((0x1F, PropType::Str(s)));
@@ -549,57 +646,71 @@ impl Properties for ConfirmationProperties {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+/// Every property that is absent on the wire is None. Accessor methods
+/// with the same name as the field return the value the spec assumes
+/// when the property is absent
+#[cfg_attr(feature = "serde_support", serde_with::skip_serializing_none)]
+#[derive(PartialEq, Debug, Clone, Default)]
 #[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ConnectProperties {
-    // defaults to 0
-    pub session_expiry_interval: u32,
-    // defaults to 65,535
-    pub receive_maximum: u16,
+    pub session_expiry_interval: Option<u32>,
+    pub receive_maximum: Option<u16>,
     // if None then no limit
     pub maximum_packet_size: Option<u32>,
-    // default value is 0
-    pub topic_alias_maximum: u16,
-    // default is false
-    pub request_response_information: bool,
-    // default is true
-    pub request_problem_information: bool,
-    // default is just an empty hashMap
+    pub topic_alias_maximum: Option<u16>,
+    pub request_response_information: Option<bool>,
+    pub request_problem_information: Option<bool>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(default, skip_serializing_if = "crate::structure::json::is_default")
+    )]
     pub user_properties: UserProperties,
-    // default is None
     pub authentication_method: Option<String>,
     pub authentication_data: Option<String>,
 }
 
-impl Default for ConnectProperties {
-    fn default() -> ConnectProperties {
-        ConnectProperties {
-            session_expiry_interval: 0,
-            receive_maximum: 0xffff,
-            maximum_packet_size: None,
-            topic_alias_maximum: 0,
-            request_response_information: false,
-            request_problem_information: true,
-            user_properties: UserProperties::new(),
-            // extended_auth: None,
-            authentication_method: None,
-            authentication_data: None,
-        }
+impl ConnectProperties {
+    /// defaults to 0, i.e. the session ends when the connection is closed
+    pub fn session_expiry_interval(&self) -> u32 {
+        self.session_expiry_interval.unwrap_or(0)
+    }
+
+    /// defaults to 65,535
+    pub fn receive_maximum(&self) -> u16 {
+        self.receive_maximum.unwrap_or(0xffff)
+    }
+
+    /// defaults to 0, i.e. no topic aliases are accepted
+    pub fn topic_alias_maximum(&self) -> u16 {
+        self.topic_alias_maximum.unwrap_or(0)
+    }
+
+    /// default is false
+    pub fn request_response_information(&self) -> bool {
+        self.request_response_information.unwrap_or(false)
+    }
+
+    /// default is true
+    pub fn request_problem_information(&self) -> bool {
+        self.request_problem_information.unwrap_or(true)
     }
 }
 
 impl Properties for ConnectProperties {
+    const TABLE: &'static PropertyTable = &CONNECT_PROPERTIES;
+
     fn from_properties(props: Vec<(u8, PropType)>) -> Res<ConnectProperties> {
         let mut out = ConnectProperties::default();
         for p in props {
             match p {
-                (0x11, PropType::U32(v)) => out.session_expiry_interval = v,
+                (0x11, PropType::U32(v)) => out.session_expiry_interval = Some(v),
                 (0x15, PropType::String(v)) => out.authentication_method = Some(v),
                 (0x16, PropType::String(v)) => out.authentication_data = Some(v),
-                (0x17, PropType::Bool(v)) => out.request_problem_information = v,
-                (0x19, PropType::Bool(v)) => out.request_response_information = v,
-                (0x21, PropType::U16(v)) => out.receive_maximum = v,
-                (0x22, PropType::U16(v)) => out.topic_alias_maximum = v,
+                (0x17, PropType::Bool(v)) => out.request_problem_information = Some(v),
+                (0x19, PropType::Bool(v)) => out.request_response_information = Some(v),
+                (0x21, PropType::U16(v)) => out.receive_maximum = Some(v),
+                (0x22, PropType::U16(v)) => out.topic_alias_maximum = Some(v),
                 (0x26, PropType::Map(v)) => out.user_properties = v,
                 (0x27, PropType::U32(v)) => out.maximum_packet_size = Some(v),
                 s => return Err(format!("Failed to parse connect properties {:?}", s)),
@@ -608,26 +719,29 @@ impl Properties for ConnectProperties {
         Ok(out)
     }
 
-    fn to_pairs(&self) -> Res<Vec<(u8, PropType)>> {
-        let mut out = vec![
-            (0x11, PropType::U32(self.session_expiry_interval)),
-            (0x21, PropType::U16(self.receive_maximum)),
-        ];
+    fn to_pairs(&self) -> Res<Vec<(u8, PropType<'_>)>> {
+        let mut out = vec![];
+        if let Some(v) = self.session_expiry_interval {
+            out.push((0x11, PropType::U32(v)));
+        }
+        if let Some(v) = self.receive_maximum {
+            out.push((0x21, PropType::U16(v)));
+        }
         if let Some(v) = self.maximum_packet_size {
             out.push((0x27, PropType::U32(v)));
         }
-        // if let v = self.topic_alias_maximum {
-        out.push((0x22, PropType::U16(self.topic_alias_maximum)));
-        // }
-        // if let v = self.request_response_information {
-        out.push((0x19, PropType::Bool(self.request_response_information)));
-        // }
-        // if let v = self.request_problem_information {
-        out.push((0x17, PropType::Bool(self.request_problem_information)));
-        // }
-        // if let v = self.user_properties {
-        out.push((0x26, PropType::MapRef(&self.user_properties)));
-        // }
+        if let Some(v) = self.topic_alias_maximum {
+            out.push((0x22, PropType::U16(v)));
+        }
+        if let Some(v) = self.request_response_information {
+            out.push((0x19, PropType::Bool(v)));
+        }
+        if let Some(v) = self.request_problem_information {
+            out.push((0x17, PropType::Bool(v)));
+        }
+        if !self.user_properties.is_empty() {
+            out.push((0x26, PropType::MapRef(&self.user_properties)));
+        }
         if let Some(v) = self.authentication_method.as_ref() {
             out.push((0x15, PropType::Str(v)));
         }
@@ -0,0 +1,202 @@
+use super::common::*;
+use super::properties::PropType;
+
+/// How often a property is allowed to appear in a single
+/// properties section
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Multiplicity {
+    /// It is a Protocol Error to include the property more than once
+    Single,
+    /// The property may appear any number of times (User Property,
+    /// Subscription Identifier in PUBLISH)
+    Multiple,
+}
+
+/// Lists the property identifiers that are allowed in the properties of
+/// a specific packet type (or a will) according to the table in chapter 2.2.2.2
+/// of the MQTT 5 spec
+#[derive(Debug, PartialEq)]
+pub struct PropertyTable {
+    /// name used in error messages, e.g. "CONNECT" or "will"
+    pub name: &'static str,
+    pub allowed: &'static [(u8, Multiplicity)],
+}
+
+use Multiplicity::*;
+
+pub static CONNECT_PROPERTIES: PropertyTable = PropertyTable {
+    name: "CONNECT",
+    allowed: &[
+        (0x11, Single),
+        (0x15, Single),
+        (0x16, Single),
+        (0x17, Single),
+        (0x19, Single),
+        (0x21, Single),
+        (0x22, Single),
+        (0x26, Multiple),
+        (0x27, Single),
+    ],
+};
+
+pub static WILL_PROPERTIES: PropertyTable = PropertyTable {
+    name: "will",
+    allowed: &[
+        (0x01, Single),
+        (0x02, Single),
+        (0x03, Single),
+        (0x08, Single),
+        (0x09, Single),
+        (0x18, Single),
+        (0x26, Multiple),
+    ],
+};
+
+pub static CONNACK_PROPERTIES: PropertyTable = PropertyTable {
+    name: "CONNACK",
+    allowed: &[
+        (0x11, Single),
+        (0x12, Single),
+        (0x13, Single),
+        (0x15, Single),
+        (0x16, Single),
+        (0x1A, Single),
+        (0x1C, Single),
+        (0x1F, Single),
+        (0x21, Single),
+        (0x22, Single),
+        (0x24, Single),
+        (0x25, Single),
+        (0x26, Multiple),
+        (0x27, Single),
+        (0x28, Single),
+        (0x29, Single),
+        (0x2A, Single),
+    ],
+};
+
+pub static PUBLISH_PROPERTIES: PropertyTable = PropertyTable {
+    name: "PUBLISH",
+    allowed: &[
+        (0x01, Single),
+        (0x02, Single),
+        (0x03, Single),
+        (0x08, Single),
+        (0x09, Single),
+        (0x0B, Multiple),
+        (0x23, Single),
+        (0x26, Multiple),
+    ],
+};
+
+/// shared by PUBACK, PUBREC, PUBREL, PUBCOMP, SUBACK and UNSUBACK
+pub static CONFIRMATION_PROPERTIES: PropertyTable = PropertyTable {
+    name: "acknowledgement",
+    allowed: &[(0x1F, Single), (0x26, Multiple)],
+};
+
+pub static SUBSCRIBE_PROPERTIES: PropertyTable = PropertyTable {
+    name: "SUBSCRIBE",
+    allowed: &[(0x0B, Single), (0x26, Multiple)],
+};
+
+pub static UNSUBSCRIBE_PROPERTIES: PropertyTable = PropertyTable {
+    name: "UNSUBSCRIBE",
+    allowed: &[(0x26, Multiple)],
+};
+
+pub static DISCONNECT_PROPERTIES: PropertyTable = PropertyTable {
+    name: "DISCONNECT",
+    allowed: &[
+        (0x11, Single),
+        (0x1C, Single),
+        (0x1F, Single),
+        (0x26, Multiple),
+    ],
+};
+
+pub static AUTH_PROPERTIES: PropertyTable = PropertyTable {
+    name: "AUTH",
+    allowed: &[
+        (0x15, Single),
+        (0x16, Single),
+        (0x1F, Single),
+        (0x26, Multiple),
+    ],
+};
+
+impl PropertyTable {
+    pub fn multiplicity(&self, id: u8) -> Option<Multiplicity> {
+        self.allowed
+            .iter()
+            .find(|(allowed, _)| *allowed == id)
+            .map(|(_, m)| *m)
+    }
+
+    /// Returns a protocol error if a property is not allowed for this
+    /// packet type or if a single valued property appears more than once
+    pub fn check(&self, props: &[(u8, PropType)]) -> Res<()> {
+        let mut seen: Vec<u8> = Vec::with_capacity(props.len());
+        for (id, _) in props {
+            match self.multiplicity(*id) {
+                None => {
+                    return Err(format!(
+                        "Protocol error: property {} is not allowed in {} properties",
+                        describe_property(*id),
+                        self.name
+                    ))
+                }
+                Some(Single) if seen.contains(id) => {
+                    return Err(format!(
+                        "Protocol error: duplicate property {} in {} properties",
+                        describe_property(*id),
+                        self.name
+                    ))
+                }
+                _ => seen.push(*id),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Name of a property identifier as used in the MQTT 5 spec
+pub fn property_name(id: u8) -> Option<&'static str> {
+    Some(match id {
+        0x01 => "Payload Format Indicator",
+        0x02 => "Message Expiry Interval",
+        0x03 => "Content Type",
+        0x08 => "Response Topic",
+        0x09 => "Correlation Data",
+        0x0B => "Subscription Identifier",
+        0x11 => "Session Expiry Interval",
+        0x12 => "Assigned Client Identifier",
+        0x13 => "Server Keep Alive",
+        0x15 => "Authentication Method",
+        0x16 => "Authentication Data",
+        0x17 => "Request Problem Information",
+        0x18 => "Will Delay Interval",
+        0x19 => "Request Response Information",
+        0x1A => "Response Information",
+        0x1C => "Server Reference",
+        0x1F => "Reason String",
+        0x21 => "Receive Maximum",
+        0x22 => "Topic Alias Maximum",
+        0x23 => "Topic Alias",
+        0x24 => "Maximum QoS",
+        0x25 => "Retain Available",
+        0x26 => "User Property",
+        0x27 => "Maximum Packet Size",
+        0x28 => "Wildcard Subscription Available",
+        0x29 => "Subscription Identifier Available",
+        0x2A => "Shared Subscription Available",
+        _ => return None,
+    })
+}
+
+fn describe_property(id: u8) -> String {
+    match property_name(id) {
+        Some(name) => format!("{} (0x{:02X})", name, id),
+        None => format!("0x{:02X}", id),
+    }
+}
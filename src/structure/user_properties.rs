@@ -0,0 +1,155 @@
+#[cfg(feature = "serde_support")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Ordered multimap of MQTT 5 user properties.
+///
+/// The spec requires the order of user properties to be preserved when
+/// forwarding a packet, including the relative order of different keys,
+/// and the same key is allowed to appear more than once. Therefore the
+/// pairs are kept in a plain list in the order they were added or read
+/// from the wire, which also makes encoding deterministic.
+///
+/// Serialized as a list of `{"key": ..., "value": ...}` objects.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(Serialize, Deserialize),
+    serde(
+        from = "Vec<super::json::UserProperty>",
+        into = "Vec<super::json::UserProperty>"
+    )
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct UserProperties {
+    pairs: Vec<(String, String)>,
+}
+
+impl UserProperties {
+    pub fn new() -> UserProperties {
+        UserProperties { pairs: vec![] }
+    }
+
+    /// appends a key-value pair, existing pairs with the
+    /// same key are kept
+    pub fn push<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        self.pairs.push((key.into(), value.into()));
+    }
+
+    /// returns the first value for the given key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// returns all values for the given key in wire order
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == key)
+    }
+
+    /// removes every pair with the given key and returns
+    /// the removed values in wire order
+    pub fn remove(&mut self, key: &str) -> Vec<String> {
+        let mut removed = vec![];
+        let mut kept = Vec::with_capacity(self.pairs.len());
+        for (k, v) in self.pairs.drain(..) {
+            if k == key {
+                removed.push(v);
+            } else {
+                kept.push((k, v));
+            }
+        }
+        self.pairs = kept;
+        removed
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, String)> {
+        self.pairs.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[(String, String)] {
+        &self.pairs
+    }
+
+    pub fn into_vec(self) -> Vec<(String, String)> {
+        self.pairs
+    }
+}
+
+impl From<Vec<(String, String)>> for UserProperties {
+    fn from(pairs: Vec<(String, String)>) -> UserProperties {
+        UserProperties { pairs }
+    }
+}
+
+/// Conversion from the map based representation used in earlier versions.
+/// A `HashMap` has no order, so keys are sorted to keep the
+/// result (and therefore the encoded packet) deterministic
+impl From<HashMap<String, Vec<String>>> for UserProperties {
+    fn from(map: HashMap<String, Vec<String>>) -> UserProperties {
+        let mut entries: Vec<(String, Vec<String>)> = map.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.into_iter().collect()
+    }
+}
+
+impl FromIterator<(String, String)> for UserProperties {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> UserProperties {
+        UserProperties {
+            pairs: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl FromIterator<(String, Vec<String>)> for UserProperties {
+    fn from_iter<I: IntoIterator<Item = (String, Vec<String>)>>(iter: I) -> UserProperties {
+        let mut out = UserProperties::new();
+        for (k, values) in iter {
+            for v in values {
+                out.push(k.clone(), v);
+            }
+        }
+        out
+    }
+}
+
+impl Extend<(String, String)> for UserProperties {
+    fn extend<I: IntoIterator<Item = (String, String)>>(&mut self, iter: I) {
+        self.pairs.extend(iter);
+    }
+}
+
+impl IntoIterator for UserProperties {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pairs.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a UserProperties {
+    type Item = &'a (String, String);
+    type IntoIter = std::slice::Iter<'a, (String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pairs.iter()
+    }
+}
@@ -10,14 +10,15 @@ impl SubackPacket {
     /// # Example
     ///
     /// ```
-    /// use mqtt_packet_3_5::{SubackPacket, Granted};
-    /// let packet = SubackPacket::new_v3(123, vec![Granted::QoS1]);
+    /// use mqtt_packet_3_5::{SubackPacket, Granted, SubscriptionReasonCode};
+    /// let packet = SubackPacket::new_v3(123, vec![Granted::QoS1, Granted::Failure]);
     /// assert_eq!(packet, SubackPacket {
     ///     message_id: 123,
-    ///     granted: vec![Granted::QoS1],
-    ///     granted_reason_codes: vec![], // v3 has QoS, v5 has more possible codes
-    ///     properties: None,             // v3 has no properties
-    ///     reason_code: None,            // v3 has no reason code
+    ///     granted: vec![
+    ///         SubscriptionReasonCode::GrantedQoS1,
+    ///         SubscriptionReasonCode::UnspecifiedError, // 0x80 is the same in v3 and v5
+    ///     ],
+    ///     properties: None, // v3 has no properties
     /// });
     ///
     ///
@@ -25,10 +26,11 @@ impl SubackPacket {
     pub fn new_v3(message_id: u16, granted: Vec<Granted>) -> SubackPacket {
         SubackPacket {
             message_id,
-            granted,
-            granted_reason_codes: vec![],
+            granted: granted
+                .into_iter()
+                .map(SubscriptionReasonCode::from)
+                .collect(),
             properties: None,
-            reason_code: None,
         }
     }
 }
@@ -38,23 +40,21 @@ impl Packet for SubackPacket {
         reader: &mut ByteReader<R>,
         _: FixedHeader,
         _: u32,
-        protocol_version: u8,
+        protocol_version: ProtocolVersion,
     ) -> Res<Self> {
         let message_id = reader.read_u16()?;
 
         let mut packet = SubackPacket {
-            reason_code: None,
             properties: None,
-            granted_reason_codes: vec![],
             granted: vec![],
             message_id,
         };
 
         // Properties mqtt 5
-        if protocol_version == 5 {
+        if protocol_version.has_properties() {
             packet.properties = match reader.read_properties()? {
                 None => None,
-                Some(props) => Some(ConfirmationProperties::from_properties(props)?),
+                Some(props) => Some(ConfirmationProperties::decode_properties(props)?),
             };
         }
 
@@ -65,29 +65,37 @@ impl Packet for SubackPacket {
         // Parse granted QoSes
         while reader.has_more() {
             let code = reader.read_u8()?;
-            if protocol_version == 5 {
-                packet
-                    .granted_reason_codes
-                    .push(SubscriptionReasonCode::from_byte(code)?);
+            let result = if protocol_version.has_reason_codes() {
+                SubscriptionReasonCode::from_byte(code)?
             } else {
-                packet.granted.push(Granted::from_byte(code)?);
-            }
+                SubscriptionReasonCode::from(Granted::from_byte(code)?)
+            };
+            packet.granted.push(result);
         }
         Ok(packet)
     }
 
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>> {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
         // Check message ID
         let mut length = 2;
 
-        // Check granted qos vector
-        let granted: Vec<u8> = if protocol_version == 5 {
-            self.granted_reason_codes
+        // The SUBACK packet sent by the Server to the Client MUST contain a
+        // Reason Code for each Topic Filter/Subscription pair [MQTT-3.8.4-6],
+        // and a SUBSCRIBE always has at least one
+        if self.granted.is_empty() {
+            return Err("Cannot encode SUBACK without any granted results".to_string());
+        }
+
+        // Check granted qos vector, MQTT 3 only knows 0x80 as failure. MQTT 3.1
+        // has no failure code, 0x80 is written for it as well since that is
+        // what it decodes from
+        let granted: Vec<u8> = if protocol_version.has_reason_codes() {
+            self.granted.iter().map(|code| code.to_byte()).collect()
+        } else {
+            self.granted
                 .iter()
-                .map(|code| code.to_byte())
+                .map(|code| Granted::from(code).to_byte())
                 .collect()
-        } else {
-            self.granted.iter().map(|code| code.to_byte()).collect()
         };
         length += granted.len();
 
@@ -100,7 +108,7 @@ impl Packet for SubackPacket {
         writer.write_header(FixedHeader::for_type(PacketType::Suback));
 
         // Length
-        writer.write_variable_num(length as u32)?;
+        writer.write_length(length)?;
 
         // Message ID
         writer.write_u16(self.message_id);
@@ -15,7 +15,7 @@ impl Packet for SubscribePacket {
         reader: &mut ByteReader<R>,
         fixed: FixedHeader,
         _: u32,
-        protocol_version: u8,
+        protocol_version: ProtocolVersion,
     ) -> Res<Self> {
         let message_id = reader.read_u16()?;
 
@@ -27,10 +27,10 @@ impl Packet for SubscribePacket {
         };
 
         // Properties mqtt 5
-        if protocol_version == 5 {
+        if protocol_version.has_properties() {
             packet.properties = match reader.read_properties()? {
                 None => None,
-                Some(props) => Some(SubscribeProperties::from_properties(props)?),
+                Some(props) => Some(SubscribeProperties::decode_properties(props)?),
             };
         }
 
@@ -40,15 +40,23 @@ impl Packet for SubscribePacket {
 
         while reader.has_more() {
             // Parse topic
-            let topic = reader.read_utf8_string()?;
-            let options = reader.read_u8()?;
+            reader.check_subscriptions(packet.subscriptions.len() + 1)?;
+            let topic = reader.read_topic()?;
+            let mut options = reader.read_u8()?;
 
-            if protocol_version == 5 {
+            if protocol_version.has_subscription_options() {
                 if options & 0xc0 > 0 {
                     return Err("Invalid subscribe topic flag bits, bits 7-6 must be 0".to_string());
                 }
             } else if options & 0xfc > 0 {
-                return Err("Invalid subscribe topic flag bits, bits 7-2 must be 0".to_string());
+                if !reader.options().is_lenient() {
+                    return Err("Invalid subscribe topic flag bits, bits 7-2 must be 0".to_string());
+                }
+                reader.warn(DecodeWarning::SubscriptionOptionBits {
+                    index: packet.subscriptions.len(),
+                    options,
+                });
+                options &= 0x03;
             }
 
             let qos = QoS::from_byte(options & 0x03)?;
@@ -58,20 +66,18 @@ impl Packet for SubscribePacket {
                 qos,
                 nl: false,
                 rap: false,
-                rh: None,
+                rh: RetainHandling::SendAtSubscribe,
             };
 
             // mqtt 5 options
-            if protocol_version == 5 {
+            if protocol_version.has_subscription_options() {
                 subscription.nl =
                     ((options >> SUBSCRIBE_OPTIONS_NL_SHIFT) & SUBSCRIBE_OPTIONS_NL_MASK) != 0;
                 subscription.rap =
                     ((options >> SUBSCRIBE_OPTIONS_RAP_SHIFT) & SUBSCRIBE_OPTIONS_RAP_MASK) != 0;
-                subscription.rh =
-                    match (options >> SUBSCRIBE_OPTIONS_RH_SHIFT) & SUBSCRIBE_OPTIONS_RH_MASK {
-                        rh @ (0 | 1 | 2) => Some(rh),
-                        _ => return Err("Invalid retain handling, must be <= 2".to_string()),
-                    };
+                subscription.rh = RetainHandling::from_byte(
+                    (options >> SUBSCRIBE_OPTIONS_RH_SHIFT) & SUBSCRIBE_OPTIONS_RH_MASK,
+                )?;
             }
             // TODO: include once bridge_mode is implemented
             /*else if bridge_mode {
@@ -86,7 +92,7 @@ impl Packet for SubscribePacket {
         Ok(packet)
     }
 
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>> {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
         // Check message ID
         let mut length = 2;
 
@@ -96,10 +102,6 @@ impl Packet for SubscribePacket {
                 return Err("Invalid subscriptions - empty topic".to_string());
             }
 
-            if protocol_version == 5 && (sub.rh.is_none() || sub.rh.unwrap() > 2) {
-                return Err("Invalid subscriptions - invalid Retain Handling".to_string());
-            }
-
             length += sub.topic.len() + 2 + 1;
         }
 
@@ -117,7 +119,7 @@ impl Packet for SubscribePacket {
         });
 
         // Length
-        writer.write_variable_num(length as u32)?;
+        writer.write_length(length)?;
 
         // Message ID
         writer.write_u16(self.message_id);
@@ -127,15 +129,12 @@ impl Packet for SubscribePacket {
 
         // subscriptions payload
         for sub in self.subscriptions.iter() {
-            writer.write_utf8_str(&sub.topic);
+            writer.write_utf8_str(&sub.topic)?;
             let mut options = sub.qos.to_byte();
-            if protocol_version == 5 {
+            if protocol_version.has_subscription_options() {
                 let nl = (sub.nl as u8) << SUBSCRIBE_OPTIONS_NL_SHIFT;
                 let rap = (sub.rap as u8) << SUBSCRIBE_OPTIONS_RAP_SHIFT;
-                let rh = match sub.rh {
-                    Some(0 | 1 | 2) => sub.rh.unwrap() << SUBSCRIBE_OPTIONS_RH_SHIFT,
-                    _ => return Err("Invalid retain handling, must be <= 2".to_string()),
-                };
+                let rh = sub.rh.to_byte() << SUBSCRIBE_OPTIONS_RH_SHIFT;
                 options = options | nl | rap | rh;
             }
             writer.write_u8(options);
@@ -0,0 +1,24 @@
+//! Delivery of a received PUBLISH to a matching subscription (chapter 3.3.1
+//! and 3.8.3.1 of the MQTT 5 spec), shared by retained messages and shared
+//! subscriptions
+use crate::structure::*;
+
+/// The copy of `publish` to send for a matching subscription. The QoS is
+/// reduced to the one granted for the subscription, RETAIN is only kept with
+/// Retain As Published [MQTT-3.3.1-12], [MQTT-3.3.1-13] and the topic alias of
+/// the incoming connection is dropped. The message id of a QoS 1 or 2 copy
+/// still has to be replaced with one of the outgoing session
+pub fn forward(publish: &PublishPacket, subscription: &Subscription) -> PublishPacket {
+    let mut publish = publish.clone();
+    publish.dup = false;
+    publish.retain = publish.retain && subscription.rap;
+    publish.qos = match publish.qos {
+        PublishQoS::QoS2(id) if subscription.qos == QoS::QoS1 => PublishQoS::QoS1(id),
+        qos if qos.qos() > subscription.qos => PublishQoS::QoS0,
+        qos => qos,
+    };
+    if let Some(props) = publish.properties.as_mut() {
+        props.topic_alias = None;
+    }
+    publish
+}
@@ -0,0 +1,804 @@
+//! Compact one line text form of packets for tests, scripts and logs:
+//!
+//! ```text
+//! PUBLISH qos=1 id=10 retain topic="a/b" props{expiry=60 user{k=v}} payload="hi"
+//! ```
+//!
+//! A packet is its type followed by fields, which are either flags (`retain`),
+//! `key=value` pairs, lists (`granted=[GrantedQoS0 NotAuthorized]`) or blocks
+//! (`will{...}`, `props{...}`, `sub{...}`). Strings may be left unquoted if they
+//! only consist of letters, digits, `_`, `-` and `.`; quoted strings use the
+//! escapes of Rust string literals. Binary data is a string or hex (`0x00ff`),
+//! reason codes are the names of the variants of the code enums or numbers.
+//! Properties use the same identifiers as the decoder, so a block that is not
+//! allowed for the packet type is rejected with the same error.
+use crate::packet::MqttPacket;
+use crate::structure::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// parses a packet in the text form
+pub fn parse(input: &str) -> Res<MqttPacket> {
+    let mut parser = Parser { input, pos: 0 };
+    parser.skip_whitespace();
+    let name = parser.scalar()?.text;
+    let items = parser.items(None)?;
+    let mut fields = Fields {
+        context: name.clone(),
+        items,
+    };
+    let packet = match name.as_str() {
+        "CONNECT" => MqttPacket::Connect(connect(&mut fields)?),
+        "CONNACK" => MqttPacket::Connack(ConnackPacket {
+            session_present: fields.flag("session_present")?,
+            code: fields.required_number("code")?,
+            properties: properties(fields.block("props")?)?,
+        }),
+        "PUBLISH" => MqttPacket::Publish(publish(&mut fields)?),
+        "PUBACK" => MqttPacket::Puback(PubackPacket {
+            message_id: fields.required_number("id")?,
+            reason_code: fields.code("reason")?,
+            properties: properties(fields.block("props")?)?,
+        }),
+        "PUBREC" => MqttPacket::Pubrec(PubrecPacket {
+            message_id: fields.required_number("id")?,
+            reason_code: fields.code("reason")?,
+            properties: properties(fields.block("props")?)?,
+        }),
+        "PUBREL" => MqttPacket::Pubrel(PubrelPacket {
+            message_id: fields.required_number("id")?,
+            reason_code: fields.code("reason")?,
+            properties: properties(fields.block("props")?)?,
+        }),
+        "PUBCOMP" => MqttPacket::Pubcomp(PubcompPacket {
+            message_id: fields.required_number("id")?,
+            reason_code: fields.code("reason")?,
+            properties: properties(fields.block("props")?)?,
+        }),
+        "SUBSCRIBE" => MqttPacket::Subscribe(SubscribePacket {
+            message_id: fields.required_number("id")?,
+            qos: fields.qos("qos")?.unwrap_or(QoS::QoS1),
+            properties: properties(fields.block("props")?)?,
+            subscriptions: fields
+                .blocks("sub")
+                .into_iter()
+                .map(subscription)
+                .collect::<Res<_>>()?,
+        }),
+        "SUBACK" => MqttPacket::Suback(SubackPacket {
+            message_id: fields.required_number("id")?,
+            properties: properties(fields.block("props")?)?,
+            granted: codes(fields.list("granted")?)?,
+        }),
+        "UNSUBSCRIBE" => MqttPacket::Unsubscribe(UnsubscribePacket {
+            message_id: fields.required_number("id")?,
+            qos: fields.qos("qos")?.unwrap_or(QoS::QoS1),
+            properties: properties(fields.block("props")?)?,
+            unsubscriptions: fields.list("topics")?.into_iter().map(|s| s.text).collect(),
+        }),
+        "UNSUBACK" => MqttPacket::Unsuback(UnsubackPacket {
+            message_id: fields.required_number("id")?,
+            properties: properties(fields.block("props")?)?,
+            granted: codes(fields.list("granted")?)?,
+        }),
+        "PINGREQ" => MqttPacket::Pingreq,
+        "PINGRESP" => MqttPacket::Pingresp,
+        "DISCONNECT" => MqttPacket::Disconnect(DisconnectPacket {
+            reason_code: fields.code("reason")?,
+            properties: properties(fields.block("props")?)?,
+        }),
+        "AUTH" => MqttPacket::Auth(AuthPacket {
+            reason_code: fields.required("reason", |f, key| f.code(key))?,
+            properties: properties(fields.block("props")?)?,
+        }),
+        _ => return Err(format!("Unknown packet type {}", name)),
+    };
+    fields.finish()?;
+    Ok(packet)
+}
+
+/// prints a packet in the text form, same as `packet.to_string()`
+pub fn print(packet: &MqttPacket) -> String {
+    packet.to_string()
+}
+
+impl FromStr for MqttPacket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Res<MqttPacket> {
+        parse(s)
+    }
+}
+
+impl fmt::Display for MqttPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = Out::default();
+        let name = match self {
+            MqttPacket::Connect(p) => {
+                out.field("version", p.protocol_version.to_byte());
+                out.field("client_id", string(&p.client_id));
+                out.flag("clean_session", p.clean_session);
+                out.field("keep_alive", p.keep_alive);
+                out.option("user_name", p.user_name.as_deref().map(string));
+                out.option("password", p.password.as_deref().map(string));
+                if let Some(will) = &p.will {
+                    let mut block = Out::default();
+                    block.field("qos", will.qos.to_byte());
+                    block.flag("retain", will.retain);
+                    block.option("topic", will.topic.as_deref().map(string));
+                    block.option("payload", will.payload.as_deref().map(string));
+                    block.properties(&will.properties);
+                    out.block("will", block);
+                }
+                out.properties(&p.properties);
+                "CONNECT"
+            }
+            MqttPacket::Connack(p) => {
+                out.flag("session_present", p.session_present);
+                out.field("code", p.code);
+                out.properties(&p.properties);
+                "CONNACK"
+            }
+            MqttPacket::Publish(p) => {
+                out.flag("dup", p.dup);
+                out.field("qos", p.qos.qos().to_byte());
+                out.option("id", p.qos.message_id());
+                out.flag("retain", p.retain);
+                out.field("topic", string(&p.topic));
+                out.properties(&p.properties);
+                out.field("payload", binary(&p.payload));
+                "PUBLISH"
+            }
+            MqttPacket::Puback(p) => {
+                out.confirmation(p.message_id, &p.reason_code, &p.properties);
+                "PUBACK"
+            }
+            MqttPacket::Pubrec(p) => {
+                out.confirmation(p.message_id, &p.reason_code, &p.properties);
+                "PUBREC"
+            }
+            MqttPacket::Pubrel(p) => {
+                out.confirmation(p.message_id, &p.reason_code, &p.properties);
+                "PUBREL"
+            }
+            MqttPacket::Pubcomp(p) => {
+                out.confirmation(p.message_id, &p.reason_code, &p.properties);
+                "PUBCOMP"
+            }
+            MqttPacket::Subscribe(p) => {
+                out.field("id", p.message_id);
+                if p.qos != QoS::QoS1 {
+                    out.field("qos", p.qos.to_byte());
+                }
+                out.properties(&p.properties);
+                for s in &p.subscriptions {
+                    let mut block = Out::default();
+                    block.field("topic", string(&s.topic));
+                    block.field("qos", s.qos.to_byte());
+                    block.flag("no_local", s.nl);
+                    block.flag("retain_as_published", s.rap);
+                    if s.rh != RetainHandling::default() {
+                        block.field("retain_handling", format!("{:?}", s.rh));
+                    }
+                    out.block("sub", block);
+                }
+                "SUBSCRIBE"
+            }
+            MqttPacket::Suback(p) => {
+                out.field("id", p.message_id);
+                out.properties(&p.properties);
+                out.list("granted", p.granted.iter().map(|c| format!("{:?}", c)));
+                "SUBACK"
+            }
+            MqttPacket::Unsubscribe(p) => {
+                out.field("id", p.message_id);
+                if p.qos != QoS::QoS1 {
+                    out.field("qos", p.qos.to_byte());
+                }
+                out.properties(&p.properties);
+                out.list("topics", p.unsubscriptions.iter().map(|t| string(t)));
+                "UNSUBSCRIBE"
+            }
+            MqttPacket::Unsuback(p) => {
+                out.field("id", p.message_id);
+                out.properties(&p.properties);
+                if !p.granted.is_empty() {
+                    out.list("granted", p.granted.iter().map(|c| format!("{:?}", c)));
+                }
+                "UNSUBACK"
+            }
+            MqttPacket::Pingreq => "PINGREQ",
+            MqttPacket::Pingresp => "PINGRESP",
+            MqttPacket::Disconnect(p) => {
+                out.option("reason", p.reason_code.as_ref().map(|c| format!("{:?}", c)));
+                out.properties(&p.properties);
+                "DISCONNECT"
+            }
+            MqttPacket::Auth(p) => {
+                out.field("reason", format!("{:?}", p.reason_code));
+                out.properties(&p.properties);
+                "AUTH"
+            }
+        };
+        f.write_str(name)?;
+        for part in out.0 {
+            write!(f, " {}", part)?;
+        }
+        Ok(())
+    }
+}
+
+/// names of the properties, the value type is the one the decoder uses
+const PROPERTY_NAMES: [(u8, &str); 26] = [
+    (0x01, "payload_format"),
+    (0x02, "expiry"),
+    (0x03, "content_type"),
+    (0x08, "response_topic"),
+    (0x09, "correlation"),
+    (0x0B, "sub_id"),
+    (0x11, "session_expiry"),
+    (0x12, "assigned_client_id"),
+    (0x13, "server_keep_alive"),
+    (0x15, "auth_method"),
+    (0x16, "auth_data"),
+    (0x17, "request_problem_info"),
+    (0x18, "will_delay"),
+    (0x19, "request_response_info"),
+    (0x1A, "response_info"),
+    (0x1C, "server_reference"),
+    (0x1F, "reason_string"),
+    (0x21, "receive_max"),
+    (0x22, "topic_alias_max"),
+    (0x23, "topic_alias"),
+    (0x24, "max_qos"),
+    (0x25, "retain_available"),
+    (0x27, "max_packet_size"),
+    (0x28, "wildcard_sub_available"),
+    (0x29, "sub_id_available"),
+    (0x2A, "shared_sub_available"),
+];
+
+fn connect(fields: &mut Fields) -> Res<ConnectPacket> {
+    let version = fields.required("version", |f, key| f.number(key))?;
+    Ok(ConnectPacket {
+        protocol_version: ProtocolVersion::from_byte(version)?,
+        client_id: fields.string("client_id")?.unwrap_or_default(),
+        clean_session: fields.flag("clean_session")?,
+        keep_alive: fields.number("keep_alive")?.unwrap_or(0),
+        user_name: fields.string("user_name")?,
+        password: fields.string("password")?,
+        will: match fields.block("will")? {
+            Some(mut will) => {
+                let will_packet = LastWill {
+                    qos: will.qos("qos")?.unwrap_or(QoS::QoS0),
+                    retain: will.flag("retain")?,
+                    topic: will.string("topic")?,
+                    payload: will.string("payload")?,
+                    properties: properties(will.block("props")?)?,
+                };
+                will.finish()?;
+                Some(will_packet)
+            }
+            None => None,
+        },
+        properties: properties(fields.block("props")?)?,
+    })
+}
+
+fn publish(fields: &mut Fields) -> Res<PublishPacket> {
+    let qos = fields.qos("qos")?.unwrap_or(QoS::QoS0);
+    Ok(PublishPacket {
+        dup: fields.flag("dup")?,
+        qos: PublishQoS::from_parts(qos, fields.number("id")?)?,
+        retain: fields.flag("retain")?,
+        topic: fields.required("topic", |f, key| f.string(key))?,
+        properties: properties(fields.block("props")?)?,
+        payload: match fields.scalar("payload")? {
+            Some(s) => s.binary()?,
+            None => vec![],
+        },
+    })
+}
+
+fn subscription(mut fields: Fields) -> Res<Subscription> {
+    let subscription = Subscription {
+        topic: fields.required("topic", |f, key| f.string(key))?,
+        qos: fields.qos("qos")?.unwrap_or(QoS::QoS0),
+        nl: fields.flag("no_local")?,
+        rap: fields.flag("retain_as_published")?,
+        rh: match fields.scalar("retain_handling")? {
+            Some(s) => match s.number::<u8>() {
+                Ok(n) => RetainHandling::from_byte(n)?,
+                Err(_) => [
+                    RetainHandling::SendAtSubscribe,
+                    RetainHandling::SendAtSubscribeIfNew,
+                    RetainHandling::DoNotSend,
+                ]
+                .into_iter()
+                .find(|rh| format!("{:?}", rh) == s.text)
+                .ok_or(format!("Invalid retain handling {}", s.text))?,
+            },
+            None => RetainHandling::default(),
+        },
+    };
+    fields.finish()?;
+    Ok(subscription)
+}
+
+fn properties<P: Properties>(fields: Option<Fields>) -> Res<Option<P>> {
+    let fields = match fields {
+        Some(fields) => fields,
+        None => return Ok(None),
+    };
+    let mut pairs = vec![];
+    let mut user_properties = UserProperties::new();
+    for item in fields.items {
+        if item.key == "user" {
+            match item.value {
+                Value::Block(items) => {
+                    for pair in items {
+                        match pair.value {
+                            Value::Scalar(value) => user_properties.push(pair.key, value.text),
+                            _ => return Err(format!("Invalid user property {}", pair.key)),
+                        }
+                    }
+                }
+                _ => return Err("user properties must be a block".to_string()),
+            }
+            continue;
+        }
+        let id = PROPERTY_NAMES
+            .iter()
+            .find(|(_, name)| *name == item.key)
+            .map(|(id, _)| *id)
+            .ok_or(format!("Unknown property {}", item.key))?;
+        let value = match item.value {
+            Value::Scalar(value) => value,
+            _ => return Err(format!("Invalid value for {}", item.key)),
+        };
+        let value = match id {
+            0x02 | 0x18 | 0x11 | 0x27 => PropType::U32(value.number()?),
+            0x01 | 0x17 | 0x19 | 0x25 | 0x28 | 0x29 | 0x2A => PropType::Bool(value.boolean()?),
+            0x23 | 0x21 | 0x22 | 0x13 => PropType::U16(value.number()?),
+            0x09 => PropType::Binary(value.binary()?),
+            0x0B => PropType::VarInt(value.number()?),
+            0x24 => PropType::U8(value.number()?),
+            _ => PropType::String(value.text),
+        };
+        pairs.push((id, value));
+    }
+    if !user_properties.is_empty() {
+        pairs.push((0x26, PropType::Map(user_properties)));
+    }
+    P::decode_properties(pairs).map(Some)
+}
+
+fn code<T: MqttCode<T> + fmt::Debug>(value: &Scalar) -> Res<T> {
+    if let Ok(byte) = value.number() {
+        return T::from_byte(byte);
+    }
+    (0..=255)
+        .filter_map(|byte| T::from_byte(byte).ok())
+        .find(|code| format!("{:?}", code) == value.text)
+        .ok_or(format!("Invalid reason code {}", value.text))
+}
+
+fn codes<T: MqttCode<T> + fmt::Debug>(values: Vec<Scalar>) -> Res<Vec<T>> {
+    values.iter().map(code).collect()
+}
+
+/// a string, quoted unless it is a plain word
+fn string(s: &str) -> String {
+    let plain = s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if plain && !s.is_empty() {
+        s.to_string()
+    } else {
+        format!("{:?}", s)
+    }
+}
+
+/// quoted if valid UTF-8, hex otherwise
+fn binary(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => format!("{:?}", s),
+        Err(_) => bytes.iter().fold("0x".to_string(), |mut out, b| {
+            out.push_str(&format!("{:02x}", b));
+            out
+        }),
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Flag,
+    Scalar(Scalar),
+    List(Vec<Scalar>),
+    Block(Vec<Item>),
+}
+
+#[derive(Debug)]
+struct Scalar {
+    text: String,
+    quoted: bool,
+}
+
+impl Scalar {
+    fn number<T: TryFrom<u64>>(&self) -> Res<T> {
+        let n = match self.text.strip_prefix("0x") {
+            _ if self.quoted => None,
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => self.text.parse().ok(),
+        };
+        n.and_then(|n| T::try_from(n).ok())
+            .ok_or(format!("Invalid number {}", self.text))
+    }
+
+    fn boolean(&self) -> Res<bool> {
+        match self.text.as_str() {
+            "true" if !self.quoted => Ok(true),
+            "false" if !self.quoted => Ok(false),
+            _ => Err(format!("Invalid boolean {}", self.text)),
+        }
+    }
+
+    fn binary(&self) -> Res<Vec<u8>> {
+        match self.text.strip_prefix("0x") {
+            Some(hex) if !self.quoted => {
+                if !hex.len().is_multiple_of(2) {
+                    return Err(format!("Odd number of hex digits in {}", self.text));
+                }
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|i| {
+                        hex.get(i..i + 2)
+                            .and_then(|b| u8::from_str_radix(b, 16).ok())
+                            .ok_or(format!("Invalid hex {}", self.text))
+                    })
+                    .collect()
+            }
+            _ => Ok(self.text.clone().into_bytes()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Item {
+    key: String,
+    value: Value,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.pos)
+    }
+
+    /// fields up to `closing` or the end of the input
+    fn items(&mut self, closing: Option<char>) -> Res<Vec<Item>> {
+        let mut items = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None if closing.is_some() => return Err(self.error("Missing closing bracket")),
+                None => return Ok(items),
+                c if c == closing => {
+                    self.bump();
+                    return Ok(items);
+                }
+                _ => {}
+            }
+            let key = self.scalar()?.text;
+            let value = match self.peek() {
+                Some('=') => {
+                    self.bump();
+                    if self.peek() == Some('[') {
+                        self.bump();
+                        Value::List(self.list()?)
+                    } else {
+                        Value::Scalar(self.scalar()?)
+                    }
+                }
+                Some('{') => {
+                    self.bump();
+                    Value::Block(self.items(Some('}'))?)
+                }
+                _ => Value::Flag,
+            };
+            items.push(Item { key, value });
+        }
+    }
+
+    fn list(&mut self) -> Res<Vec<Scalar>> {
+        let mut values = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(']') => {
+                    self.bump();
+                    return Ok(values);
+                }
+                None => return Err(self.error("Missing ]")),
+                _ => values.push(self.scalar()?),
+            }
+        }
+    }
+
+    fn scalar(&mut self) -> Res<Scalar> {
+        if self.peek() == Some('"') {
+            self.bump();
+            return self.quoted();
+        }
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| !c.is_whitespace() && !"={}[]\"".contains(c))
+        {
+            self.bump();
+        }
+        if start == self.pos {
+            return Err(self.error("Expected a value"));
+        }
+        Ok(Scalar {
+            text: self.input[start..self.pos].to_string(),
+            quoted: false,
+        })
+    }
+
+    fn quoted(&mut self) -> Res<Scalar> {
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error("Unterminated string")),
+                Some('"') => {
+                    return Ok(Scalar { text, quoted: true });
+                }
+                Some('\\') => text.push(self.escape()?),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Res<char> {
+        Ok(match self.bump() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '"' | '\'')) => c,
+            Some('u') if self.bump() == Some('{') => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != '}') {
+                    self.bump();
+                }
+                let hex = &self.input[start..self.pos];
+                self.bump();
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or(self.error("Invalid unicode escape"))?
+            }
+            _ => return Err(self.error("Invalid escape")),
+        })
+    }
+}
+
+/// fields of a packet or block, which are taken out one by one
+struct Fields {
+    context: String,
+    items: Vec<Item>,
+}
+
+impl Fields {
+    fn take(&mut self, key: &str) -> Res<Option<Value>> {
+        let mut matching = self.items.iter().filter(|item| item.key == key);
+        if matching.nth(1).is_some() {
+            return Err(format!("Duplicate {} in {}", key, self.context));
+        }
+        Ok(self
+            .items
+            .iter()
+            .position(|item| item.key == key)
+            .map(|i| self.items.remove(i).value))
+    }
+
+    fn required<T, F>(&mut self, key: &str, get: F) -> Res<T>
+    where
+        F: FnOnce(&mut Fields, &str) -> Res<Option<T>>,
+    {
+        get(self, key)?.ok_or(format!("Missing {} in {}", key, self.context))
+    }
+
+    fn flag(&mut self, key: &str) -> Res<bool> {
+        match self.take(key)? {
+            None => Ok(false),
+            Some(Value::Flag) => Ok(true),
+            Some(Value::Scalar(s)) => s.boolean(),
+            Some(_) => Err(format!("Invalid value for {}", key)),
+        }
+    }
+
+    fn scalar(&mut self, key: &str) -> Res<Option<Scalar>> {
+        match self.take(key)? {
+            None => Ok(None),
+            Some(Value::Scalar(s)) => Ok(Some(s)),
+            Some(_) => Err(format!("Invalid value for {}", key)),
+        }
+    }
+
+    fn string(&mut self, key: &str) -> Res<Option<String>> {
+        Ok(self.scalar(key)?.map(|s| s.text))
+    }
+
+    fn number<T: TryFrom<u64>>(&mut self, key: &str) -> Res<Option<T>> {
+        self.scalar(key)?.map(|s| s.number()).transpose()
+    }
+
+    fn required_number<T: TryFrom<u64>>(&mut self, key: &str) -> Res<T> {
+        self.required(key, |f, key| f.number(key))
+    }
+
+    fn qos(&mut self, key: &str) -> Res<Option<QoS>> {
+        self.number(key)?.map(QoS::from_byte).transpose()
+    }
+
+    fn code<T: MqttCode<T> + fmt::Debug>(&mut self, key: &str) -> Res<Option<T>> {
+        self.scalar(key)?.map(|s| code(&s)).transpose()
+    }
+
+    fn list(&mut self, key: &str) -> Res<Vec<Scalar>> {
+        match self.take(key)? {
+            None => Ok(vec![]),
+            Some(Value::List(values)) => Ok(values),
+            Some(_) => Err(format!("{} must be a list", key)),
+        }
+    }
+
+    fn block(&mut self, key: &str) -> Res<Option<Fields>> {
+        match self.take(key)? {
+            None => Ok(None),
+            Some(Value::Block(items)) => Ok(Some(Fields {
+                context: key.to_string(),
+                items,
+            })),
+            Some(_) => Err(format!("{} must be a block", key)),
+        }
+    }
+
+    /// all blocks with the given key, in order
+    fn blocks(&mut self, key: &str) -> Vec<Fields> {
+        let (blocks, rest) = std::mem::take(&mut self.items)
+            .into_iter()
+            .partition(|item| item.key == key && matches!(item.value, Value::Block(_)));
+        self.items = rest;
+        blocks
+            .into_iter()
+            .filter_map(|item| match item.value {
+                Value::Block(items) => Some(Fields {
+                    context: key.to_string(),
+                    items,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn finish(self) -> Res<()> {
+        match self.items.first() {
+            Some(item) => Err(format!("Unknown field {} in {}", item.key, self.context)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// the printed fields of a packet or block
+#[derive(Default)]
+struct Out(Vec<String>);
+
+impl Out {
+    fn field<T: fmt::Display>(&mut self, key: &str, value: T) {
+        self.0.push(format!("{}={}", key, value));
+    }
+
+    fn option<T: fmt::Display>(&mut self, key: &str, value: Option<T>) {
+        if let Some(value) = value {
+            self.field(key, value);
+        }
+    }
+
+    fn flag(&mut self, key: &str, value: bool) {
+        if value {
+            self.0.push(key.to_string());
+        }
+    }
+
+    fn list<I: Iterator<Item = String>>(&mut self, key: &str, values: I) {
+        self.0.push(format!(
+            "{}=[{}]",
+            key,
+            values.collect::<Vec<_>>().join(" ")
+        ));
+    }
+
+    fn block(&mut self, key: &str, block: Out) {
+        self.0.push(format!("{}{{{}}}", key, block.0.join(" ")));
+    }
+
+    fn confirmation<T: fmt::Debug>(
+        &mut self,
+        message_id: u16,
+        reason_code: &Option<T>,
+        properties: &Option<ConfirmationProperties>,
+    ) {
+        self.field("id", message_id);
+        self.option("reason", reason_code.as_ref().map(|c| format!("{:?}", c)));
+        self.properties(properties);
+    }
+
+    fn properties<P: Properties>(&mut self, properties: &Option<P>) {
+        let properties = match properties {
+            Some(properties) => properties,
+            None => return,
+        };
+        let mut block = Out::default();
+        let mut pairs = match properties.to_pairs() {
+            Ok(pairs) => pairs,
+            Err(e) => {
+                // not valid on the wire either, so not meant to be parsed again
+                block.field("error", string(&e));
+                return self.block("props", block);
+            }
+        };
+        pairs.sort_by_key(|(id, _)| *id);
+        for (id, value) in pairs {
+            let name = PROPERTY_NAMES
+                .iter()
+                .find(|(i, _)| *i == id)
+                .map_or("unknown", |(_, name)| name);
+            match value {
+                PropType::U32(v) | PropType::VarInt(v) => block.field(name, v),
+                PropType::U16(v) => block.field(name, v),
+                PropType::U8(v) => block.field(name, v),
+                PropType::Bool(v) => block.field(name, v),
+                PropType::Str(v) => block.field(name, string(v)),
+                PropType::String(v) => block.field(name, string(&v)),
+                PropType::Binary(v) => block.field(name, binary(&v)),
+                PropType::BinaryRef(v) => block.field(name, binary(v)),
+                PropType::U32Vec(v) => v.iter().for_each(|v| block.field(name, v)),
+                PropType::Pair(k, v) => block.user_properties(&[(k, v)]),
+                PropType::Map(v) => block.user_properties(v.as_slice()),
+                PropType::MapRef(v) => block.user_properties(v.as_slice()),
+            }
+        }
+        self.block("props", block);
+    }
+
+    fn user_properties(&mut self, pairs: &[(String, String)]) {
+        let mut block = Out::default();
+        for (k, v) in pairs {
+            block.0.push(format!("{}={}", string(k), string(v)));
+        }
+        self.block("user", block);
+    }
+}
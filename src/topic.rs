@@ -0,0 +1,34 @@
+//! Matching of topic names against topic filters (chapter 4.7 of the
+//! MQTT 5 spec), the filters are expected to be valid
+
+/// Whether `topic` matches `filter`. `#` matches the parent level as well,
+/// `+` exactly one (possibly empty) level. Topics starting with `$` are not
+/// matched by filters starting with a wildcard [MQTT-4.7.2-1]. For shared
+/// subscriptions pass the filter without the `$share/{group}/` prefix, see
+/// `shared`
+pub fn matches(filter: &str, topic: &str) -> bool {
+    if topic.starts_with('$') && filter.starts_with(['+', '#']) {
+        return false;
+    }
+    let mut topic_levels = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(t)) if level == t => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+/// Splits a shared subscription `$share/{group}/{filter}` into the share
+/// name and the filter, None for other filters or an invalid share name
+pub fn shared(filter: &str) -> Option<(&str, &str)> {
+    let rest = filter.strip_prefix("$share/")?;
+    let (group, filter) = rest.split_once('/')?;
+    if group.is_empty() || group.contains(['+', '#']) || filter.is_empty() {
+        return None;
+    }
+    Some((group, filter))
+}
@@ -5,7 +5,7 @@ use std::io;
 
 impl Packet for UnsubackPacket {
     /// This
-    fn encode(&self, protocol_version: u8) -> Res<Vec<u8>> {
+    fn encode(&self, protocol_version: ProtocolVersion) -> Res<Vec<u8>> {
         // Check message ID
         let mut length = 2;
 
@@ -21,7 +21,7 @@ impl Packet for UnsubackPacket {
         writer.write_header(FixedHeader::for_type(PacketType::Unsuback));
 
         // Length
-        writer.write_variable_num(length as u32)?;
+        writer.write_length(length)?;
 
         // Message ID
         writer.write_u16(self.message_id);
@@ -39,11 +39,11 @@ impl Packet for UnsubackPacket {
         reader: &mut ByteReader<R>,
         _: FixedHeader,
         length: u32,
-        protocol_version: u8,
+        protocol_version: ProtocolVersion,
     ) -> Res<Self> {
         let message_id = reader.read_u16()?;
 
-        if (protocol_version == 3 || protocol_version == 4) && length != 2 {
+        if !protocol_version.has_reason_codes() && length != 2 {
             return Err("Malformed unsuback, payload length must be 2".to_string());
         }
         if length == 0 {
@@ -56,10 +56,10 @@ impl Packet for UnsubackPacket {
         };
 
         // Properties mqtt 5
-        if protocol_version == 5 {
+        if protocol_version.has_properties() {
             packet.properties = match reader.read_properties()? {
                 None => None,
-                Some(props) => Some(ConfirmationProperties::from_properties(props)?),
+                Some(props) => Some(ConfirmationProperties::decode_properties(props)?),
             };
             // Parse granted QoSes
 
@@ -0,0 +1,370 @@
+//! Audits decoded or hand-built packets against the normative MUST statements
+//! of MQTT 5 and 3.1.1. MQTT 3.1 has no numbered statements, packets of that
+//! version are checked against the 3.1.1 rules.
+//!
+//! The decoder already rejects most malformed input (reserved bits, invalid
+//! UTF-8 and therefore surrogates U+D800..U+DFFF, QoS 3, ...) and the types make
+//! other errors impossible, so this only covers rules that a well formed packet
+//! can still break.
+use crate::packet::MqttPacket;
+use crate::structure::*;
+use std::fmt;
+
+/// A single broken rule
+#[derive(Debug, PartialEq, Clone)]
+pub struct Violation {
+    /// Conformance statement ID without brackets, e.g. `MQTT-3.1.3-5`. Some MQTT 5
+    /// Protocol Errors have no statement ID, for those this is the section
+    /// that defines them, e.g. `3.8.2.1.2`
+    pub id: &'static str,
+    /// Path of the offending field, e.g. `subscriptions[1].nl`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.id, self.path, self.message)
+    }
+}
+
+/// Checks a packet against the rules of the given version and returns every
+/// violation found, an empty list means the packet is conformant
+///
+/// # Example
+///
+/// ```
+/// use mqtt_packet_3_5::*;
+/// let packet = MqttPacket::Unsubscribe(UnsubscribePacket {
+///     qos: QoS::QoS1,
+///     message_id: 0,
+///     properties: None,
+///     unsubscriptions: vec!["a/#/b".to_string()],
+/// });
+/// let violations = validate(&packet, ProtocolVersion::V5);
+/// assert_eq!(violations[0].id, "MQTT-2.2.1-3");
+/// assert_eq!(violations[1].path, "unsubscriptions[0]");
+/// ```
+pub fn validate(packet: &MqttPacket, version: ProtocolVersion) -> Vec<Violation> {
+    let mut checker = Checker {
+        version,
+        violations: vec![],
+    };
+    checker.packet(packet);
+    checker.violations
+}
+
+struct Checker {
+    version: ProtocolVersion,
+    violations: Vec<Violation>,
+}
+
+impl Checker {
+    fn push(&mut self, id: &'static str, path: &str, message: String) {
+        self.violations.push(Violation {
+            id,
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    /// picks the statement ID of the checked version
+    fn id(&self, v5: &'static str, v311: &'static str) -> &'static str {
+        if self.version == ProtocolVersion::V5 {
+            v5
+        } else {
+            v311
+        }
+    }
+
+    fn packet(&mut self, packet: &MqttPacket) {
+        match packet {
+            MqttPacket::Connect(p) => self.connect(p),
+            MqttPacket::Connack(p) => self.connack(p),
+            MqttPacket::Publish(p) => self.publish(p),
+            MqttPacket::Subscribe(p) => self.subscribe(p),
+            MqttPacket::Unsubscribe(p) => self.unsubscribe(p),
+            MqttPacket::Puback(p) => self.properties("properties", p.properties.as_ref()),
+            MqttPacket::Pubrec(p) => self.properties("properties", p.properties.as_ref()),
+            MqttPacket::Pubrel(p) => self.properties("properties", p.properties.as_ref()),
+            MqttPacket::Pubcomp(p) => self.properties("properties", p.properties.as_ref()),
+            MqttPacket::Suback(p) => self.properties("properties", p.properties.as_ref()),
+            MqttPacket::Unsuback(p) => self.properties("properties", p.properties.as_ref()),
+            MqttPacket::Disconnect(p) => self.properties("properties", p.properties.as_ref()),
+            MqttPacket::Auth(p) => self.properties("properties", p.properties.as_ref()),
+            MqttPacket::Pingreq | MqttPacket::Pingresp => {}
+        }
+    }
+
+    fn connect(&mut self, packet: &ConnectPacket) {
+        let client_id = &packet.client_id;
+        self.string("client_id", client_id);
+        // Servers MUST allow 1 to 23 alphanumeric characters [MQTT-3.1.3-5] and
+        // MAY allow others, so only an empty client id can be a violation
+        if client_id.is_empty() {
+            if self.version == ProtocolVersion::V31 {
+                self.push(
+                    "MQTT-3.1.3-5",
+                    "client_id",
+                    "MQTT 3.1 requires a client id".to_string(),
+                );
+            } else if self.version == ProtocolVersion::V311 && !packet.clean_session {
+                self.push(
+                    "MQTT-3.1.3-7",
+                    "clean_session",
+                    "a zero length client id requires clean_session to be set".to_string(),
+                );
+            }
+        }
+
+        if let Some(user_name) = packet.user_name.as_ref() {
+            self.string("user_name", user_name);
+        } else if packet.password.is_some() && self.version != ProtocolVersion::V5 {
+            self.push(
+                "MQTT-3.1.2-22",
+                "password",
+                "the password flag must be 0 if the user name flag is 0".to_string(),
+            );
+        }
+
+        if let Some(will) = packet.will.as_ref() {
+            match will.topic.as_ref() {
+                Some(topic) => {
+                    let wildcard_id = self.id("MQTT-4.7.0-1", "MQTT-4.7.1-1");
+                    self.topic_name("will.topic", topic, wildcard_id, false);
+                }
+                None => self.push(
+                    "MQTT-3.1.2-9",
+                    "will.topic",
+                    "the will topic must be present if the will flag is set".to_string(),
+                ),
+            }
+            if let Some(properties) = will.properties.as_ref() {
+                self.properties("will.properties", Some(properties));
+            }
+        }
+        self.properties("properties", packet.properties.as_ref());
+    }
+
+    fn connack(&mut self, packet: &ConnackPacket) {
+        if packet.session_present && packet.code != 0 {
+            self.push(
+                self.id("MQTT-3.2.2-6", "MQTT-3.2.2-4"),
+                "session_present",
+                "session present must be 0 if the connection is refused".to_string(),
+            );
+        }
+        self.properties("properties", packet.properties.as_ref());
+    }
+
+    fn publish(&mut self, packet: &PublishPacket) {
+        if let Some(message_id) = packet.qos.message_id() {
+            self.packet_id("qos", message_id);
+        }
+        if packet.dup && packet.qos == PublishQoS::QoS0 {
+            self.push(
+                "MQTT-3.3.1-2",
+                "dup",
+                "the DUP flag must be 0 for QoS 0 messages".to_string(),
+            );
+        }
+        let has_alias = packet
+            .properties
+            .as_ref()
+            .is_some_and(|p| p.topic_alias.is_some());
+        self.topic_name("topic", &packet.topic, "MQTT-3.3.2-2", has_alias);
+
+        if let Some(properties) = packet.properties.as_ref() {
+            if properties.topic_alias == Some(0) {
+                self.push(
+                    "MQTT-3.3.2-8",
+                    "properties.topic_alias",
+                    "a topic alias of 0 is not permitted".to_string(),
+                );
+            }
+            for (i, id) in properties.subscription_identifiers.iter().enumerate() {
+                self.subscription_identifier(
+                    "3.3.2.3.8",
+                    &format!("properties.subscription_identifiers[{}]", i),
+                    *id,
+                );
+            }
+        }
+        self.properties("properties", packet.properties.as_ref());
+    }
+
+    fn subscribe(&mut self, packet: &SubscribePacket) {
+        self.packet_id("message_id", packet.message_id);
+        if packet.subscriptions.is_empty() {
+            self.push(
+                self.id("MQTT-3.8.3-2", "MQTT-3.8.3-3"),
+                "subscriptions",
+                "a SUBSCRIBE must contain at least one subscription".to_string(),
+            );
+        }
+        for (i, sub) in packet.subscriptions.iter().enumerate() {
+            self.topic_filter(&format!("subscriptions[{}].topic", i), &sub.topic);
+            // It is a Protocol Error to set the No Local bit to 1 on a Shared Subscription [MQTT-3.8.3-4]
+            if self.version.has_subscription_options() && sub.nl && sub.topic.starts_with("$share/")
+            {
+                self.push(
+                    "MQTT-3.8.3-4",
+                    &format!("subscriptions[{}].nl", i),
+                    "no local must not be set on a shared subscription".to_string(),
+                );
+            }
+        }
+        if let Some(id) = packet
+            .properties
+            .as_ref()
+            .and_then(|p| p.subscription_identifier)
+        {
+            self.subscription_identifier("3.8.2.1.2", "properties.subscription_identifier", id);
+        }
+        self.properties("properties", packet.properties.as_ref());
+    }
+
+    fn unsubscribe(&mut self, packet: &UnsubscribePacket) {
+        self.packet_id("message_id", packet.message_id);
+        if packet.unsubscriptions.is_empty() {
+            self.push(
+                "MQTT-3.10.3-2",
+                "unsubscriptions",
+                "an UNSUBSCRIBE must contain at least one topic filter".to_string(),
+            );
+        }
+        for (i, filter) in packet.unsubscriptions.iter().enumerate() {
+            self.topic_filter(&format!("unsubscriptions[{}]", i), filter);
+        }
+        self.properties("properties", packet.properties.as_ref());
+    }
+
+    /// SUBSCRIBE, UNSUBSCRIBE, and PUBLISH (in cases where QoS > 0) Control
+    /// Packets MUST contain a non-zero 16-bit Packet Identifier
+    fn packet_id(&mut self, path: &str, message_id: u16) {
+        if message_id == 0 {
+            self.push(
+                self.id("MQTT-2.2.1-3", "MQTT-2.3.1-1"),
+                path,
+                "packet identifier must be non-zero".to_string(),
+            );
+        }
+    }
+
+    fn subscription_identifier(&mut self, id: &'static str, path: &str, value: u32) {
+        if value == 0 || value > VARBYTEINT_MAX {
+            self.push(
+                id,
+                path,
+                format!(
+                    "subscription identifier {} is not in the range 1 to {}",
+                    value, VARBYTEINT_MAX
+                ),
+            );
+        }
+    }
+
+    /// A UTF-8 Encoded String MUST NOT include an encoding of the null character U+0000
+    fn string(&mut self, path: &str, s: &str) {
+        if s.contains('\u{0}') {
+            self.push(
+                self.id("MQTT-1.5.4-2", "MQTT-1.5.3-2"),
+                path,
+                "string must not contain the null character U+0000".to_string(),
+            );
+        }
+    }
+
+    fn topic_name(&mut self, path: &str, topic: &str, wildcard_id: &'static str, has_alias: bool) {
+        self.string(path, topic);
+        // an empty topic name is only valid in MQTT 5 together with a topic alias
+        if topic.is_empty() && !(has_alias && self.version == ProtocolVersion::V5) {
+            self.push(
+                "MQTT-4.7.3-1",
+                path,
+                "topic names must be at least one character long".to_string(),
+            );
+        }
+        if topic.contains(['+', '#']) {
+            self.push(
+                wildcard_id,
+                path,
+                "topic names must not contain wildcard characters".to_string(),
+            );
+        }
+    }
+
+    fn topic_filter(&mut self, path: &str, filter: &str) {
+        self.string(path, filter);
+        if filter.is_empty() {
+            self.push(
+                "MQTT-4.7.3-1",
+                path,
+                "topic filters must be at least one character long".to_string(),
+            );
+            return;
+        }
+        let levels: Vec<&str> = filter.split('/').collect();
+        for (i, level) in levels.iter().enumerate() {
+            if level.contains('#') && (*level != "#" || i != levels.len() - 1) {
+                self.push(
+                    self.id("MQTT-4.7.1-1", "MQTT-4.7.1-2"),
+                    path,
+                    "'#' must occupy the last level of a topic filter on its own".to_string(),
+                );
+            }
+            if level.contains('+') && *level != "+" {
+                self.push(
+                    self.id("MQTT-4.7.1-2", "MQTT-4.7.1-3"),
+                    path,
+                    "'+' must occupy an entire level of a topic filter".to_string(),
+                );
+            }
+        }
+    }
+
+    /// checks every string inside properties, including user properties
+    fn properties<P: Properties>(&mut self, path: &str, properties: Option<&P>) {
+        let pairs = match properties.map(|p| p.to_pairs()) {
+            Some(Ok(pairs)) => pairs,
+            _ => return,
+        };
+        for (id, value) in pairs {
+            // Authentication Data is binary data, which may contain U+0000
+            if id == 0x16 {
+                continue;
+            }
+            match value {
+                PropType::Str(s) => {
+                    let name = property_field(id);
+                    self.string(&format!("{}.{}", path, name), s);
+                }
+                PropType::String(s) => {
+                    let name = property_field(id);
+                    self.string(&format!("{}.{}", path, name), &s);
+                }
+                PropType::MapRef(user_properties) => {
+                    for (i, (k, v)) in user_properties.iter().enumerate() {
+                        self.string(&format!("{}.user_properties[{}].key", path, i), k);
+                        self.string(&format!("{}.user_properties[{}].value", path, i), v);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// field names of string properties as used in the property structs
+fn property_field(id: u8) -> &'static str {
+    match id {
+        0x03 => "content_type",
+        0x08 => "response_topic",
+        0x12 => "assigned_client_identifier",
+        0x15 => "authentication_method",
+        0x1A => "response_information",
+        0x1C => "server_reference",
+        0x1F => "reason_string",
+        _ => "unknown",
+    }
+}
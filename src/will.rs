@@ -0,0 +1,148 @@
+//! Will messages (chapter 3.1.2.5 and 3.1.3.2 of the MQTT 5 spec). The will of
+//! a CONNECT is published when the network connection is closed without a
+//! DISCONNECT with reason code 0x00, after the Will Delay Interval or when the
+//! session ends, whichever comes first.
+//!
+//! `WillTracker` is sans-I/O, the current time comes from the `Clock` passed
+//! in by the caller
+use crate::clock::Clock;
+use crate::structure::*;
+use std::time::{Duration, Instant};
+
+/// The PUBLISH of a will, carrying the will properties that are sent on with
+/// the message. The Will Delay Interval is not part of it. For QoS 1 and 2
+/// `message_id` is used as the message id
+pub fn publish(will: &LastWill, message_id: u16) -> Res<PublishPacket> {
+    let topic = will
+        .topic
+        .clone()
+        .ok_or_else(|| "Will message without a topic".to_string())?;
+    let message_id = match will.qos {
+        QoS::QoS0 => None,
+        _ => Some(message_id),
+    };
+    Ok(PublishPacket {
+        dup: false,
+        qos: PublishQoS::from_parts(will.qos, message_id)?,
+        retain: will.retain,
+        topic,
+        payload: will.payload.clone().unwrap_or_default().into_bytes(),
+        properties: will.properties.as_ref().map(|props| PublishProperties {
+            payload_format_indicator: props.payload_format_indicator,
+            message_expiry_interval: props.message_expiry_interval,
+            content_type: props.content_type.clone(),
+            response_topic: props.response_topic.clone(),
+            correlation_data: props.correlation_data.clone(),
+            user_properties: props.user_properties.clone(),
+            ..Default::default()
+        }),
+    })
+}
+
+/// Tracks the will of one session from its CONNECT until it is published or
+/// removed
+#[derive(Debug, PartialEq, Clone)]
+pub struct WillTracker {
+    will: Option<LastWill>,
+    /// seconds the session outlives the network connection, u32::MAX never ends
+    session_expiry_interval: u32,
+    /// set once the network connection is closed
+    due: Option<Instant>,
+}
+
+impl WillTracker {
+    /// Takes the will of a CONNECT. The session expiry is the Session Expiry
+    /// Interval in MQTT 5, for MQTT 3 the session ends with the connection
+    /// if Clean Session is set and never otherwise
+    pub fn new(connect: &ConnectPacket) -> WillTracker {
+        let session_expiry_interval = match connect.protocol_version {
+            ProtocolVersion::V5 => connect
+                .properties
+                .as_ref()
+                .map_or(0, |props| props.session_expiry_interval()),
+            _ if connect.clean_session => 0,
+            _ => u32::MAX,
+        };
+        WillTracker {
+            will: connect.will.clone(),
+            session_expiry_interval,
+            due: None,
+        }
+    }
+
+    /// the will that is still to be published, if any
+    pub fn will(&self) -> Option<&LastWill> {
+        self.will.as_ref()
+    }
+
+    /// Handles a DISCONNECT of the client. Reason code 0x00 (or a MQTT 3
+    /// DISCONNECT) removes the will [MQTT-3.1.2-10], any other reason code,
+    /// e.g. DisconnectWithWillMessage, keeps it. A Session Expiry Interval in
+    /// the DISCONNECT replaces the one of the CONNECT. The connection is closed
+    /// afterwards, so the will is scheduled like for `connection_closed`
+    pub fn disconnect(&mut self, disconnect: &DisconnectPacket, clock: &dyn Clock) {
+        if let Some(interval) = disconnect
+            .properties
+            .as_ref()
+            .and_then(|props| props.session_expiry_interval)
+        {
+            self.session_expiry_interval = interval;
+        }
+        match disconnect.reason_code {
+            None | Some(DisconnectCode::NormalDisconnection) => self.will = None,
+            Some(_) => self.connection_closed(clock),
+        }
+    }
+
+    /// The network connection was closed without a DISCONNECT (or after one
+    /// that keeps the will). The will is due after the Will Delay Interval or
+    /// when the session ends, whichever comes first [MQTT-3.1.3-9]
+    pub fn connection_closed(&mut self, clock: &dyn Clock) {
+        if self.will.is_none() || self.due.is_some() {
+            return;
+        }
+        let delay = self
+            .will
+            .as_ref()
+            .and_then(|will| will.properties.as_ref())
+            .map_or(0, |props| props.will_delay_interval());
+        let seconds = delay.min(self.session_expiry_interval);
+        self.due = Some(clock.now() + Duration::from_secs(seconds as u64));
+    }
+
+    /// A new network connection resumed the session before the will was
+    /// published, which then must not be sent [MQTT-3.1.3-9]. With Clean Start
+    /// the previous session ends instead and the will is due at once
+    pub fn reconnected(&mut self, clean_start: bool, clock: &dyn Clock) {
+        if clean_start && self.due.is_some() {
+            self.due = Some(clock.now());
+        } else {
+            self.will = None;
+            self.due = None;
+        }
+    }
+
+    /// The session ended, e.g. because it was taken over or the server shuts
+    /// down, so a will whose connection is closed is due at once
+    pub fn session_ended(&mut self, clock: &dyn Clock) {
+        if self.due.is_some() {
+            self.due = Some(clock.now());
+        }
+    }
+
+    /// when `poll` returns the will, to set a timer
+    pub fn deadline(&self) -> Option<Instant> {
+        self.due.filter(|_| self.will.is_some())
+    }
+
+    /// the will once it is due, it is returned only once
+    pub fn poll(&mut self, clock: &dyn Clock) -> Option<LastWill> {
+        match self.deadline() {
+            Some(due) if due <= clock.now() => {
+                self.due = None;
+                self.will.take()
+            }
+            _ => None,
+        }
+    }
+}
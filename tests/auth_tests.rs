@@ -9,7 +9,12 @@ mod tests {
         PacketDecoder::new(ByteReader::new(BufReader::new(c)))
     }
 
-    fn test_encode_decode(name: &str, packet: MqttPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_encode_decode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         let mut decoder = dec_from_buf(buf.clone());
         eprintln!("Failed decode: {}", name);
         assert_eq!(
@@ -44,7 +49,22 @@ mod tests {
                 31, 0, 4, 116, 101, 115, 116, // reasonString
                 38, 0, 4, 116, 101, 115, 116, 0, 4, 116, 101, 115, 116, // userProperties
             ],
-            5,
+            ProtocolVersion::V5,
+        );
+    }
+
+    #[test]
+    fn test_auth_without_property_length() {
+        let mut decoder = dec_from_buf(vec![
+            240, 1,    // Header
+            0x18, // reason code, no properties length
+        ]);
+        assert_eq!(
+            MqttPacket::Auth(AuthPacket {
+                reason_code: AuthCode::ContinueAuthentication,
+                properties: None,
+            }),
+            decoder.decode_packet(ProtocolVersion::V5).unwrap()
         );
     }
 }
@@ -1,3 +1,4 @@
+#[allow(clippy::bool_assert_comparison)]
 mod test {
     use mqtt_packet_3_5::byte_reader::ByteReader;
     use std::io::{BufReader, Cursor};
@@ -79,7 +80,7 @@ mod test {
         let src = Cursor::new(vec![0, 4, 8, 16, 32]);
         let r = BufReader::new(src);
         let mut reader = ByteReader::new(r);
-        reader.take(2);
+        assert_eq!(Ok(()), reader.take(2));
         assert_eq!(Ok(0), reader.read_u8());
         assert!(reader.has_more());
         assert_eq!(Ok(4), reader.read_u8());
@@ -99,10 +100,10 @@ mod test {
         let src = Cursor::new(vec![0, 4, 8, 16, 32, 64, 128]);
         let r = BufReader::new(src);
         let mut reader = ByteReader::new(r);
-        reader.take(5);
+        assert_eq!(Ok(()), reader.take(5));
         assert_eq!(Ok(0), reader.read_u8());
         // take less now, 4 were left
-        reader.take(3);
+        assert_eq!(Ok(()), reader.take(3));
         assert!(reader.has_more());
         assert_eq!(Ok(4), reader.read_u8());
         assert!(reader.has_more());
@@ -129,10 +130,10 @@ mod test {
         let src = Cursor::new(vec![0, 4, 8, 16, 32, 64, 128]);
         let r = BufReader::new(src);
         let mut reader = ByteReader::new(r);
-        reader.take(5);
+        assert_eq!(Ok(()), reader.take(5));
         assert_eq!(Ok(0), reader.read_u8());
         // take less now, 4 were left
-        reader.take(3);
+        assert_eq!(Ok(()), reader.take(3));
         assert!(reader.has_more());
         assert_eq!(Ok(4), reader.read_u8());
         assert!(reader.has_more());
@@ -150,4 +151,33 @@ mod test {
         // now we are done for real
         assert_eq!(false, reader.has_more());
     }
+
+    #[test]
+    fn test_take_whole_limit() {
+        let src = Cursor::new(vec![0, 4, 8]);
+        let r = BufReader::new(src);
+        let mut reader = ByteReader::new(r);
+        assert_eq!(Ok(()), reader.take(2));
+        // nested limit spanning everything that is left
+        assert_eq!(Ok(()), reader.take(2));
+        assert_eq!(Ok(0), reader.read_u8());
+        assert_eq!(Ok(4), reader.read_u8());
+        assert!(!reader.has_more());
+        // outer limit is still in place
+        reader.reset_limit();
+        assert!(!reader.has_more());
+        assert_eq!(Ok(vec![]), reader.consume());
+        reader.reset_limit();
+        assert_eq!(Ok(8), reader.read_u8());
+    }
+
+    #[test]
+    fn test_take_beyond_limit() {
+        let src = Cursor::new(vec![0, 4, 8]);
+        let r = BufReader::new(src);
+        let mut reader = ByteReader::new(r);
+        assert_eq!(Ok(()), reader.take(2));
+        assert_eq!(Ok(0), reader.read_u8());
+        assert_eq!(Err("Cannot take more than 1".to_string()), reader.take(2));
+    }
 }
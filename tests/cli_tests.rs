@@ -0,0 +1,164 @@
+mod tests {
+    use std::fs;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::process::{Command, Output, Stdio};
+
+    fn golden(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name)
+    }
+
+    fn read_golden(name: &str) -> String {
+        fs::read_to_string(golden(name)).unwrap()
+    }
+
+    fn run(args: &[&str], stdin: &[u8]) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_mqtt-packet"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(stdin).unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    fn run_ok(args: &[&str], stdin: &[u8]) -> Vec<u8> {
+        let output = run(args, stdin);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output.stdout
+    }
+
+    fn path(name: &str) -> String {
+        golden(name).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_decode_json() {
+        let out = run_ok(&["decode", &path("session_v5.hex")], b"");
+        assert_eq!(
+            read_golden("session_v5.json"),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_auto_version() {
+        // the SUBSCRIBE is only valid as MQTT 3.1.1, which the CONNECT announces
+        let input = read_golden("session_v311.hex");
+        let out = run_ok(&["decode", "--auto"], input.as_bytes());
+        assert_eq!(
+            read_golden("session_v311.json"),
+            String::from_utf8(out).unwrap()
+        );
+        assert!(!run(&["decode"], input.as_bytes()).status.success());
+        let out = run_ok(&["decode", "--version", "4"], input.as_bytes());
+        assert_eq!(
+            read_golden("session_v311.json"),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_text() {
+        let out = run_ok(
+            &[
+                "decode",
+                "--auto",
+                "--format",
+                "text",
+                &path("session_v311.hex"),
+            ],
+            b"",
+        );
+        assert_eq!(
+            read_golden("session_v311.txt"),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_raw() {
+        let out = run_ok(&["decode", "--raw", "--version", "3"], &[192, 0, 208, 0]);
+        assert_eq!(
+            "{\"type\":\"pingreq\"}\n{\"type\":\"pingresp\"}\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let out = run_ok(&["encode", "--hex", &path("session_v5.json")], b"");
+        assert_eq!(
+            read_golden("session_v5.hex"),
+            String::from_utf8(out).unwrap()
+        );
+        let out = run_ok(&["encode", "--auto", &path("session_v311.json")], b"");
+        let hex: Vec<String> = out.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(
+            read_golden("session_v311.hex")
+                .split_whitespace()
+                .collect::<Vec<_>>(),
+            hex
+        );
+    }
+
+    #[test]
+    fn test_pcap() {
+        let capture =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/captures/session_v5.pcapng");
+        let out = run_ok(&["pcap", "--port", "1884", capture.to_str().unwrap()], b"");
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!("[fd00::1]:40000 -> [fd00::2]:1884 MQTT 5", lines[0]);
+        assert_eq!("[fd00::1]:40001 -> [fd00::2]:1884 (no CONNECT)", lines[6]);
+        let out = run_ok(
+            &[
+                "pcap",
+                "--port",
+                "1884",
+                "--format",
+                "json",
+                capture.to_str().unwrap(),
+            ],
+            b"",
+        );
+        assert_eq!(2, String::from_utf8(out).unwrap().lines().count());
+    }
+
+    #[test]
+    fn test_schema() {
+        let out = run_ok(&["schema"], b"");
+        assert_eq!(
+            fs::read_to_string(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("schema/mqtt-packet.schema.json")
+            )
+            .unwrap(),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let output = run(&["decode"], b"c0 0");
+        assert!(!output.status.success());
+        assert_eq!(
+            "mqtt-packet: Odd number of hex digits\n",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let output = run(&["encode"], b"{\"Nope\": 1}");
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("mqtt-packet: Invalid packet"));
+        // packets before the malformed one are still printed
+        let output = run(&["decode"], b"c0 00 c0");
+        assert!(!output.status.success());
+        assert_eq!(b"{\"type\":\"pingreq\"}\n".to_vec(), output.stdout);
+    }
+}
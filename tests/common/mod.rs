@@ -0,0 +1,40 @@
+//! Packets from their text form, shared by the test files
+// every test file is its own crate and uses only some of them
+#![allow(dead_code)]
+use mqtt_packet_3_5::packet::*;
+use mqtt_packet_3_5::structure::*;
+
+pub fn connect(text: &str) -> ConnectPacket {
+    match text.parse() {
+        Ok(MqttPacket::Connect(p)) => p,
+        other => panic!("{:?}", other),
+    }
+}
+
+pub fn connack(text: &str) -> ConnackPacket {
+    match text.parse() {
+        Ok(MqttPacket::Connack(p)) => p,
+        other => panic!("{:?}", other),
+    }
+}
+
+pub fn publish(text: &str) -> PublishPacket {
+    match text.parse() {
+        Ok(MqttPacket::Publish(p)) => p,
+        other => panic!("{:?}", other),
+    }
+}
+
+pub fn subscribe(text: &str) -> SubscribePacket {
+    match text.parse() {
+        Ok(MqttPacket::Subscribe(p)) => p,
+        other => panic!("{:?}", other),
+    }
+}
+
+pub fn disconnect(text: &str) -> DisconnectPacket {
+    match text.parse() {
+        Ok(MqttPacket::Disconnect(p)) => p,
+        other => panic!("{:?}", other),
+    }
+}
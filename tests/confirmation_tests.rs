@@ -9,70 +9,56 @@ mod tests {
         PacketDecoder::new(ByteReader::new(BufReader::new(c)))
     }
 
-    fn test_decode(name: &str, packet: ConfirmationPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", name);
-        match packet.cmd {
-            PacketType::Puback => assert_eq!(
-                MqttPacket::Puback(packet.clone()),
-                decoder.decode_packet(protocol_version).unwrap()
-            ),
-            PacketType::Pubrel => assert_eq!(
-                MqttPacket::Pubrel(packet.clone()),
-                decoder.decode_packet(protocol_version).unwrap()
-            ),
-            PacketType::Pubrec => assert_eq!(
-                MqttPacket::Pubrec(packet.clone()),
-                decoder.decode_packet(protocol_version).unwrap()
-            ),
-            PacketType::Pubcomp => assert_eq!(
-                MqttPacket::Pubcomp(packet.clone()),
-                decoder.decode_packet(protocol_version).unwrap()
-            ),
-            _ => panic!("Should only use confirmation types"),
-        }
+        assert_eq!(packet, decoder.decode_packet(protocol_version).unwrap());
     }
 
-    fn test_encode(name: &str, packet: ConfirmationPacket, buf: Vec<u8>) {
+    fn test_encode(name: &str, packet: MqttPacket, buf: Vec<u8>) {
         println!("Failed encode {}", name);
-        assert_eq!(buf, packet.encode(5).unwrap());
+        assert_eq!(buf, packet.encode(ProtocolVersion::V5).unwrap());
     }
 
     fn test_decode_error(msg: &str, buf: Vec<u8>) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", msg);
-        assert_eq!(Err(msg.to_string()), decoder.decode_packet(5));
+        assert_eq!(
+            Err(msg.to_string()),
+            decoder.decode_packet(ProtocolVersion::V5)
+        );
     }
 
     #[test]
     fn test_puback_0() {
         test_decode(
             "Version 5 PUBACK test 1",
-            ConfirmationPacket {
-                cmd: PacketType::Puback,
-                pubcomp_reason_code: None,
-                puback_reason_code: Some(PubackPubrecCode::Success),
+            MqttPacket::Puback(PubackPacket {
+                reason_code: Some(PubackPubrecCode::Success),
                 properties: None,
                 message_id: 42,
-            },
+            }),
             vec![
                 64, 2, // Fixed Header (PUBACK, Remaining Length)
                 0,
                 42, // Variable Header (2 Bytes: Packet Identifier 42, Implied Reason code: Success, Implied no properties)
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
     #[test]
     fn test_puback_1() {
-        let packet = ConfirmationPacket {
-            cmd: PacketType::Puback,
-            pubcomp_reason_code: None,
-            puback_reason_code: Some(PubackPubrecCode::Success),
+        let packet = MqttPacket::Puback(PubackPacket {
+            reason_code: Some(PubackPubrecCode::Success),
             properties: None,
             message_id: 42,
-        };
+        });
         test_decode(
             "Version 5 PUBACK test 2",
             packet.clone(),
@@ -82,7 +68,7 @@ mod tests {
                 42,
                 // No Variable Header (2 Bytes: Packet Identifier 42, Reason code: 0 Success, Implied no properties)
             ],
-            5,
+            ProtocolVersion::V5,
         );
         // encoder should always write reason code
         test_encode(
@@ -100,20 +86,18 @@ mod tests {
     fn test_puback_2() {
         test_decode(
             "Version 5 PUBACK test 3",
-            ConfirmationPacket {
-                cmd: PacketType::Puback,
-                pubcomp_reason_code: None,
-                puback_reason_code: Some(PubackPubrecCode::Success),
+            MqttPacket::Puback(PubackPacket {
+                reason_code: Some(PubackPubrecCode::Success),
                 properties: None,
                 message_id: 42,
-            },
+            }),
             vec![
                 64, 4, // Fixed Header (PUBACK, Remaining Length)
                 0, 42,
                 0, // Variable Header (2 Bytes: Packet Identifier 42, Reason code: 0 Success)
                 0, // no properties
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -121,18 +105,16 @@ mod tests {
     fn test_puback_3() {
         test_decode(
             "puback",
-            ConfirmationPacket {
-                cmd: PacketType::Puback,
-                pubcomp_reason_code: None,
-                puback_reason_code: None,
+            MqttPacket::Puback(PubackPacket {
+                reason_code: None,
                 properties: None,
                 message_id: 2,
-            },
+            }),
             vec![
                 64, 2, // Header
                 0, 2, // Message ID
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -152,19 +134,17 @@ mod tests {
     fn test_puback_4() {
         test_decode(
             "puback with reason and no MQTT 5 properties",
-            ConfirmationPacket {
-                cmd: PacketType::Puback,
-                pubcomp_reason_code: None,
-                puback_reason_code: Some(PubackPubrecCode::NoMatchingSubscribers),
+            MqttPacket::Puback(PubackPacket {
+                reason_code: Some(PubackPubrecCode::NoMatchingSubscribers),
                 properties: None,
                 message_id: 2,
-            },
+            }),
             vec![
                 64, 3, // Header
                 0, 2,  // Message ID
                 16, // reason code
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -172,10 +152,8 @@ mod tests {
     fn test_puback_5() {
         test_decode(
             "puback MQTT 5 properties",
-            ConfirmationPacket {
-                cmd: PacketType::Puback,
-                pubcomp_reason_code: None,
-                puback_reason_code: Some(PubackPubrecCode::NoMatchingSubscribers),
+            MqttPacket::Puback(PubackPacket {
+                reason_code: Some(PubackPubrecCode::NoMatchingSubscribers),
                 message_id: 2,
                 properties: Some(ConfirmationProperties {
                     reason_string: Some("test".to_string()),
@@ -183,7 +161,7 @@ mod tests {
                         .into_iter()
                         .collect::<UserProperties>(),
                 }),
-            },
+            }),
             vec![
                 64, 24, // Header
                 0, 2,  // Message ID
@@ -192,7 +170,7 @@ mod tests {
                 31, 0, 4, 116, 101, 115, 116, // reasonString
                 38, 0, 4, 116, 101, 115, 116, 0, 4, 116, 101, 115, 116, // userProperties
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -213,18 +191,16 @@ mod tests {
     fn test_pubrec_1() {
         test_decode(
             "pubrec",
-            ConfirmationPacket {
-                cmd: PacketType::Pubrec,
-                pubcomp_reason_code: None,
-                puback_reason_code: None,
+            MqttPacket::Pubrec(PubrecPacket {
+                reason_code: None,
                 properties: None,
                 message_id: 2,
-            },
+            }),
             vec![
                 80, 2, // Header
                 0, 2, // Message ID
             ],
-            4,
+            ProtocolVersion::V311,
         );
     }
 
@@ -232,18 +208,16 @@ mod tests {
     fn test_pubrec_5() {
         test_decode(
             "pubrec",
-            ConfirmationPacket {
-                cmd: PacketType::Pubrec,
-                pubcomp_reason_code: None,
-                puback_reason_code: Some(PubackPubrecCode::Success),
+            MqttPacket::Pubrec(PubrecPacket {
+                reason_code: Some(PubackPubrecCode::Success),
                 properties: None,
                 message_id: 2,
-            },
+            }),
             vec![
                 80, 2, // Header
                 0, 2, // Message ID
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -262,7 +236,7 @@ mod tests {
     fn test_pubrec_7() {
         test_decode(
             "pubrec MQTT 5 properties",
-            ConfirmationPacket::pubrec_v5(
+            MqttPacket::Pubrec(PubrecPacket::v5(
                 2, // message_id
                 PubackPubrecCode::NoMatchingSubscribers,
                 // properties:
@@ -272,7 +246,7 @@ mod tests {
                         .into_iter()
                         .collect::<UserProperties>(),
                 }),
-            ),
+            )),
             vec![
                 80, 24, // Header
                 0, 2,  // Message ID
@@ -281,7 +255,7 @@ mod tests {
                 31, 0, 4, 116, 101, 115, 116, // reasonString
                 38, 0, 4, 116, 101, 115, 116, 0, 4, 116, 101, 115, 116, // userProperties
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -289,18 +263,16 @@ mod tests {
     fn test_pubrel_8() {
         test_decode(
             "pubrel",
-            ConfirmationPacket {
-                cmd: PacketType::Pubrel,
-                pubcomp_reason_code: None,
-                puback_reason_code: None,
+            MqttPacket::Pubrel(PubrelPacket {
+                reason_code: None,
                 properties: None,
                 message_id: 2,
-            },
+            }),
             vec![
                 98, 2, // Header
                 0, 2, // Message ID
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -333,10 +305,8 @@ mod tests {
     fn test_pubrel_11() {
         test_decode(
             "pubrel MQTT5 properties",
-            ConfirmationPacket {
-                cmd: PacketType::Pubrel,
-                pubcomp_reason_code: Some(PubcompPubrelCode::PacketIdentifierNotFound),
-                puback_reason_code: None,
+            MqttPacket::Pubrel(PubrelPacket {
+                reason_code: Some(PubcompPubrelCode::PacketIdentifierNotFound),
                 message_id: 2,
                 properties: Some(ConfirmationProperties {
                     reason_string: Some("test".to_string()),
@@ -344,7 +314,7 @@ mod tests {
                         .into_iter()
                         .collect::<UserProperties>(),
                 }),
-            },
+            }),
             vec![
                 98, 24, // Header
                 0, 2,    // Message ID
@@ -353,7 +323,7 @@ mod tests {
                 31, 0, 4, 116, 101, 115, 116, // reasonString
                 38, 0, 4, 116, 101, 115, 116, 0, 4, 116, 101, 115, 116, // userProperties
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -374,18 +344,16 @@ mod tests {
     fn test_pubcomp_13() {
         test_decode(
             "pubcomp",
-            ConfirmationPacket {
-                cmd: PacketType::Pubcomp,
-                pubcomp_reason_code: Some(PubcompPubrelCode::Success),
-                puback_reason_code: None,
+            MqttPacket::Pubcomp(PubcompPacket {
+                reason_code: Some(PubcompPubrelCode::Success),
                 message_id: 2,
                 properties: None,
-            },
+            }),
             vec![
                 112, 2, // Header
                 0, 2, // Message ID
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -404,10 +372,8 @@ mod tests {
     fn test_pubcomp_15() {
         test_decode(
             "pubcomp MQTT 5 properties",
-            ConfirmationPacket {
-                cmd: PacketType::Pubcomp,
-                pubcomp_reason_code: Some(PubcompPubrelCode::PacketIdentifierNotFound),
-                puback_reason_code: None,
+            MqttPacket::Pubcomp(PubcompPacket {
+                reason_code: Some(PubcompPubrelCode::PacketIdentifierNotFound),
                 message_id: 2,
                 properties: Some(ConfirmationProperties {
                     reason_string: Some("test".to_string()),
@@ -415,7 +381,7 @@ mod tests {
                         .into_iter()
                         .collect::<UserProperties>(),
                 }),
-            },
+            }),
             vec![
                 112, 24, // Header
                 0, 2,    // Message ID
@@ -424,7 +390,7 @@ mod tests {
                 31, 0, 4, 116, 101, 115, 116, // reasonString
                 38, 0, 4, 116, 101, 115, 116, 0, 4, 116, 101, 115, 116, // userProperties
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -440,4 +406,83 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_confirmation_properties_beyond_packet() {
+        test_decode_error(
+            "Cannot take more than 7",
+            vec![
+                112, 11, // Header
+                0, 2,    // Message ID
+                0x92, // reason code
+                20,   // properties length, only 7 bytes are left
+                31, 0, 4, 116, 101, 115, 116, // reasonString
+            ],
+        );
+    }
+
+    #[test]
+    fn test_pubrel_encode_reason_code_without_properties() {
+        let packet = MqttPacket::Pubrel(PubrelPacket::v5(
+            2,
+            PubcompPubrelCode::PacketIdentifierNotFound,
+            None,
+        ));
+        let buf = vec![
+            98, 4, // Header
+            0, 2,    // Message ID
+            0x92, // reason code
+            0,    // properties length
+        ];
+        test_encode(
+            "pubrel reason code without properties",
+            packet.clone(),
+            buf.clone(),
+        );
+        test_decode(
+            "pubrel reason code without properties",
+            packet,
+            buf,
+            ProtocolVersion::V5,
+        );
+    }
+
+    #[test]
+    fn test_v3_encode_ignores_reason_code() {
+        let packet = MqttPacket::Puback(PubackPacket::v5(
+            2,
+            PubackPubrecCode::NoMatchingSubscribers,
+            None,
+        ));
+        assert_eq!(Ok(vec![64, 2, 0, 2]), packet.encode(ProtocolVersion::V311));
+    }
+
+    #[test]
+    fn test_convert_confirmation_packet() {
+        use std::convert::TryFrom;
+        let packet = ConfirmationPacket {
+            cmd: PacketType::Pubcomp,
+            puback_reason_code: None,
+            pubcomp_reason_code: Some(PubcompPubrelCode::Success),
+            properties: None,
+            message_id: 7,
+        };
+        assert_eq!(
+            Ok(MqttPacket::Pubcomp(PubcompPacket::v5(
+                7,
+                PubcompPubrelCode::Success,
+                None
+            ))),
+            MqttPacket::try_from(packet.clone())
+        );
+        assert!(PubackPacket::try_from(packet).is_err());
+        let mixed = ConfirmationPacket {
+            cmd: PacketType::Puback,
+            puback_reason_code: None,
+            pubcomp_reason_code: Some(PubcompPubrelCode::Success),
+            properties: None,
+            message_id: 7,
+        };
+        assert!(MqttPacket::try_from(mixed).is_err());
+    }
 }
@@ -8,7 +8,7 @@ fn dec_from_buf(v: Vec<u8>) -> PacketDecoder<Cursor<Vec<u8>>> {
   PacketDecoder::new(ByteReader::new(BufReader::new(c)))
 }
 
-fn test_decode(name: &str, packet: ConnackPacket, buf: Vec<u8>, protocol_version: u8) {
+fn test_decode(name: &str, packet: ConnackPacket, buf: Vec<u8>, protocol_version: ProtocolVersion) {
   let mut decoder = dec_from_buf(buf.clone());
   println!("Failed: {}", name);
   assert_eq!(
@@ -18,7 +18,7 @@ fn test_decode(name: &str, packet: ConnackPacket, buf: Vec<u8>, protocol_version
   // assert_eq!(Ok(buf), packet.encode(protocol_version))
 }
 
-fn test_encode(name: &str, packet: ConnackPacket, buf: Vec<u8>, protocol_version: u8) {
+fn test_encode(name: &str, packet: ConnackPacket, buf: Vec<u8>, protocol_version: ProtocolVersion) {
   println!("Failed: {}", name);
   assert_eq!(buf, packet.encode(protocol_version).unwrap());
 }
@@ -26,7 +26,7 @@ fn test_encode(name: &str, packet: ConnackPacket, buf: Vec<u8>, protocol_version
 fn test_parse_error(name: &str, msg: String, buf: Vec<u8>) {
   println!("Failed: {}", name);
   let mut decoder = dec_from_buf(buf);
-  assert_eq!(Err(msg), decoder.decode_packet(3));
+  assert_eq!(Err(msg), decoder.decode_packet(ProtocolVersion::V31));
 }
 
 #[test]
@@ -42,8 +42,7 @@ fn test_connack_v4() {
       // },
       // length: 2,
       properties: None,
-      reason_code: None,
-      return_code: Some(1),
+      code: 0x84,
       session_present: false,
     },
     vec![
@@ -51,7 +50,7 @@ fn test_connack_v4() {
       0,
       1, // Variable Header (Session not present, Connection Refused - unacceptable protocol version)
     ],
-    4,
+    ProtocolVersion::V311,
   );
   test_encode(
     "Version 4 CONNACK",
@@ -64,8 +63,7 @@ fn test_connack_v4() {
       // },
       // length: 2,
       properties: None,
-      reason_code: None,
-      return_code: Some(1),
+      code: 0x84,
       session_present: false,
     },
     vec![
@@ -73,7 +71,7 @@ fn test_connack_v4() {
       0,
       1, // Variable Header (Session not present, Connection Refused - unacceptable protocol version)
     ],
-    4,
+    ProtocolVersion::V311,
   );
 }
 
@@ -90,8 +88,7 @@ fn test_connack_v5() {
       // },
       // length: 3,
       properties: None,
-      reason_code: Some(140),
-      return_code: None,
+      code: 140,
       session_present: false,
     },
     vec![
@@ -99,7 +96,7 @@ fn test_connack_v5() {
       0, 140, // Variable Header (Session not present, Bad authentication method)
       0,   // Property Length Zero
     ],
-    5,
+    ProtocolVersion::V5,
   );
   test_encode(
     "Version 5 CONNACK",
@@ -112,8 +109,7 @@ fn test_connack_v5() {
       // },
       // length: 3,
       properties: None,
-      reason_code: Some(140),
-      return_code: None,
+      code: 140,
       session_present: false,
     },
     vec![
@@ -121,7 +117,7 @@ fn test_connack_v5() {
       0, 140, // Variable Header (Session not present, Bad authentication method)
       0,   // Property Length Zero
     ],
-    5,
+    ProtocolVersion::V5,
   );
 }
 #[test]
@@ -137,8 +133,7 @@ fn test_connack_v4_v5_mode() {
       // },
       // length: 2,
       properties: None,
-      reason_code: Some(1),
-      return_code: None,
+      code: 1,
       session_present: false,
     },
     vec![
@@ -146,7 +141,7 @@ fn test_connack_v4_v5_mode() {
       0,
       1, // Variable Header (Session not present, Connection Refused - unacceptable protocol version)
     ],
-    5,
+    ProtocolVersion::V5,
   );
 }
 
@@ -163,12 +158,11 @@ fn test_connack_7() {
       // },
       // length: 2,
       session_present: false,
-      reason_code: None,
-      return_code: Some(0),
+      code: 0,
       properties: None,
     },
     vec![32, 2, 0, 0],
-    3,
+    ProtocolVersion::V31,
   );
 }
 
@@ -185,24 +179,22 @@ fn test_connack_8() {
       // },
       // length: 87,
       session_present: false,
-      reason_code: Some(0),
-      return_code: None,
+      code: 0,
       properties: Some(ConnackProperties {
-        is_default: false,
-        session_expiry_interval: 1234,
-        receive_maximum: 432,
-        maximum_qos: 2,
-        retain_available: true,
+        session_expiry_interval: Some(1234),
+        receive_maximum: Some(432),
+        maximum_qos: Some(QoS::QoS2),
+        retain_available: Some(true),
         maximum_packet_size: Some(100),
         assigned_client_identifier: Some("test".to_string()),
-        topic_alias_maximum: 456,
+        topic_alias_maximum: Some(456),
         reason_string: Some("test".to_string()),
         user_properties: [("test".to_string(), vec!["test".to_string()])]
           .into_iter()
           .collect::<UserProperties>(),
-        wildcard_subscription_available: true,
-        subscription_identifiers_available: true,
-        shared_subscription_available: false,
+        wildcard_subscription_available: Some(true),
+        subscription_identifiers_available: Some(true),
+        shared_subscription_available: Some(false),
         server_keep_alive: Some(1234),
         response_information: Some("test".to_string()),
         server_reference: Some("test".to_string()),
@@ -230,7 +222,7 @@ fn test_connack_8() {
       21, 0, 4, 116, 101, 115, 116, // authenticationMethod
       22, 0, 4, 1, 2, 3, 4, // authenticationData
     ],
-    5,
+    ProtocolVersion::V5,
   )
 }
 
@@ -247,17 +239,15 @@ fn test_connack_9() {
       // },
       // length: 100,
       session_present: false,
-      reason_code: Some(0),
-      return_code: None,
+      code: 0,
       properties: Some(ConnackProperties {
-        is_default: false,
-        session_expiry_interval: 1234,
-        receive_maximum: 432,
-        maximum_qos: 2,
-        retain_available: true,
+        session_expiry_interval: Some(1234),
+        receive_maximum: Some(432),
+        maximum_qos: Some(QoS::QoS2),
+        retain_available: Some(true),
         maximum_packet_size: Some(100),
         assigned_client_identifier: Some("test".to_string()),
-        topic_alias_maximum: 456,
+        topic_alias_maximum: Some(456),
         reason_string: Some("test".to_string()),
         user_properties: [(
           "test".to_string(),
@@ -265,9 +255,9 @@ fn test_connack_9() {
         )]
         .into_iter()
         .collect::<UserProperties>(),
-        wildcard_subscription_available: true,
-        subscription_identifiers_available: true,
-        shared_subscription_available: false,
+        wildcard_subscription_available: Some(true),
+        subscription_identifiers_available: Some(true),
+        shared_subscription_available: Some(false),
         server_keep_alive: Some(1234),
         response_information: Some("test".to_string()),
         server_reference: Some("test".to_string()),
@@ -296,7 +286,7 @@ fn test_connack_9() {
       21, 0, 4, 116, 101, 115, 116, // authenticationMethod
       22, 0, 4, 1, 2, 3, 4, // authenticationData
     ],
-    5,
+    ProtocolVersion::V5,
   );
 }
 
@@ -313,12 +303,11 @@ fn test_connack_10() {
       // },
       // length: 2,
       session_present: true,
-      reason_code: None,
-      return_code: Some(0),
+      code: 0,
       properties: None,
     },
     vec![32, 2, 1, 0],
-    3,
+    ProtocolVersion::V31,
   );
 }
 
@@ -335,12 +324,57 @@ fn test_connack_11() {
       // },
       // length: 2,
       session_present: false,
-      reason_code: None,
-      return_code: Some(5),
+      code: 0x87,
       properties: None,
     },
     vec![32, 2, 0, 5],
-    3,
+    ProtocolVersion::V31,
+  );
+}
+
+#[test]
+fn test_connack_partial_properties_round_trip() {
+  let packet = ConnackPacket {
+    session_present: false,
+    code: 0,
+    properties: Some(ConnackProperties {
+      receive_maximum: Some(10),
+      retain_available: Some(false),
+      ..ConnackProperties::default()
+    }),
+  };
+  let buf = vec![
+    32, 8, 0, 0, 5, // properties length
+    33, 0, 10, // receiveMaximum
+    37, 0, // retainAvailable
+  ];
+  test_decode("connack with some properties", packet.clone(), buf.clone(), ProtocolVersion::V5);
+  // only the properties that were on the wire are written back
+  test_encode("connack with some properties", packet.clone(), buf, ProtocolVersion::V5);
+  let props = packet.properties.unwrap();
+  assert_eq!(10, props.receive_maximum());
+  assert!(!props.retain_available());
+  // absent properties fall back to the defaults of the spec
+  assert_eq!(0, props.topic_alias_maximum());
+  assert_eq!(QoS::QoS2, props.maximum_qos());
+  assert!(props.wildcard_subscription_available());
+  assert!(props.subscription_identifiers_available());
+  assert!(props.shared_subscription_available());
+}
+
+#[test]
+fn test_connack_empty_properties() {
+  test_encode(
+    "connack with no properties set",
+    ConnackPacket {
+      session_present: false,
+      code: 0,
+      properties: Some(ConnackProperties::default()),
+    },
+    vec![
+      32, 3, 0, 0, 0, // properties length
+    ],
+    ProtocolVersion::V5,
   );
 }
 
@@ -375,3 +409,27 @@ fn test_connack_err_1() {
     ],
   )
 }
+
+#[test]
+fn test_connack_code_per_version() {
+  // 0x86 Bad User Name or Password is return code 4
+  let packet = ConnackPacket {
+    session_present: false,
+    code: 0x86,
+    properties: None,
+  };
+  test_decode("connack with return code 4", packet.clone(), vec![32, 2, 0, 4], ProtocolVersion::V311);
+  test_encode("connack with return code 4", packet.clone(), vec![32, 2, 0, 4], ProtocolVersion::V311);
+  test_encode("connack with reason code 0x86", packet, vec![32, 3, 0, 0x86, 0], ProtocolVersion::V5);
+  // 0x8C Bad authentication method only exists in MQTT 5
+  let packet = ConnackPacket {
+    session_present: false,
+    code: 0x8C,
+    properties: None,
+  };
+  assert_eq!(
+    Err("CONNACK reason code 0x8c has no MQTT 3 return code".to_string()),
+    packet.encode(ProtocolVersion::V311)
+  );
+  test_parse_error("connack with return code 6", "Invalid return code 6".to_string(), vec![32, 2, 0, 6]);
+}
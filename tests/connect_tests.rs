@@ -1,3 +1,4 @@
+#[allow(clippy::bool_assert_comparison, clippy::match_like_matches_macro)]
 mod tests {
     use mqtt_packet_3_5::byte_reader::*;
     use mqtt_packet_3_5::packet::*;
@@ -14,7 +15,7 @@ mod tests {
         println!("Failed: {}", name);
         assert_eq!(
             MqttPacket::Connect(packet.clone()),
-            decoder.decode_packet(3).unwrap()
+            decoder.decode_packet(ProtocolVersion::V31).unwrap()
         );
         assert_eq!(buf, packet.encode(packet.protocol_version).unwrap());
     }
@@ -27,8 +28,7 @@ mod tests {
     #[test]
     fn decode_bytes_connect() {
         let expected = ConnectPacket {
-            protocol_id: Protocol::MQIsdp,
-            protocol_version: 3,
+            protocol_version: ProtocolVersion::V31,
             keep_alive: 30,
             clean_session: false,
             user_name: None,
@@ -53,8 +53,7 @@ mod tests {
     #[test]
     fn test_err_without_client_id() {
         let expected = ConnectPacket {
-            protocol_id: Protocol::MQIsdp,
-            protocol_version: 3,
+            protocol_version: ProtocolVersion::V31,
             keep_alive: 30,
             clean_session: false,
             user_name: None,
@@ -71,20 +70,19 @@ mod tests {
         test_decode(
             "connect MQTT 5",
             ConnectPacket {
-                protocol_id: Protocol::Mqtt,
-                protocol_version: 5,
+                protocol_version: ProtocolVersion::V5,
                 user_name: None,
                 password: None,
                 will: Some(LastWill {
                     retain: true,
-                    qos: 2,
+                    qos: QoS::QoS2,
                     properties: Some(WillProperties {
-                        will_delay_interval: 1234,
-                        payload_format_indicator: false,
+                        will_delay_interval: Some(1234),
+                        payload_format_indicator: Some(false),
                         message_expiry_interval: Some(4321),
                         content_type: Some("test".to_string()),
                         response_topic: Some("topic".to_string()),
-                        correlation_data: vec![1, 2, 3, 4],
+                        correlation_data: Some(vec![1, 2, 3, 4]),
                         user_properties: [("test".to_string(), vec!["test".to_string()])]
                             .into_iter()
                             .collect::<UserProperties>(), //{ test: 'test' }
@@ -95,12 +93,12 @@ mod tests {
                 clean_session: true,
                 keep_alive: 30,
                 properties: Some(ConnectProperties {
-                    session_expiry_interval: 1234,
-                    receive_maximum: 432,
+                    session_expiry_interval: Some(1234),
+                    receive_maximum: Some(432),
                     maximum_packet_size: Some(100),
-                    topic_alias_maximum: 456,
-                    request_response_information: true,
-                    request_problem_information: true,
+                    topic_alias_maximum: Some(456),
+                    request_response_information: Some(true),
+                    request_problem_information: Some(true),
                     user_properties: [("test".to_string(), vec!["test".to_string()])]
                         .into_iter()
                         .collect::<UserProperties>(), // { test: 'test' },
@@ -152,20 +150,19 @@ mod tests {
         test_decode(
             "connect MQTT 5 with will properties but with empty will payload",
             ConnectPacket {
-                protocol_id: Protocol::Mqtt,
-                protocol_version: 5,
+                protocol_version: ProtocolVersion::V5,
                 user_name: None,
                 password: None,
                 will: Some(LastWill {
                     retain: true,
-                    qos: 2,
+                    qos: QoS::QoS2,
                     properties: Some(WillProperties {
-                        will_delay_interval: 1234,
-                        payload_format_indicator: false,
+                        will_delay_interval: Some(1234),
+                        payload_format_indicator: Some(false),
                         message_expiry_interval: Some(4321),
                         content_type: Some("test".to_string()),
                         response_topic: Some("topic".to_string()),
-                        correlation_data: vec![1, 2, 3, 4],
+                        correlation_data: Some(vec![1, 2, 3, 4]),
                         user_properties: user_properties.clone(), //{ test: 'test' }
                     }),
                     topic: Some("topic".to_string()),
@@ -175,12 +172,12 @@ mod tests {
                 keep_alive: 30,
                 client_id: String::from("test"),
                 properties: Some(ConnectProperties {
-                    session_expiry_interval: 1234,
-                    receive_maximum: 432,
+                    session_expiry_interval: Some(1234),
+                    receive_maximum: Some(432),
                     maximum_packet_size: Some(100),
-                    topic_alias_maximum: 456,
-                    request_response_information: true,
-                    request_problem_information: true,
+                    topic_alias_maximum: Some(456),
+                    request_response_information: Some(true),
+                    request_problem_information: Some(true),
                     user_properties,
                     authentication_method: Some("test".to_string()),
                     authentication_data: Some(String::from_utf8(vec![1, 2, 3, 4]).unwrap()),
@@ -225,13 +222,12 @@ mod tests {
         test_decode(
             "connect MQTT 5 w/o will properties",
             ConnectPacket {
-                protocol_id: Protocol::Mqtt,
-                protocol_version: 5,
+                protocol_version: ProtocolVersion::V5,
                 user_name: None,
                 password: None,
                 will: Some(LastWill {
                     retain: true,
-                    qos: 2,
+                    qos: QoS::QoS2,
                     properties: None,
                     topic: Some("topic".to_string()),
                     payload: Some(String::from_utf8(vec![4, 3, 2, 1]).unwrap()),
@@ -240,12 +236,12 @@ mod tests {
                 keep_alive: 30,
                 client_id: String::from("test"),
                 properties: Some(ConnectProperties {
-                    session_expiry_interval: 1234,
-                    receive_maximum: 432,
+                    session_expiry_interval: Some(1234),
+                    receive_maximum: Some(432),
                     maximum_packet_size: Some(100),
-                    topic_alias_maximum: 456,
-                    request_response_information: true,
-                    request_problem_information: true,
+                    topic_alias_maximum: Some(456),
+                    request_response_information: Some(true),
+                    request_problem_information: Some(true),
                     user_properties: [("test".to_string(), vec!["test".to_string()])]
                         .into_iter()
                         .collect::<UserProperties>(),
@@ -286,8 +282,7 @@ mod tests {
         test_decode(
             "no client_id with 3.1.1",
             ConnectPacket {
-                protocol_id: Protocol::Mqtt,
-                protocol_version: 4,
+                protocol_version: ProtocolVersion::V311,
                 user_name: None,
                 password: None,
                 will: None,
@@ -352,7 +347,7 @@ mod tests {
         let mut decoder = dec_from_buf(buf);
         let mut messages = vec![];
         while decoder.has_more() {
-            let msg = decoder.decode_packet(3);
+            let msg = decoder.decode_packet(ProtocolVersion::V31);
             println!("DECODING {:?}", msg);
             messages.push(msg);
         }
@@ -395,4 +390,135 @@ mod tests {
             false
         });
     }
+
+    fn test_decode_error(msg: &str, buf: Vec<u8>) {
+        let mut decoder = dec_from_buf(buf);
+        println!("Failed: {}", msg);
+        assert_eq!(
+            Err(msg.to_string()),
+            decoder.decode_packet(ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_property_error() {
+        test_decode_error(
+            "Protocol error: duplicate property Session Expiry Interval (0x11) in CONNECT properties",
+            vec![
+                16, 23, // Header
+                0, 4, // Protocol ID length
+                77, 81, 84, 84, // Protocol ID
+                5,  // Protocol version
+                2,  // Connect flags
+                0, 30, // Keepalive
+                10, // properties length
+                17, 0, 0, 0, 1, // sessionExpiryInterval
+                17, 0, 0, 0, 2, // sessionExpiryInterval (again)
+                0, 0, // Client ID length
+            ],
+        );
+    }
+
+    #[test]
+    fn test_disallowed_will_property_error() {
+        test_decode_error(
+            "Protocol error: property Session Expiry Interval (0x11) is not allowed in will properties",
+            vec![
+                16, 24, // Header
+                0, 4, // Protocol ID length
+                77, 81, 84, 84, // Protocol ID
+                5,  // Protocol version
+                6,  // Connect flags (will, clean session)
+                0, 30, // Keepalive
+                0,  // properties length
+                0, 0, // Client ID length
+                5,  // will properties length
+                17, 0, 0, 0, 1, // sessionExpiryInterval is not a will property
+                0, 1, 116, // Will topic
+                0, 0, // Will payload
+            ],
+        );
+    }
+
+    #[test]
+    fn test_will_qos_3_error() {
+        test_decode_error(
+            "Will QoS must not be 3",
+            vec![
+                16, 17, // Header
+                0, 4, // Protocol ID length
+                77, 81, 84, 84, // Protocol ID
+                4,  // Protocol version
+                30, // Connect flags (will, will qos 3, clean session)
+                0, 30, // Keepalive
+                0, 0, // Client ID length
+                0, 1, 116, // Will topic
+                0, 0, // Will payload
+            ],
+        );
+    }
+
+    #[test]
+    fn test_encode_version_mismatch() {
+        let mut packet = ConnectPacket {
+            protocol_version: ProtocolVersion::V31,
+            keep_alive: 30,
+            clean_session: true,
+            user_name: None,
+            password: None,
+            will: None,
+            client_id: "test".to_string(),
+            properties: None,
+        };
+        assert_eq!(
+            Err("CONNECT announces V31 but is encoded as V311".to_string()),
+            packet.encode(ProtocolVersion::V311)
+        );
+        packet.protocol_version = ProtocolVersion::V311;
+        assert_eq!(
+            Ok(vec![
+                16, 16, // Header
+                0, 4, // Protocol ID length
+                77, 81, 84, 84, // Protocol ID (MQTT)
+                4,  // Protocol version
+                2,  // Connect flags
+                0, 30, // Keepalive
+                0, 4, // Client ID length
+                116, 101, 115, 116, // Client ID
+            ]),
+            packet.encode(ProtocolVersion::V311)
+        );
+    }
+
+    #[test]
+    fn test_protocol_name_mismatch_error() {
+        let mut decoder = dec_from_buf(vec![
+            16, 16, // Header
+            0, 4, // Protocol ID length
+            77, 81, 84, 84, // Protocol ID (MQTT)
+            3,  // Protocol version
+            2,  // Connect flags
+            0, 30, // Keepalive
+            0, 4, // Client ID length
+            116, 101, 115, 116, // Client ID
+        ]);
+        assert_eq!(
+            Err("Protocol name MQTT does not match protocol version 3.1".to_string()),
+            decoder.decode_packet(ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_protocol_version_capabilities() {
+        assert_eq!(Protocol::MQIsdp, ProtocolVersion::V31.protocol());
+        assert_eq!(Protocol::Mqtt, ProtocolVersion::V311.protocol());
+        assert_eq!(Protocol::Mqtt, ProtocolVersion::V5.protocol());
+        assert!(!ProtocolVersion::V311.has_properties());
+        assert!(!ProtocolVersion::V311.has_reason_codes());
+        assert!(ProtocolVersion::V5.has_properties());
+        assert!(ProtocolVersion::V5.has_reason_codes());
+        assert!(!ProtocolVersion::V31.allows_empty_client_id());
+        assert_eq!(Ok(ProtocolVersion::V311), ProtocolVersion::from_byte(4));
+        assert!(ProtocolVersion::from_byte(6).is_err());
+    }
 }
@@ -0,0 +1,281 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Tracks the allocations of each thread so the tests can check
+/// how much memory decoding a packet took
+struct CountingAllocator;
+
+thread_local! {
+    static CURRENT: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+fn record(change: isize) {
+    let _ = CURRENT.try_with(|current| {
+        current.set(current.get() + change);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(current.get())));
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        record(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// runs f and returns how many bytes it allocated at most at the same time
+fn peak_allocation<T>(f: impl FnOnce() -> T) -> (T, isize) {
+    let start = CURRENT.with(|current| current.get());
+    PEAK.with(|peak| peak.set(start));
+    let res = f();
+    (res, PEAK.with(|peak| peak.get()) - start)
+}
+
+mod tests {
+    use super::peak_allocation;
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::structure::*;
+    use std::io::Cursor;
+
+    fn variable_int(mut num: usize) -> Vec<u8> {
+        let mut bytes = vec![];
+        loop {
+            let mut byte = (num % 128) as u8;
+            num /= 128;
+            if num > 0 {
+                byte |= 0x80;
+            }
+            bytes.push(byte);
+            if num == 0 {
+                return bytes;
+            }
+        }
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut bytes = vec![0, s.len() as u8];
+        bytes.extend(s.as_bytes());
+        bytes
+    }
+
+    /// MQTT 5 DISCONNECT with the given properties
+    fn disconnect(props: Vec<u8>) -> Vec<u8> {
+        let mut body = vec![0];
+        body.extend(variable_int(props.len()));
+        body.extend(props);
+        let mut buf = vec![224];
+        buf.extend(variable_int(body.len()));
+        buf.extend(body);
+        buf
+    }
+
+    fn user_properties(count: usize) -> Vec<u8> {
+        let mut props = vec![];
+        for _ in 0..count {
+            props.push(38);
+            props.extend(string("a"));
+            props.extend(string("b"));
+        }
+        props
+    }
+
+    fn decode(
+        buf: Vec<u8>,
+        limits: DecoderLimits,
+        version: ProtocolVersion,
+    ) -> Result<DecodedPacket, DecodeErrorKind> {
+        let options = DecoderOptions {
+            limits,
+            ..DecoderOptions::default()
+        };
+        PacketDecoder::from_stream(Cursor::new(buf))
+            .with_options(options)
+            .try_decode_packet(version)
+            .map_err(|e| e.kind)
+    }
+
+    fn limit_error(limit: LimitExceeded) -> Result<DecodedPacket, DecodeErrorKind> {
+        Err(DecodeErrorKind::LimitExceeded(limit))
+    }
+
+    #[test]
+    fn test_declared_length_does_not_allocate() {
+        // publish claiming 256MB, but only carrying a few bytes
+        let buf = vec![48, 255, 255, 255, 127, 0, 1, 97, 1, 2, 3];
+        let (res, peak) =
+            peak_allocation(|| decode(buf, DecoderLimits::default(), ProtocolVersion::V311));
+        assert!(matches!(res, Err(DecodeErrorKind::Malformed(_))));
+        assert!(peak < 64 * 1024, "allocated {} bytes", peak);
+    }
+
+    #[test]
+    fn test_packet_size() {
+        let limits = DecoderLimits {
+            max_packet_size: 4,
+            ..DecoderLimits::default()
+        };
+        // size includes the fixed header
+        let buf = vec![176, 2, 0, 1];
+        assert!(decode(buf.clone(), limits, ProtocolVersion::V311).is_ok());
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(vec![48, 255, 255, 255, 127, 0]))
+            .with_options(DecoderOptions {
+                limits,
+                ..DecoderOptions::default()
+            });
+        assert_eq!(
+            limit_error(LimitExceeded::PacketSize {
+                size: 268435460,
+                max: 4
+            }),
+            decoder
+                .try_decode_packet(ProtocolVersion::V5)
+                .map_err(|e| e.kind)
+        );
+        // the body was not read
+        assert_eq!(5, decoder.reader.position());
+        let buf = vec![176, 3, 0, 1, 0];
+        assert_eq!(
+            limit_error(LimitExceeded::PacketSize { size: 5, max: 4 }),
+            decode(buf, limits, ProtocolVersion::V311)
+        );
+    }
+
+    #[test]
+    fn test_topic_length() {
+        let limits = DecoderLimits {
+            max_topic_length: 3,
+            ..DecoderLimits::default()
+        };
+        let mut buf = vec![48, 6];
+        buf.extend(string("abcd"));
+        assert_eq!(
+            limit_error(LimitExceeded::TopicLength { length: 4, max: 3 }),
+            decode(buf, limits, ProtocolVersion::V311)
+        );
+        // 3 bytes are fine, the limit is inclusive
+        let mut buf = vec![48, 5];
+        buf.extend(string("abc"));
+        assert!(decode(buf, limits, ProtocolVersion::V311).is_ok());
+        // topic filters are limited as well
+        let mut buf = vec![162, 8, 0, 1];
+        buf.extend(string("abcd"));
+        assert_eq!(
+            limit_error(LimitExceeded::TopicLength { length: 4, max: 3 }),
+            decode(buf, limits, ProtocolVersion::V311)
+        );
+    }
+
+    #[test]
+    fn test_string_length() {
+        let limits = DecoderLimits {
+            max_string_length: 3,
+            ..DecoderLimits::default()
+        };
+        let mut props = vec![31];
+        props.extend(string("reason"));
+        assert_eq!(
+            limit_error(LimitExceeded::StringLength { length: 6, max: 3 }),
+            decode(disconnect(props), limits, ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_user_properties() {
+        let limits = DecoderLimits {
+            max_user_properties: 2,
+            ..DecoderLimits::default()
+        };
+        assert!(decode(disconnect(user_properties(2)), limits, ProtocolVersion::V5).is_ok());
+        assert_eq!(
+            limit_error(LimitExceeded::UserProperties { count: 3, max: 2 }),
+            decode(disconnect(user_properties(3)), limits, ProtocolVersion::V5)
+        );
+        let limits = DecoderLimits {
+            max_user_property_bytes: 5,
+            ..DecoderLimits::default()
+        };
+        assert_eq!(
+            limit_error(LimitExceeded::UserPropertyBytes { bytes: 6, max: 5 }),
+            decode(disconnect(user_properties(3)), limits, ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_many_user_properties_stay_bounded() {
+        let limits = DecoderLimits {
+            max_user_properties: 100,
+            ..DecoderLimits::default()
+        };
+        let buf = disconnect(user_properties(50_000));
+        let (res, peak) = peak_allocation(|| decode(buf, limits, ProtocolVersion::V5));
+        assert_eq!(
+            limit_error(LimitExceeded::UserProperties {
+                count: 101,
+                max: 100
+            }),
+            res
+        );
+        assert!(peak < 64 * 1024, "allocated {} bytes", peak);
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let limits = DecoderLimits {
+            max_subscriptions: 2,
+            ..DecoderLimits::default()
+        };
+        let mut buf = vec![130, 14, 0, 1];
+        for topic in ["a", "b", "c"] {
+            buf.extend(string(topic));
+            buf.push(0);
+        }
+        assert_eq!(
+            limit_error(LimitExceeded::Subscriptions { count: 3, max: 2 }),
+            decode(buf, limits, ProtocolVersion::V311)
+        );
+        let mut buf = vec![162, 11, 0, 1];
+        for topic in ["a", "b", "c"] {
+            buf.extend(string(topic));
+        }
+        assert_eq!(
+            limit_error(LimitExceeded::Subscriptions { count: 3, max: 2 }),
+            decode(buf, limits, ProtocolVersion::V311)
+        );
+    }
+
+    #[test]
+    fn test_limit_error_message() {
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(vec![130, 6, 0, 1, 0, 1, 97, 0]))
+            .with_options(DecoderOptions {
+                limits: DecoderLimits {
+                    max_subscriptions: 0,
+                    ..DecoderLimits::default()
+                },
+                ..DecoderOptions::default()
+            });
+        assert_eq!(
+            Err("More than 0 subscriptions (1)".to_string()),
+            decoder.decode_packet(ProtocolVersion::V311)
+        );
+    }
+}
@@ -0,0 +1,195 @@
+mod tests {
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::structure::*;
+    use std::io::Cursor;
+
+    fn decode(
+        buf: Vec<u8>,
+        options: DecoderOptions,
+        version: ProtocolVersion,
+    ) -> Res<DecodedPacket> {
+        PacketDecoder::from_stream(Cursor::new(buf))
+            .with_options(options)
+            .decode_packet_with_warnings(version)
+    }
+
+    fn assert_lenient_only(
+        buf: Vec<u8>,
+        version: ProtocolVersion,
+        packet: MqttPacket,
+        warnings: Vec<DecodeWarning>,
+    ) {
+        assert!(decode(buf.clone(), DecoderOptions::strict(), version).is_err());
+        assert_eq!(
+            DecodedPacket { packet, warnings },
+            decode(buf, DecoderOptions::lenient(), version).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_default_is_strict() {
+        assert_eq!(DecoderOptions::strict(), DecoderOptions::default());
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(vec![193, 0]));
+        assert_eq!(
+            Err("Flags 1 should not be set for type Pingreq".to_string()),
+            decoder.decode_packet(ProtocolVersion::V5).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_reserved_header_bits() {
+        assert_lenient_only(
+            vec![193, 0],
+            ProtocolVersion::V5,
+            MqttPacket::Pingreq,
+            vec![DecodeWarning::ReservedHeaderBits {
+                cmd: PacketType::Pingreq,
+                flags: 1,
+            }],
+        );
+        // subscribe must have 0x2 set, the packet still gets its QoS 1
+        assert_lenient_only(
+            vec![128, 6, 0, 1, 0, 1, 97, 1],
+            ProtocolVersion::V311,
+            MqttPacket::Subscribe(SubscribePacket {
+                qos: QoS::QoS1,
+                message_id: 1,
+                properties: None,
+                subscriptions: vec![Subscription {
+                    topic: "a".to_string(),
+                    qos: QoS::QoS1,
+                    nl: false,
+                    rap: false,
+                    rh: RetainHandling::SendAtSubscribe,
+                }],
+            }),
+            vec![DecodeWarning::ReservedHeaderBits {
+                cmd: PacketType::Subscribe,
+                flags: 0,
+            }],
+        );
+    }
+
+    #[test]
+    fn test_publish_qos_3_still_rejected() {
+        let buf = vec![54, 5, 0, 1, 97, 0, 1];
+        assert!(decode(buf, DecoderOptions::lenient(), ProtocolVersion::V311).is_err());
+    }
+
+    #[test]
+    fn test_empty_disconnect_and_auth() {
+        assert_lenient_only(
+            vec![224, 0],
+            ProtocolVersion::V5,
+            MqttPacket::Disconnect(DisconnectPacket {
+                reason_code: Some(DisconnectCode::NormalDisconnection),
+                properties: None,
+            }),
+            vec![DecodeWarning::EmptyPacket(PacketType::Disconnect)],
+        );
+        assert_lenient_only(
+            vec![240, 0],
+            ProtocolVersion::V5,
+            MqttPacket::Auth(AuthPacket {
+                reason_code: AuthCode::Success,
+                properties: None,
+            }),
+            vec![DecodeWarning::EmptyPacket(PacketType::Auth)],
+        );
+        // nothing to tolerate for MQTT 3
+        let decoded = decode(
+            vec![224, 0],
+            DecoderOptions::lenient(),
+            ProtocolVersion::V311,
+        )
+        .unwrap();
+        assert!(decoded.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_property() {
+        assert_lenient_only(
+            vec![
+                224, 10, // Header
+                0,  // reason code
+                8,  // properties length
+                17, 0, 0, 0, 10, // session expiry interval
+                127, 1, 2, // unknown property
+            ],
+            ProtocolVersion::V5,
+            MqttPacket::Disconnect(DisconnectPacket {
+                reason_code: Some(DisconnectCode::NormalDisconnection),
+                properties: Some(DisconnectProperties {
+                    session_expiry_interval: Some(10),
+                    ..DisconnectProperties::default()
+                }),
+            }),
+            vec![DecodeWarning::UnknownProperty {
+                id: 127,
+                bytes: vec![1, 2],
+            }],
+        );
+    }
+
+    #[test]
+    fn test_properties_after_unknown_property() {
+        // the session expiry interval after the unknown property is lost
+        assert_lenient_only(
+            vec![
+                224, 10, // Header
+                0,  // reason code
+                8,  // properties length
+                127, 1, 2, // unknown property
+                17, 0, 0, 0, 10, // session expiry interval
+            ],
+            ProtocolVersion::V5,
+            MqttPacket::Disconnect(DisconnectPacket {
+                reason_code: Some(DisconnectCode::NormalDisconnection),
+                properties: None,
+            }),
+            vec![DecodeWarning::UnknownProperty {
+                id: 127,
+                bytes: vec![1, 2, 17, 0, 0, 0, 10],
+            }],
+        );
+    }
+
+    #[test]
+    fn test_v3_subscription_option_bits() {
+        assert_lenient_only(
+            vec![130, 6, 0, 1, 0, 1, 97, 5],
+            ProtocolVersion::V311,
+            MqttPacket::Subscribe(SubscribePacket {
+                qos: QoS::QoS1,
+                message_id: 1,
+                properties: None,
+                subscriptions: vec![Subscription {
+                    topic: "a".to_string(),
+                    qos: QoS::QoS1,
+                    nl: false,
+                    rap: false,
+                    rh: RetainHandling::SendAtSubscribe,
+                }],
+            }),
+            vec![DecodeWarning::SubscriptionOptionBits {
+                index: 0,
+                options: 5,
+            }],
+        );
+    }
+
+    #[test]
+    fn test_warnings_are_per_packet() {
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(vec![193, 0, 192, 0]))
+            .with_options(DecoderOptions::lenient());
+        let first = decoder
+            .decode_packet_with_warnings(ProtocolVersion::V5)
+            .unwrap();
+        assert_eq!(1, first.warnings.len());
+        let second = decoder
+            .decode_packet_with_warnings(ProtocolVersion::V5)
+            .unwrap();
+        assert_eq!(MqttPacket::Pingreq, second.packet);
+        assert!(second.warnings.is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+mod tests {
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::structure::*;
+    use std::io::Cursor;
+
+    // SUBSCRIBE with QoS 3 for its only subscription
+    fn invalid_subscribe() -> Vec<u8> {
+        vec![130, 6, 0, 1, 0, 1, 97, 3]
+    }
+
+    fn decoder(buf: Vec<u8>, recovery: RecoveryPolicy) -> PacketDecoder<Cursor<Vec<u8>>> {
+        PacketDecoder::from_stream(Cursor::new(buf)).with_options(DecoderOptions {
+            recovery,
+            ..DecoderOptions::default()
+        })
+    }
+
+    fn poisoned() -> DecodeError {
+        DecodeError {
+            kind: DecodeErrorKind::Poisoned,
+            framed: false,
+            discarded: 0,
+        }
+    }
+
+    #[test]
+    fn test_skip_packet() {
+        let mut buf = invalid_subscribe();
+        buf.extend([192, 0]);
+        let mut decoder = decoder(buf, RecoveryPolicy::SkipPacket);
+        let err = decoder
+            .try_decode_packet(ProtocolVersion::V311)
+            .unwrap_err();
+        assert!(matches!(err.kind, DecodeErrorKind::Malformed(_)));
+        assert!(err.framed);
+        assert_eq!(8, err.discarded);
+        assert!(!decoder.is_poisoned());
+        assert_eq!(
+            MqttPacket::Pingreq,
+            decoder.decode_packet(ProtocolVersion::V311).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_skip_packet_after_nested_error() {
+        // the unknown property fails while the properties length is still set
+        let buf = vec![224, 6, 0, 4, 127, 1, 2, 3, 192, 0];
+        let mut decoder = decoder(buf, RecoveryPolicy::SkipPacket);
+        let err = decoder.try_decode_packet(ProtocolVersion::V5).unwrap_err();
+        assert_eq!(
+            DecodeError {
+                kind: DecodeErrorKind::Malformed("Invalid property code: 127".to_string()),
+                framed: true,
+                discarded: 8,
+            },
+            err
+        );
+        assert_eq!(
+            MqttPacket::Pingreq,
+            decoder.decode_packet(ProtocolVersion::V5).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_fail_fast() {
+        let buf = vec![224, 6, 0, 4, 127, 1, 2, 3, 192, 0];
+        let mut decoder = decoder(buf, RecoveryPolicy::FailFast);
+        let err = decoder.try_decode_packet(ProtocolVersion::V5).unwrap_err();
+        assert!(decoder.is_poisoned());
+        // the rest of the packet is left in the stream
+        assert!(!err.framed);
+        assert_eq!(5, err.discarded);
+        assert!(!decoder.has_more());
+        assert_eq!(
+            Err(poisoned()),
+            decoder.try_decode_packet(ProtocolVersion::V5)
+        );
+        assert_eq!(
+            Err("Decoder stopped after a previous error".to_string()),
+            decoder.decode_packet(ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_corrupted_length() {
+        // the 4th byte of the remaining length must not have the continuation bit set
+        let buf = vec![48, 255, 255, 255, 255, 1, 192, 0];
+        let mut decoder = decoder(buf, RecoveryPolicy::SkipPacket);
+        assert_eq!(
+            Err(DecodeError {
+                kind: DecodeErrorKind::Malformed(
+                    "Invalid variable int, longer than 4 bytes".to_string()
+                ),
+                framed: false,
+                discarded: 5,
+            }),
+            decoder.try_decode_packet(ProtocolVersion::V5)
+        );
+        assert!(decoder.is_poisoned());
+        assert_eq!(
+            Err(poisoned()),
+            decoder.try_decode_packet(ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_truncated_stream() {
+        // publish announcing 10 bytes, but the stream ends after 3
+        let buf = vec![48, 10, 0, 1, 97];
+        let mut decoder = decoder(buf, RecoveryPolicy::SkipPacket);
+        let err = decoder
+            .try_decode_packet(ProtocolVersion::V311)
+            .unwrap_err();
+        assert!(!err.framed);
+        assert_eq!(5, err.discarded);
+        assert!(decoder.is_poisoned());
+    }
+
+    #[test]
+    fn test_invalid_header_flags_stay_framed() {
+        let buf = vec![193, 2, 0, 0, 192, 0];
+        let mut decoder = decoder(buf, RecoveryPolicy::SkipPacket);
+        let err = decoder
+            .try_decode_packet(ProtocolVersion::V311)
+            .unwrap_err();
+        assert!(err.framed);
+        assert_eq!(4, err.discarded);
+        assert_eq!(
+            MqttPacket::Pingreq,
+            decoder.decode_packet(ProtocolVersion::V311).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        // PINGREQ with a remaining length of 2, PUBACK with 3 on MQTT 3.1.1
+        for (buf, protocol_version, trailing) in [
+            (vec![192, 2, 0, 0, 192, 0], ProtocolVersion::V5, 2),
+            (vec![64, 3, 0, 1, 0, 192, 0], ProtocolVersion::V311, 1),
+        ] {
+            let mut decoder = decoder(buf, RecoveryPolicy::SkipPacket);
+            assert_eq!(
+                Err(DecodeError {
+                    kind: DecodeErrorKind::Malformed(format!(
+                        "Unexpected {} bytes at the end of the packet",
+                        trailing
+                    )),
+                    framed: true,
+                    discarded: trailing,
+                }),
+                decoder.try_decode_packet(protocol_version)
+            );
+            assert!(!decoder.is_poisoned());
+            assert_eq!(
+                MqttPacket::Pingreq,
+                decoder.decode_packet(protocol_version).unwrap()
+            );
+            assert!(!decoder.has_more());
+        }
+    }
+
+    #[test]
+    fn test_trailing_bytes_fail_fast() {
+        let buf = vec![192, 2, 0, 0, 192, 0];
+        let mut decoder = decoder(buf, RecoveryPolicy::FailFast);
+        let err = decoder.try_decode_packet(ProtocolVersion::V5).unwrap_err();
+        assert!(err.framed);
+        assert_eq!(2, err.discarded);
+        assert!(decoder.is_poisoned());
+        assert_eq!(
+            Err(poisoned()),
+            decoder.try_decode_packet(ProtocolVersion::V5)
+        );
+    }
+}
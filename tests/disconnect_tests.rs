@@ -9,18 +9,28 @@ mod tests {
         PacketDecoder::new(ByteReader::new(BufReader::new(c)))
     }
 
-    fn test_decode(name: &str, packet: MqttPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", name);
         assert_eq!(packet, decoder.decode_packet(protocol_version).unwrap());
     }
 
-    fn test_encode(name: &str, packet: MqttPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_encode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         println!("Failed encode {}", name);
         assert_eq!(buf, packet.encode(protocol_version).unwrap());
     }
 
-    fn test_decode_error(msg: &str, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode_error(msg: &str, buf: Vec<u8>, protocol_version: ProtocolVersion) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", msg);
         assert_eq!(
@@ -34,10 +44,10 @@ mod tests {
         let buf = vec![
             192, 0, // Header
         ];
-        test_decode("pingreq", packet.clone(), buf.clone(), 3);
-        test_encode("pingreq", packet.clone(), buf.clone(), 3);
-        test_decode("pingreq", packet.clone(), buf.clone(), 5);
-        test_encode("pingreq", packet, buf, 5);
+        test_decode("pingreq", packet.clone(), buf.clone(), ProtocolVersion::V31);
+        test_encode("pingreq", packet.clone(), buf.clone(), ProtocolVersion::V31);
+        test_decode("pingreq", packet.clone(), buf.clone(), ProtocolVersion::V5);
+        test_encode("pingreq", packet, buf, ProtocolVersion::V5);
     }
 
     #[test]
@@ -47,7 +57,7 @@ mod tests {
             vec![
                 193, 0, // Header
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -57,8 +67,13 @@ mod tests {
         let buf = vec![
             208, 0, // Header
         ];
-        test_decode("pingresp", packet.clone(), buf.clone(), 3);
-        test_encode("pingresp", packet, buf, 3);
+        test_decode(
+            "pingresp",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V31,
+        );
+        test_encode("pingresp", packet, buf, ProtocolVersion::V31);
     }
 
     #[test]
@@ -68,7 +83,7 @@ mod tests {
             vec![
                 209, 0, // Header
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -81,8 +96,13 @@ mod tests {
         let buf = vec![
             224, 0, // Header
         ];
-        test_decode("disconnect", packet.clone(), buf.clone(), 3);
-        test_encode("disconnect", packet, buf, 3);
+        test_decode(
+            "disconnect",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V31,
+        );
+        test_encode("disconnect", packet, buf, ProtocolVersion::V31);
     }
 
     #[test]
@@ -92,7 +112,7 @@ mod tests {
             vec![
                 225, 0, // Header
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -118,8 +138,13 @@ mod tests {
             38, 0, 4, 116, 101, 115, 116, 0, 4, 116, 101, 115, 116, // userProperties
             28, 0, 4, 116, 101, 115, 116, // serverReference
         ];
-        test_decode("disconnect MQTT 5", packet.clone(), buf.clone(), 5);
-        test_encode("disconnect MQTT 5", packet, buf, 5);
+        test_decode(
+            "disconnect MQTT 5",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V5,
+        );
+        test_encode("disconnect MQTT 5", packet, buf, ProtocolVersion::V5);
     }
 
     #[test]
@@ -137,9 +162,14 @@ mod tests {
             "disconnect MQTT 5 with no properties",
             packet.clone(),
             buf.clone(),
-            5,
+            ProtocolVersion::V5,
+        );
+        test_encode(
+            "disconnect MQTT 5 with no properties",
+            packet,
+            buf,
+            ProtocolVersion::V5,
         );
-        test_encode("disconnect MQTT 5 with no properties", packet, buf, 5);
     }
 
     #[test]
@@ -151,7 +181,27 @@ mod tests {
                 0x05, // Reason Code (Normal Disconnection)
                 0,    // Property Length (0 => No Properties)
             ],
-            5,
+            ProtocolVersion::V5,
+        );
+    }
+
+    #[test]
+    fn test_disconnect_without_property_length() {
+        let packet = MqttPacket::Disconnect(DisconnectPacket {
+            reason_code: Some(DisconnectCode::DisconnectWithWillMessage),
+            properties: None,
+        });
+        let buf = vec![
+            224, 1, // Fixed Header (DISCONNECT, Remaining Length)
+            4, // Reason Code (Disconnect with Will Message), no Property Length
+        ];
+        test_decode(
+            "disconnect MQTT 5 without property length",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V5,
         );
+        let mut decoder = dec_from_buf(buf).with_options(DecoderOptions::lenient());
+        assert_eq!(packet, decoder.decode_packet(ProtocolVersion::V5).unwrap());
     }
 }
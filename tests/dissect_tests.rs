@@ -0,0 +1,150 @@
+mod tests {
+    use mqtt_packet_3_5::dissect::*;
+    use mqtt_packet_3_5::*;
+
+    fn names(fields: &[Field]) -> Vec<&str> {
+        fields.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_publish_fields() {
+        let dissection = dissect(
+            &[
+                50, 9, // Header
+                0, 1, 97, // Topic
+                0, 7, // Message id
+                2, // Properties length
+                1, 1,   // Payload format indicator
+                104, // Payload
+            ],
+            ProtocolVersion::V5,
+        );
+        assert_eq!(None, dissection.error);
+        assert_eq!(1, dissection.packets.len());
+        let publish = &dissection.packets[0];
+        assert_eq!("PUBLISH", publish.name);
+        assert_eq!(0..11, publish.range);
+        assert_eq!(
+            vec![
+                "Header",
+                "Remaining length",
+                "Topic",
+                "Packet identifier",
+                "Properties",
+                "Payload"
+            ],
+            names(&publish.children)
+        );
+        let header = &publish.children[0];
+        assert_eq!(0..1, header.range);
+        assert_eq!(
+            vec!["Packet type", "DUP", "QoS", "RETAIN"],
+            names(&header.children)
+        );
+        assert_eq!(Some("1".to_string()), header.children[2].value);
+        let properties = &publish.children[4];
+        assert_eq!(7..10, properties.range);
+        let property = &properties.children[1];
+        assert_eq!("Payload Format Indicator (0x01)", property.name);
+        assert_eq!(8..10, property.range);
+        assert_eq!(Some("1".to_string()), property.value);
+    }
+
+    #[test]
+    fn test_annotated_hex() {
+        let dissection = dissect(
+            &[
+                16, 22, // Header
+                0, 4, 77, 81, 84, 84, // Protocol name
+                5,  // Protocol version
+                2,  // Connect flags
+                0, 30, // Keep alive
+                5,  // Properties length
+                17, 0, 0, 0, 10, // Session expiry interval
+                0, 4, 116, 101, 115, 116, // Client ID
+            ],
+            ProtocolVersion::V5,
+        );
+        assert_eq!(
+            "16, // Header: Packet type=CONNECT (1), Reserved=0x0\n\
+             22, // Remaining length: 22\n\
+             0, 4, 77, 81, 84, 84, // Protocol name: \"MQTT\"\n\
+             5, // Protocol version: 5 (MQTT 5)\n\
+             2, // Connect flags: User name=0, Password=0, Will retain=0, Will QoS=0, Will=0, Clean session=1, Reserved=0\n\
+             0, 30, // Keep alive: 30\n\
+             5, // Properties length: 5\n\
+             17, 0, 0, 0, 10, // Session Expiry Interval (0x11): 10\n\
+             0, 4, 116, 101, 115, 116, // Client ID: \"test\"\n",
+            dissection.to_string()
+        );
+    }
+
+    #[test]
+    fn test_multiple_packets() {
+        let dissection = dissect(&[192, 0, 64, 2, 0, 1, 208, 0], ProtocolVersion::V311);
+        assert_eq!(None, dissection.error);
+        assert_eq!(
+            vec!["PINGREQ", "PUBACK", "PINGRESP"],
+            names(&dissection.packets)
+        );
+        assert_eq!(2..6, dissection.packets[1].range);
+    }
+
+    #[test]
+    fn test_truncated_packet() {
+        let dissection = dissect(&[130, 9, 0, 1, 0, 3, 97], ProtocolVersion::V311);
+        let error = dissection.error.clone().unwrap();
+        assert_eq!(4, error.offset);
+        assert_eq!("Topic filter", error.field);
+        // the fields before the failure are kept
+        let subscribe = &dissection.packets[0];
+        assert_eq!(
+            vec![
+                "Header",
+                "Remaining length",
+                "Packet identifier",
+                "Subscription"
+            ],
+            names(&subscribe.children)
+        );
+        assert!(subscribe.children[3].children.is_empty());
+        assert_eq!(
+            "130, // Header: Packet type=SUBSCRIBE (8), Reserved=0x2\n\
+             9, // Remaining length: 9\n\
+             0, 1, // Packet identifier: 1\n\
+             0, 3, 97, // not dissected\n\
+             // error at byte 4 in Topic filter: needs 3 bytes, only 1 left\n",
+            dissection.to_string()
+        );
+    }
+
+    #[test]
+    fn test_missing_packet_bytes() {
+        let dissection = dissect(&[64, 4, 0, 1], ProtocolVersion::V311);
+        let error = dissection.error.unwrap();
+        assert_eq!(4, error.offset);
+        assert_eq!("Remaining length", error.field);
+        assert_eq!(3, dissection.packets[0].children.len());
+    }
+
+    #[test]
+    fn test_unknown_property() {
+        let dissection = dissect(&[224, 8, 0, 6, 17, 0, 0, 0, 10, 127], ProtocolVersion::V5);
+        let error = dissection.error.unwrap();
+        assert_eq!(9, error.offset);
+        assert_eq!("unknown property 0x7F", error.message);
+        let properties = &dissection.packets[0].children[3];
+        assert_eq!(
+            vec!["Properties length", "Session Expiry Interval (0x11)"],
+            names(&properties.children)
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let dissection = dissect(&[48, 4, 0, 2, 0xC3, 0x28], ProtocolVersion::V311);
+        let error = dissection.error.unwrap();
+        assert_eq!(2, error.offset);
+        assert_eq!("Topic", error.field);
+    }
+}
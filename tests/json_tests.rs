@@ -0,0 +1,387 @@
+mod tests {
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::strategies;
+    use mqtt_packet_3_5::structure::*;
+    use proptest::prelude::*;
+    use serde_json::{json, Value};
+
+    fn schema() -> Value {
+        serde_json::from_str(JSON_SCHEMA).unwrap()
+    }
+
+    /// checks `value` against `schema`, supporting only the keywords
+    /// used by `JSON_SCHEMA`
+    fn validate(root: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(r) => {
+                let name = r.strip_prefix("#/$defs/").unwrap();
+                &root["$defs"][name]
+            }
+            None => schema,
+        };
+        let fail = |msg: &str| Err(format!("{}: {}", value, msg));
+        if let Some(t) = schema.get("type").and_then(Value::as_str) {
+            let ok = match t {
+                "object" => value.is_object(),
+                "array" => value.is_array(),
+                "string" => value.is_string(),
+                "boolean" => value.is_boolean(),
+                "integer" => value.is_u64() || value.is_i64(),
+                other => panic!("Unsupported type {}", other),
+            };
+            if !ok {
+                return fail(&format!("not of type {}", t));
+            }
+        }
+        if let Some(c) = schema.get("const") {
+            if c != value {
+                return fail(&format!("not {}", c));
+            }
+        }
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            if !values.contains(value) {
+                return fail("not in enum");
+            }
+        }
+        if let Some(min) = schema.get("minimum").and_then(Value::as_i64) {
+            if value.as_i64().is_some_and(|v| v < min) {
+                return fail("too small");
+            }
+        }
+        if let Some(max) = schema.get("maximum").and_then(Value::as_u64) {
+            if value.as_u64().is_some_and(|v| v > max) {
+                return fail("too large");
+            }
+        }
+        if let Some(object) = value.as_object() {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for key in required {
+                    if !object.contains_key(key.as_str().unwrap()) {
+                        return fail(&format!("{} is missing", key));
+                    }
+                }
+            }
+            for (key, v) in object {
+                match properties.and_then(|p| p.get(key)) {
+                    Some(s) => validate(root, s, v)?,
+                    None if schema.get("additionalProperties") == Some(&json!(false)) => {
+                        return fail(&format!("unexpected {}", key));
+                    }
+                    None => {}
+                }
+            }
+        }
+        if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+            for v in array {
+                validate(root, items, v)?;
+            }
+        }
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for s in all {
+                validate(root, s, value)?;
+            }
+        }
+        if let Some(one) = schema.get("oneOf").and_then(Value::as_array) {
+            let matching = one
+                .iter()
+                .filter(|s| validate(root, s, value).is_ok())
+                .count();
+            if matching != 1 {
+                return fail(&format!("matches {} of oneOf", matching));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if validate(root, not, value).is_ok() {
+                return fail("matches not");
+            }
+        }
+        Ok(())
+    }
+
+    fn check(packet: MqttPacket, expected: Value) {
+        let value = serde_json::to_value(&packet).unwrap();
+        assert_eq!(expected, value);
+        let root = schema();
+        assert_eq!(Ok(()), validate(&root, &root, &value));
+        assert_eq!(packet, serde_json::from_value::<MqttPacket>(value).unwrap());
+    }
+
+    #[test]
+    fn test_connect() {
+        let mut user_properties = UserProperties::new();
+        user_properties.push("a", "1");
+        user_properties.push("a", "2");
+        check(
+            MqttPacket::Connect(ConnectPacket {
+                client_id: "client".to_string(),
+                protocol_version: ProtocolVersion::V5,
+                clean_session: true,
+                keep_alive: 60,
+                user_name: Some("user".to_string()),
+                password: None,
+                will: Some(LastWill {
+                    topic: Some("will".to_string()),
+                    payload: Some("bye".to_string()),
+                    qos: QoS::QoS1,
+                    retain: false,
+                    properties: Some(WillProperties {
+                        correlation_data: Some(vec![0xff, 0]),
+                        ..Default::default()
+                    }),
+                }),
+                properties: Some(ConnectProperties {
+                    session_expiry_interval: Some(10),
+                    user_properties,
+                    ..Default::default()
+                }),
+            }),
+            json!({
+                "type": "connect",
+                "client_id": "client",
+                "protocol_version": 5,
+                "clean_session": true,
+                "keep_alive": 60,
+                "user_name": "user",
+                "will": {
+                    "topic": "will",
+                    "payload": "bye",
+                    "qos": 1,
+                    "properties": {"correlation_data": {"base64": "/wA="}}
+                },
+                "properties": {
+                    "session_expiry_interval": 10,
+                    "user_properties": [
+                        {"key": "a", "value": "1"},
+                        {"key": "a", "value": "2"}
+                    ]
+                }
+            }),
+        );
+    }
+
+    #[test]
+    fn test_connack() {
+        check(
+            MqttPacket::Connack(ConnackPacket {
+                code: 0,
+                session_present: true,
+                properties: None,
+            }),
+            json!({"type": "connack", "code": 0, "session_present": true}),
+        );
+    }
+
+    #[test]
+    fn test_publish() {
+        check(
+            MqttPacket::Publish(PublishPacket {
+                dup: true,
+                qos: PublishQoS::QoS2(7),
+                retain: true,
+                topic: "t".to_string(),
+                payload: b"hello".to_vec(),
+                properties: Some(PublishProperties {
+                    subscription_identifiers: vec![1, 2],
+                    correlation_data: Some(b"id".to_vec()),
+                    ..Default::default()
+                }),
+            }),
+            json!({
+                "type": "publish",
+                "dup": true,
+                "qos": 2,
+                "message_id": 7,
+                "retain": true,
+                "topic": "t",
+                "payload": "hello",
+                "properties": {"correlation_data": "id", "subscription_identifiers": [1, 2]}
+            }),
+        );
+        check(
+            MqttPacket::Publish(PublishPacket {
+                dup: false,
+                qos: PublishQoS::QoS0,
+                retain: false,
+                topic: "t".to_string(),
+                payload: vec![0x80, 0x81],
+                properties: None,
+            }),
+            json!({"type": "publish", "qos": 0, "topic": "t", "payload": {"base64": "gIE="}}),
+        );
+    }
+
+    #[test]
+    fn test_confirmations() {
+        check(
+            MqttPacket::Puback(PubackPacket {
+                message_id: 1,
+                reason_code: Some(PubackPubrecCode::NoMatchingSubscribers),
+                properties: None,
+            }),
+            json!({"type": "puback", "message_id": 1, "reason_code": "NoMatchingSubscribers"}),
+        );
+        check(
+            MqttPacket::Pubrec(PubrecPacket {
+                message_id: 2,
+                reason_code: None,
+                properties: Some(ConfirmationProperties {
+                    reason_string: Some("ok".to_string()),
+                    ..Default::default()
+                }),
+            }),
+            json!({"type": "pubrec", "message_id": 2, "properties": {"reason_string": "ok"}}),
+        );
+        check(
+            MqttPacket::Pubrel(PubrelPacket {
+                message_id: 3,
+                reason_code: Some(PubcompPubrelCode::Success),
+                properties: None,
+            }),
+            json!({"type": "pubrel", "message_id": 3, "reason_code": "Success"}),
+        );
+        check(
+            MqttPacket::Pubcomp(PubcompPacket {
+                message_id: 4,
+                reason_code: None,
+                properties: None,
+            }),
+            json!({"type": "pubcomp", "message_id": 4}),
+        );
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        check(
+            MqttPacket::Subscribe(SubscribePacket {
+                qos: QoS::QoS1,
+                message_id: 5,
+                properties: Some(SubscribeProperties {
+                    subscription_identifier: Some(3),
+                    ..Default::default()
+                }),
+                subscriptions: vec![Subscription {
+                    topic: "a/#".to_string(),
+                    qos: QoS::QoS2,
+                    nl: true,
+                    rap: false,
+                    rh: RetainHandling::DoNotSend,
+                }],
+            }),
+            json!({
+                "type": "subscribe",
+                "qos": 1,
+                "message_id": 5,
+                "properties": {"subscription_identifier": 3},
+                "subscriptions": [
+                    {"topic": "a/#", "qos": 2, "no_local": true, "retain_handling": "DoNotSend"}
+                ]
+            }),
+        );
+        check(
+            MqttPacket::Suback(SubackPacket {
+                message_id: 5,
+                properties: None,
+                granted: vec![
+                    SubscriptionReasonCode::GrantedQoS2,
+                    SubscriptionReasonCode::NotAuthorized,
+                ],
+            }),
+            json!({"type": "suback", "message_id": 5, "granted": ["GrantedQoS2", "NotAuthorized"]}),
+        );
+        check(
+            MqttPacket::Unsubscribe(UnsubscribePacket {
+                qos: QoS::QoS1,
+                message_id: 6,
+                properties: None,
+                unsubscriptions: vec!["a/#".to_string()],
+            }),
+            json!({"type": "unsubscribe", "qos": 1, "message_id": 6, "unsubscriptions": ["a/#"]}),
+        );
+        check(
+            MqttPacket::Unsuback(UnsubackPacket {
+                message_id: 6,
+                granted: vec![],
+                properties: None,
+            }),
+            json!({"type": "unsuback", "message_id": 6}),
+        );
+    }
+
+    #[test]
+    fn test_other_packets() {
+        check(MqttPacket::Pingreq, json!({"type": "pingreq"}));
+        check(MqttPacket::Pingresp, json!({"type": "pingresp"}));
+        check(
+            MqttPacket::Disconnect(DisconnectPacket {
+                reason_code: Some(DisconnectCode::NormalDisconnection),
+                properties: Some(DisconnectProperties {
+                    session_expiry_interval: Some(0),
+                    ..Default::default()
+                }),
+            }),
+            json!({
+                "type": "disconnect",
+                "reason_code": "NormalDisconnection",
+                "properties": {"session_expiry_interval": 0}
+            }),
+        );
+        check(
+            MqttPacket::Auth(AuthPacket {
+                reason_code: AuthCode::ContinueAuthentication,
+                properties: Some(AuthProperties {
+                    authentication_method: "SCRAM".to_string(),
+                    authentication_data: None,
+                    reason_string: None,
+                    user_properties: UserProperties::new(),
+                }),
+            }),
+            json!({
+                "type": "auth",
+                "reason_code": "ContinueAuthentication",
+                "properties": {"authentication_method": "SCRAM"}
+            }),
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let invalid = [
+            // a message id is required for QoS 1
+            json!({"type": "publish", "qos": 1, "topic": "t", "payload": ""}),
+            json!({"type": "publish", "qos": 0, "message_id": 1, "topic": "t", "payload": ""}),
+            json!({"type": "publish", "qos": 3, "topic": "t", "payload": ""}),
+            json!({"type": "publish", "qos": 0, "topic": "t", "payload": {"base64": "!"}}),
+            json!({"type": "connect", "client_id": "", "protocol_version": 6, "keep_alive": 0}),
+            json!({"type": "puback"}),
+            json!({"type": "connack"}),
+            json!({"type": "nope"}),
+        ];
+        let root = schema();
+        for value in invalid {
+            assert!(serde_json::from_value::<MqttPacket>(value.clone()).is_err());
+            // the base64 alphabet is not checked by the schema
+            if value["payload"] != json!({"base64": "!"}) {
+                assert!(validate(&root, &root, &value).is_err(), "{}", value);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_json_round_trip(packet in strategies::packet(ProtocolVersion::V5)) {
+            let value = serde_json::to_value(&packet).unwrap();
+            let root = schema();
+            prop_assert_eq!(Ok(()), validate(&root, &root, &value));
+            prop_assert_eq!(packet, serde_json::from_value::<MqttPacket>(value).unwrap());
+        }
+
+        #[test]
+        fn test_json_round_trip_v311(packet in strategies::packet(ProtocolVersion::V311)) {
+            let value = serde_json::to_value(&packet).unwrap();
+            let root = schema();
+            prop_assert_eq!(Ok(()), validate(&root, &root, &value));
+            prop_assert_eq!(packet, serde_json::from_value::<MqttPacket>(value).unwrap());
+        }
+    }
+}
@@ -0,0 +1,112 @@
+mod common;
+
+mod tests {
+    use crate::common::*;
+    use mqtt_packet_3_5::clock::*;
+    use mqtt_packet_3_5::keep_alive::*;
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::structure::*;
+    use std::time::Duration;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_client_pingreq() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut keep_alive =
+            ClientKeepAlive::new(&connect("CONNECT version=4 keep_alive=10"), &clock);
+        assert_eq!(Some(start + secs(10)), keep_alive.deadline());
+        clock.advance(secs(6));
+        assert_eq!(None, keep_alive.poll(&clock));
+        // any packet that is sent delays the PINGREQ
+        keep_alive.packet_sent(&clock);
+        clock.advance(secs(9));
+        assert_eq!(None, keep_alive.poll(&clock));
+        clock.advance(secs(1));
+        assert_eq!(Some(KeepAliveAction::SendPingreq), keep_alive.poll(&clock));
+        // only one PINGREQ at a time
+        assert_eq!(None, keep_alive.poll(&clock));
+        clock.advance(secs(2));
+        keep_alive.packet_received(&MqttPacket::Pingresp);
+        assert_eq!(Some(start + secs(26)), keep_alive.deadline());
+        clock.advance(secs(8));
+        assert_eq!(Some(KeepAliveAction::SendPingreq), keep_alive.poll(&clock));
+    }
+
+    #[test]
+    fn test_client_timeout() {
+        let clock = ManualClock::new();
+        let mut keep_alive =
+            ClientKeepAlive::new(&connect("CONNECT version=5 keep_alive=10"), &clock)
+                .with_ping_timeout(secs(3));
+        clock.advance(secs(10));
+        assert_eq!(Some(KeepAliveAction::SendPingreq), keep_alive.poll(&clock));
+        // other packets do not answer the PINGREQ
+        keep_alive.packet_received(&MqttPacket::Pingreq);
+        clock.advance(secs(2));
+        assert_eq!(None, keep_alive.poll(&clock));
+        clock.advance(secs(1));
+        assert_eq!(Some(KeepAliveAction::Close(None)), keep_alive.poll(&clock));
+    }
+
+    #[test]
+    fn test_client_server_keep_alive() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut keep_alive =
+            ClientKeepAlive::new(&connect("CONNECT version=5 keep_alive=60"), &clock);
+        keep_alive.connack(&connack("CONNACK code=0"));
+        assert_eq!(Some(secs(60)), keep_alive.interval());
+        keep_alive.connack(&connack("CONNACK code=0 props{server_keep_alive=5}"));
+        assert_eq!(Some(start + secs(5)), keep_alive.deadline());
+        keep_alive.connack(&connack("CONNACK code=0 props{server_keep_alive=0}"));
+        assert_eq!(None, keep_alive.deadline());
+        clock.advance(secs(1000));
+        assert_eq!(None, keep_alive.poll(&clock));
+    }
+
+    #[test]
+    fn test_server() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut keep_alive =
+            ServerKeepAlive::new(&connect("CONNECT version=4 keep_alive=10"), None, &clock);
+        assert_eq!(Some(start + secs(15)), keep_alive.deadline());
+        clock.advance(secs(14));
+        keep_alive.packet_received(&clock);
+        clock.advance(secs(14));
+        assert_eq!(None, keep_alive.poll(&clock));
+        clock.advance(secs(1));
+        // MQTT 3 has no DISCONNECT from the server
+        assert_eq!(Some(KeepAliveAction::Close(None)), keep_alive.poll(&clock));
+    }
+
+    #[test]
+    fn test_server_override() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut keep_alive =
+            ServerKeepAlive::new(&connect("CONNECT version=5 keep_alive=0"), Some(3), &clock);
+        assert_eq!(3, keep_alive.keep_alive());
+        assert_eq!(
+            Some(start + Duration::from_millis(4500)),
+            keep_alive.deadline()
+        );
+        clock.advance(Duration::from_millis(4500));
+        assert_eq!(
+            Some(KeepAliveAction::Close(Some(DisconnectPacket {
+                reason_code: Some(DisconnectCode::KeepAliveTimeout),
+                properties: None,
+            }))),
+            keep_alive.poll(&clock)
+        );
+        // there is no Server Keep Alive in MQTT 3
+        let keep_alive =
+            ServerKeepAlive::new(&connect("CONNECT version=4 keep_alive=0"), Some(3), &clock);
+        assert_eq!(0, keep_alive.keep_alive());
+        assert_eq!(None, keep_alive.deadline());
+    }
+}
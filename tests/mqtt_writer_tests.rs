@@ -0,0 +1,94 @@
+mod test {
+    use mqtt_packet_3_5::mqtt_writer::MqttWriter;
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::structure::*;
+    use std::io::Cursor;
+
+    const MAX_FIELD: usize = u16::MAX as usize;
+
+    fn publish(topic: String, properties: Option<PublishProperties>) -> MqttPacket {
+        MqttPacket::Publish(PublishPacket {
+            dup: false,
+            qos: PublishQoS::QoS0,
+            retain: false,
+            topic,
+            payload: vec![],
+            properties,
+        })
+    }
+
+    #[test]
+    fn test_write_utf8_str_boundary() {
+        let mut writer = MqttWriter::new(0);
+        assert_eq!(Ok(()), writer.write_utf8_str(&"a".repeat(MAX_FIELD)));
+        let buf = writer.into_vec();
+        assert_eq!(MAX_FIELD + 2, buf.len());
+        assert_eq!([255, 255], buf[..2]);
+        let mut writer = MqttWriter::new(0);
+        assert_eq!(
+            Err("String of 65536 bytes exceeds the maximum of 65535".to_string()),
+            writer.write_utf8_str(&"a".repeat(MAX_FIELD + 1))
+        );
+        // nothing was written for the rejected string
+        assert!(writer.into_vec().is_empty());
+    }
+
+    #[test]
+    fn test_write_binary_boundary() {
+        let mut writer = MqttWriter::new(0);
+        assert_eq!(Ok(()), writer.write_binary(vec![0; MAX_FIELD]));
+        assert_eq!(MAX_FIELD + 2, writer.into_vec().len());
+        let mut writer = MqttWriter::new(0);
+        assert_eq!(
+            Err("Binary data of 65536 bytes exceeds the maximum of 65535".to_string()),
+            writer.write_binary_ref(&[0; MAX_FIELD + 1])
+        );
+    }
+
+    #[test]
+    fn test_write_length_boundary() {
+        let mut writer = MqttWriter::new(0);
+        assert_eq!(Ok(()), writer.write_length(VARBYTEINT_MAX as usize));
+        assert_eq!(vec![255, 255, 255, 127], writer.into_vec());
+        let mut writer = MqttWriter::new(0);
+        assert_eq!(
+            Err("Packet length 268435456 exceeds the maximum of 268435455".to_string()),
+            writer.write_length(VARBYTEINT_MAX as usize + 1)
+        );
+    }
+
+    #[test]
+    fn test_encode_long_topic() {
+        let packet = publish("a".repeat(MAX_FIELD), None);
+        let buf = packet.clone().encode(ProtocolVersion::V311).unwrap();
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(buf));
+        assert_eq!(Ok(packet), decoder.decode_packet(ProtocolVersion::V311));
+        assert_eq!(
+            Err("String of 65536 bytes exceeds the maximum of 65535".to_string()),
+            publish("a".repeat(MAX_FIELD + 1), None).encode(ProtocolVersion::V311)
+        );
+    }
+
+    #[test]
+    fn test_encode_long_properties() {
+        let properties = PublishProperties {
+            user_properties: UserProperties::from(vec![(
+                "key".to_string(),
+                "v".repeat(MAX_FIELD + 1),
+            )]),
+            ..PublishProperties::default()
+        };
+        assert_eq!(
+            Err("String of 65536 bytes exceeds the maximum of 65535".to_string()),
+            publish("a".to_string(), Some(properties)).encode(ProtocolVersion::V5)
+        );
+        let properties = PublishProperties {
+            correlation_data: Some(vec![1; MAX_FIELD + 1]),
+            ..PublishProperties::default()
+        };
+        assert_eq!(
+            Err("Binary data of 65536 bytes exceeds the maximum of 65535".to_string()),
+            publish("a".to_string(), Some(properties)).encode(ProtocolVersion::V5)
+        );
+    }
+}
@@ -0,0 +1,277 @@
+mod common;
+
+mod tests {
+    use crate::common::*;
+    use mqtt_packet_3_5::negotiate::*;
+    use mqtt_packet_3_5::structure::*;
+
+    fn assign() -> Option<String> {
+        Some("auto-1".to_string())
+    }
+
+    /// for client ids that are not empty
+    fn never() -> Option<String> {
+        panic!("client id assigned")
+    }
+
+    #[test]
+    fn test_v3_accepted() {
+        let config = ServerConfig::default();
+        let (ack, session) = config
+            .negotiate(
+                &connect("CONNECT version=4 client_id=c keep_alive=30"),
+                never,
+            )
+            .unwrap();
+        assert_eq!(connack("CONNACK code=0"), ack);
+        assert_eq!("c", session.client_id);
+        assert!(!session.clean_start);
+        assert_eq!(u32::MAX, session.session_expiry_interval);
+        assert_eq!(30, session.keep_alive);
+
+        let (_, session) = config
+            .negotiate(
+                &connect("CONNECT version=3 client_id=c clean_session keep_alive=30"),
+                never,
+            )
+            .unwrap();
+        assert_eq!(0, session.session_expiry_interval);
+    }
+
+    #[test]
+    fn test_v3_client_id() {
+        let config = ServerConfig::default();
+        let rejected = Err(connack("CONNACK code=0x85"));
+        assert_eq!(
+            rejected,
+            config.negotiate(
+                &connect("CONNECT version=3 client_id=\"\" clean_session"),
+                assign
+            )
+        );
+        let long = "x".repeat(24);
+        assert_eq!(
+            rejected,
+            config.negotiate(
+                &connect(&format!("CONNECT version=3 client_id={}", long)),
+                never
+            )
+        );
+        assert!(config
+            .negotiate(
+                &connect(&format!("CONNECT version=4 client_id={}", long)),
+                never
+            )
+            .is_ok());
+        // since 3.1.1 an empty client id requires Clean Session
+        assert_eq!(
+            rejected,
+            config.negotiate(&connect("CONNECT version=4 client_id=\"\""), assign)
+        );
+        let (ack, session) = config
+            .negotiate(
+                &connect("CONNECT version=4 client_id=\"\" clean_session"),
+                assign,
+            )
+            .unwrap();
+        assert_eq!(connack("CONNACK code=0"), ack);
+        assert!(session.assigned_client_id);
+        assert_eq!("auto-1", session.client_id);
+        // the caller may refuse to assign one
+        assert_eq!(
+            rejected,
+            config.negotiate(
+                &connect("CONNECT version=4 client_id=\"\" clean_session"),
+                || None
+            )
+        );
+    }
+
+    #[test]
+    fn test_v5_accepted() {
+        let config = ServerConfig {
+            maximum_qos: QoS::QoS1,
+            retain_available: false,
+            wildcard_subscription_available: false,
+            topic_alias_maximum: 10,
+            server_keep_alive: Some(60),
+            maximum_session_expiry_interval: Some(3600),
+            ..ServerConfig::default()
+        };
+        let (ack, session) = config
+            .negotiate(
+                &connect(
+                    "CONNECT version=5 client_id=\"\" keep_alive=0 \
+                     props{session_expiry=86400 receive_max=5 topic_alias_max=2}",
+                ),
+                assign,
+            )
+            .unwrap();
+        assert_eq!(
+            connack(
+                "CONNACK code=0 props{session_expiry=3600 assigned_client_id=auto-1 \
+                 server_keep_alive=60 topic_alias_max=10 max_qos=1 retain_available=false \
+                 wildcard_sub_available=false}"
+            ),
+            ack
+        );
+        assert!(session.assigned_client_id);
+        assert_eq!("auto-1", session.client_id);
+        assert_eq!(3600, session.session_expiry_interval);
+        assert_eq!(60, session.keep_alive);
+        assert_eq!(5, session.receive_maximum);
+        assert_eq!(2, session.topic_alias_maximum);
+
+        // nothing to announce with the defaults
+        let (ack, session) = ServerConfig::default()
+            .negotiate(
+                &connect("CONNECT version=5 client_id=c clean_session keep_alive=10"),
+                never,
+            )
+            .unwrap();
+        assert_eq!(connack("CONNACK code=0"), ack);
+        assert!(session.clean_start);
+        assert_eq!(0, session.session_expiry_interval);
+        assert_eq!(10, session.keep_alive);
+    }
+
+    #[test]
+    fn test_v5_refused() {
+        let config = ServerConfig {
+            maximum_qos: QoS::QoS1,
+            retain_available: false,
+            authentication_methods: vec!["SCRAM".to_string()],
+            ..ServerConfig::default()
+        };
+        let check = |code: &str, text: &str| {
+            assert_eq!(
+                Err(connack(&format!("CONNACK code={}", code))),
+                config.negotiate(&connect(text), || None),
+                "{}",
+                text
+            )
+        };
+        check("0x85", "CONNECT version=5 client_id=\"\"");
+        check(
+            "0x8c",
+            "CONNECT version=5 client_id=c props{auth_method=OTHER}",
+        );
+        check("0x9b", "CONNECT version=5 client_id=c will{qos=2 topic=w}");
+        check("0x9a", "CONNECT version=5 client_id=c will{retain topic=w}");
+        assert!(config
+            .negotiate(
+                &connect(
+                    "CONNECT version=5 client_id=c will{qos=1 topic=w} props{auth_method=SCRAM}"
+                ),
+                never
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_protocol_version() {
+        let config = ServerConfig {
+            protocol_versions: vec![ProtocolVersion::V311],
+            ..ServerConfig::default()
+        };
+        assert_eq!(
+            Err(connack("CONNACK code=0x84")),
+            config.negotiate(&connect("CONNECT version=3 client_id=c"), never)
+        );
+        assert_eq!(
+            Err(connack("CONNACK code=0x84")),
+            config.negotiate(&connect("CONNECT version=5 client_id=c"), never)
+        );
+    }
+
+    #[test]
+    fn test_limits_publish() {
+        let limits = NegotiatedLimits::new(
+            &connack("CONNACK code=0 props{max_qos=1 retain_available=false topic_alias_max=2}"),
+            ProtocolVersion::V5,
+        );
+        assert_eq!(
+            Ok(()),
+            limits.check_publish(&publish("PUBLISH qos=1 id=1 topic=a payload=x"))
+        );
+        assert_eq!(
+            Err(DisconnectCode::QoSNotSupported),
+            limits.check_publish(&publish("PUBLISH qos=2 id=1 topic=a payload=x"))
+        );
+        assert_eq!(
+            Err(DisconnectCode::RetainNotSupported),
+            limits.check_publish(&publish("PUBLISH retain topic=a payload=x"))
+        );
+        assert_eq!(
+            Err(DisconnectCode::TopicAliasInvalid),
+            limits.check_publish(&publish("PUBLISH topic=a props{topic_alias=3} payload=x"))
+        );
+        assert_eq!(
+            Ok(publish(
+                "PUBLISH qos=1 id=1 topic=a props{topic_alias=2} payload=x"
+            )),
+            limits.downgrade_publish(publish(
+                "PUBLISH qos=2 id=1 retain topic=a props{topic_alias=2} payload=x"
+            ))
+        );
+        assert_eq!(
+            Err(DisconnectCode::TopicAliasInvalid),
+            limits.downgrade_publish(publish("PUBLISH topic=a props{topic_alias=0} payload=x"))
+        );
+
+        let limits = NegotiatedLimits::new(
+            &connack("CONNACK code=0 props{max_qos=0 max_packet_size=20}"),
+            ProtocolVersion::V5,
+        );
+        assert_eq!(
+            Ok(publish("PUBLISH topic=a payload=x")),
+            limits.downgrade_publish(publish("PUBLISH qos=1 id=1 topic=a payload=x"))
+        );
+        // 2 bytes fixed header, 3 topic, 1 properties
+        let payload = "x".repeat(14);
+        assert_eq!(
+            Ok(()),
+            limits.check_publish(&publish(&format!("PUBLISH topic=a payload={}", payload)))
+        );
+        assert_eq!(
+            Err(DisconnectCode::PacketTooLarge),
+            limits.check_publish(&publish(&format!("PUBLISH topic=a payload={}x", payload)))
+        );
+    }
+
+    #[test]
+    fn test_limits_subscribe() {
+        let limits = NegotiatedLimits::new(
+            &connack(
+                "CONNACK code=0 props{max_qos=0 wildcard_sub_available=false \
+                 sub_id_available=false shared_sub_available=false}",
+            ),
+            ProtocolVersion::V5,
+        );
+        let check = |text: &str| limits.check_subscribe(&subscribe(text));
+        assert_eq!(Ok(()), check("SUBSCRIBE id=1 sub{topic=a/b qos=2}"));
+        assert_eq!(
+            Err(DisconnectCode::WildcardSubscriptionsNotSupported),
+            check("SUBSCRIBE id=1 sub{topic=a qos=0} sub{topic=a/+ qos=0}")
+        );
+        assert_eq!(
+            Err(DisconnectCode::SharedSubscriptionsNotSupported),
+            check("SUBSCRIBE id=1 sub{topic=$share/g/a qos=0}")
+        );
+        assert_eq!(
+            Err(DisconnectCode::SubscriptionIdentifiersNotSupported),
+            check("SUBSCRIBE id=1 props{sub_id=1} sub{topic=a qos=0}")
+        );
+
+        // MQTT 3 has no limits
+        let limits = NegotiatedLimits::new(&connack("CONNACK code=0"), ProtocolVersion::V311);
+        assert_eq!(
+            Ok(()),
+            limits.check_subscribe(&subscribe("SUBSCRIBE id=1 sub{topic=$share/g/# qos=2}"))
+        );
+        assert_eq!(
+            Ok(()),
+            limits.check_publish(&publish("PUBLISH qos=2 id=1 retain topic=a payload=x"))
+        );
+    }
+}
@@ -0,0 +1,140 @@
+mod tests {
+    use mqtt_packet_3_5::pcap::*;
+    use mqtt_packet_3_5::*;
+    use std::time::Duration;
+
+    fn capture(name: &str) -> Vec<u8> {
+        std::fs::read(format!(
+            "{}/tests/captures/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap()
+    }
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_secs(1700000000) + Duration::from_millis(ms)
+    }
+
+    fn packets(conversation: &Conversation) -> Vec<(Direction, MqttPacket)> {
+        conversation
+            .entries
+            .iter()
+            .map(|e| (e.direction, e.packet.clone().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_frames() {
+        let frames = read_frames(&capture("session_v311.pcap")).unwrap();
+        assert_eq!(14, frames.len());
+        assert_eq!(1, frames[0].link_type);
+        assert_eq!(millis(1), frames[0].timestamp);
+        let frames = read_frames(&capture("session_v5.pcapng")).unwrap();
+        assert_eq!(8, frames.len());
+        assert_eq!(101, frames[0].link_type);
+        // nanosecond resolution
+        assert_eq!(millis(1) + Duration::from_nanos(7), frames[0].timestamp);
+        assert_eq!(
+            Err("Not a pcap or pcapng capture".to_string()),
+            read_frames(&[1, 2, 3, 4, 5])
+        );
+    }
+
+    #[test]
+    fn test_reassembly() {
+        let conversations =
+            extract(&capture("session_v311.pcap"), 1883, ProtocolVersion::V5).unwrap();
+        assert_eq!(1, conversations.len());
+        let conversation = &conversations[0];
+        assert_eq!("192.168.1.10:50000", conversation.client.to_string());
+        assert_eq!("192.168.1.2:1883", conversation.server.to_string());
+        assert_eq!(Some(ProtocolVersion::V311), conversation.version);
+        let types: Vec<(Direction, PacketType)> = packets(conversation)
+            .iter()
+            .map(|(direction, packet)| {
+                let first = packet.clone().encode(ProtocolVersion::V311).unwrap()[0];
+                (*direction, PacketType::from_bits(first >> 4))
+            })
+            .collect();
+        use Direction::*;
+        assert_eq!(
+            vec![
+                (ClientToServer, PacketType::Connect),
+                (ServerToClient, PacketType::Connack),
+                (ClientToServer, PacketType::Subscribe),
+                (ClientToServer, PacketType::Publish),
+                (ServerToClient, PacketType::Suback),
+                (ClientToServer, PacketType::Pingreq),
+                (ServerToClient, PacketType::Pingresp),
+            ],
+            types
+        );
+        let timestamps: Vec<Duration> = conversation.entries.iter().map(|e| e.timestamp).collect();
+        // the CONNECT is complete with its second segment and the PUBLISH, which
+        // was captured before the SUBSCRIBE, is only available after it
+        assert_eq!(
+            vec![
+                millis(11),
+                millis(20),
+                millis(31),
+                millis(31),
+                millis(40),
+                millis(50),
+                millis(60)
+            ],
+            timestamps
+        );
+    }
+
+    #[test]
+    fn test_version_detection() {
+        let conversations =
+            extract(&capture("session_v5.pcapng"), 1884, ProtocolVersion::V311).unwrap();
+        assert_eq!(2, conversations.len());
+        let first = &conversations[0];
+        assert_eq!(Some(ProtocolVersion::V5), first.version);
+        // the CONNACK can only be decoded as MQTT 5
+        assert_eq!(
+            MqttPacket::Connack(ConnackPacket {
+                code: 0,
+                session_present: false,
+                properties: None,
+            }),
+            first.entries[1].packet.clone().unwrap()
+        );
+        // the capture ends in the middle of a PUBLISH of the server
+        let last = first.entries.last().unwrap();
+        assert_eq!(Direction::ServerToClient, last.direction);
+        assert!(last.packet.is_err());
+        // the second connection was open before the capture started
+        assert_eq!(None, conversations[1].version);
+        assert_eq!(
+            vec![
+                (Direction::ClientToServer, MqttPacket::Pingreq),
+                (Direction::ServerToClient, MqttPacket::Pingresp)
+            ],
+            packets(&conversations[1])
+        );
+    }
+
+    #[test]
+    fn test_port() {
+        let conversations =
+            extract(&capture("session_v5.pcapng"), 1883, ProtocolVersion::V5).unwrap();
+        assert!(conversations.is_empty());
+    }
+
+    #[test]
+    fn test_log() {
+        let conversations =
+            extract(&capture("session_v311.pcap"), 1883, ProtocolVersion::V5).unwrap();
+        let log = conversations[0].to_string();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(
+            "192.168.1.10:50000 -> 192.168.1.2:1883 MQTT 3.1.1",
+            lines[0]
+        );
+        assert_eq!("1700000000.060000 server Pingresp", lines[7]);
+    }
+}
@@ -9,18 +9,28 @@ mod tests {
         PacketDecoder::new(ByteReader::new(BufReader::new(c)))
     }
 
-    fn test_decode(name: &str, packet: MqttPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", name);
         assert_eq!(packet, decoder.decode_packet(protocol_version).unwrap());
     }
 
-    fn test_encode(name: &str, packet: MqttPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_encode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         println!("Failed encode {}", name);
         assert_eq!(buf, packet.encode(protocol_version).unwrap());
     }
 
-    fn test_decode_error(msg: &str, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode_error(msg: &str, buf: Vec<u8>, protocol_version: ProtocolVersion) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", msg);
         assert_eq!(
@@ -29,7 +39,12 @@ mod tests {
         );
     }
 
-    fn test_error_encode(name: &str, packet: MqttPacket, msg: &str, protocol_version: u8) {
+    fn test_error_encode(
+        name: &str,
+        packet: MqttPacket,
+        msg: &str,
+        protocol_version: ProtocolVersion,
+    ) {
         println!("Failed encode error {}", name);
         assert_eq!(Err(msg.to_string()), packet.encode(protocol_version));
     }
@@ -37,13 +52,12 @@ mod tests {
     #[test]
     fn test_publish_0() {
         let packet = MqttPacket::Publish(PublishPacket {
-            qos: 0,
+            qos: PublishQoS::QoS0,
             dup: false,
             retain: false,
             properties: None,
             topic: "test".to_string(),
             payload: vec![116, 101, 115, 116],
-            message_id: None,
         });
         let buf = vec![
             48, 10, // Header
@@ -51,22 +65,27 @@ mod tests {
             116, 101, 115, 116, // Topic (test)
             116, 101, 115, 116, // Payload (test)
         ];
-        test_decode("minimal publish", packet.clone(), buf.clone(), 3);
-        test_encode("minimal publish", packet, buf, 3);
+        test_decode(
+            "minimal publish",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V31,
+        );
+        test_encode("minimal publish", packet, buf, ProtocolVersion::V31);
     }
 
     #[test]
     fn test_publish_1() {
         let packet = MqttPacket::Publish(PublishPacket {
-            qos: 2,
+            qos: PublishQoS::QoS2(10),
             dup: true,
             retain: true,
             properties: Some(PublishProperties {
-                payload_format_indicator: true,
+                payload_format_indicator: Some(true),
                 message_expiry_interval: Some(4321),
                 topic_alias: Some(100),
                 response_topic: Some("topic".to_string()),
-                correlation_data: vec![1, 2, 3, 4],
+                correlation_data: Some(vec![1, 2, 3, 4]),
                 user_properties: [(
                     "test".to_string(),
                     vec!["test".to_string(), "test".to_string(), "test".to_string()],
@@ -78,7 +97,6 @@ mod tests {
             }),
             topic: "test".to_string(),
             payload: vec![116, 101, 115, 116],
-            message_id: Some(10),
         });
         let buf = vec![
             61, 86, // Header
@@ -98,22 +116,32 @@ mod tests {
             3, 0, 4, 116, 101, 115, 116, // content type
             116, 101, 115, 116, // Payload (test)
         ];
-        test_decode("publish MQTT 5 properties", packet.clone(), buf.clone(), 5);
-        test_encode("publish MQTT 5 properties", packet, buf, 5);
+        test_decode(
+            "publish MQTT 5 properties",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V5,
+        );
+        test_encode(
+            "publish MQTT 5 properties",
+            packet,
+            buf,
+            ProtocolVersion::V5,
+        );
     }
 
     #[test]
     fn test_publish_2() {
         let packet = MqttPacket::Publish(PublishPacket {
-            qos: 2,
+            qos: PublishQoS::QoS2(10),
             dup: true,
             retain: true,
             properties: Some(PublishProperties {
-                payload_format_indicator: true,
+                payload_format_indicator: Some(true),
                 message_expiry_interval: Some(4321),
                 topic_alias: Some(100),
                 response_topic: Some("topic".to_string()),
-                correlation_data: vec![1, 2, 3, 4],
+                correlation_data: Some(vec![1, 2, 3, 4]),
                 user_properties: [("test".to_string(), vec!["test".to_string()])]
                     .into_iter()
                     .collect::<UserProperties>(),
@@ -122,7 +150,6 @@ mod tests {
             }),
             topic: "test".to_string(),
             payload: vec![116, 101, 115, 116],
-            message_id: Some(10),
         });
         let buf = vec![
             61, 64, // Header
@@ -146,27 +173,27 @@ mod tests {
             "publish MQTT 5 with multiple same properties",
             packet.clone(),
             buf.clone(),
-            5,
+            ProtocolVersion::V5,
         );
         test_encode(
             "publish MQTT 5 with multiple same properties",
             packet,
             buf,
-            5,
+            ProtocolVersion::V5,
         );
     }
 
     #[test]
     fn test_publish_3() {
         let packet = MqttPacket::Publish(PublishPacket {
-            qos: 2,
+            qos: PublishQoS::QoS2(10),
             dup: true,
             retain: true,
             properties: Some(PublishProperties {
-                payload_format_indicator: false,
+                payload_format_indicator: Some(false),
                 subscription_identifiers: vec![128, 16384, 2097152],
                 content_type: None,
-                correlation_data: vec![],
+                correlation_data: None,
                 message_expiry_interval: None,
                 response_topic: None,
                 topic_alias: None,
@@ -174,7 +201,6 @@ mod tests {
             }),
             topic: "test".to_string(),
             payload: vec![116, 101, 115, 116],
-            message_id: Some(10),
         });
         let buf = vec![
             61, 27, // Header
@@ -192,27 +218,27 @@ mod tests {
             "publish MQTT 5 properties with 0-4 byte varbyte",
             packet.clone(),
             buf.clone(),
-            5,
+            ProtocolVersion::V5,
         );
         test_encode(
             "publish MQTT 5 properties with 0-4 byte varbyte",
             packet,
             buf,
-            5,
+            ProtocolVersion::V5,
         );
     }
 
     #[test]
     fn test_publish_4() {
         let packet = MqttPacket::Publish(PublishPacket {
-            qos: 2,
+            qos: PublishQoS::QoS2(10),
             dup: true,
             retain: true,
             properties: Some(PublishProperties {
-                payload_format_indicator: false,
+                payload_format_indicator: Some(false),
                 subscription_identifiers: vec![1, 268435455],
                 content_type: None,
-                correlation_data: vec![],
+                correlation_data: None,
                 message_expiry_interval: None,
                 response_topic: None,
                 topic_alias: None,
@@ -220,7 +246,6 @@ mod tests {
             }),
             topic: "test".to_string(),
             payload: vec![116, 101, 115, 116],
-            message_id: Some(10),
         });
         let buf = vec![
             61, 22, // Header
@@ -237,26 +262,25 @@ mod tests {
             "publish MQTT 5 properties with max value varbyte",
             packet.clone(),
             buf.clone(),
-            5,
+            ProtocolVersion::V5,
         );
         test_encode(
             "publish MQTT 5 properties with max value varbyte",
             packet,
             buf,
-            5,
+            ProtocolVersion::V5,
         );
     }
 
     #[test]
     fn test_publish_5() {
         let packet = MqttPacket::Publish(PublishPacket {
-            qos: 2,
+            qos: PublishQoS::QoS2(10),
             dup: true,
             retain: true,
             properties: None,
             topic: "test".to_string(),
             payload: vec![116, 101, 115, 116],
-            message_id: Some(10),
         });
         let buf = vec![
             61, 12, // Header
@@ -265,20 +289,24 @@ mod tests {
             0, 10, // Message ID
             116, 101, 115, 116, // Payload
         ];
-        test_decode("maximal publish", packet.clone(), buf.clone(), 3);
-        test_encode("maximal publish", packet, buf, 3);
+        test_decode(
+            "maximal publish",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V31,
+        );
+        test_encode("maximal publish", packet, buf, ProtocolVersion::V31);
     }
 
     #[test]
     fn test_publish_6() {
         let packet = MqttPacket::Publish(PublishPacket {
-            qos: 0,
+            qos: PublishQoS::QoS0,
             dup: false,
             retain: false,
             properties: None,
             topic: "test".to_string(),
             payload: vec![],
-            message_id: None,
         });
         let buf = vec![
             48, 6, // Header
@@ -286,8 +314,13 @@ mod tests {
             116, 101, 115, 116, // Topic
                  // Empty payload
         ];
-        test_decode("empty publish", packet.clone(), buf.clone(), 3);
-        test_encode("empty publish", packet, buf, 3);
+        test_decode(
+            "empty publish",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V31,
+        );
+        test_encode("empty publish", packet, buf, ProtocolVersion::V31);
     }
 
     #[test]
@@ -300,7 +333,7 @@ mod tests {
                 116, 101, 115, 116, // Topic
                      // Empty payload
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
     #[test]
@@ -308,14 +341,14 @@ mod tests {
         test_error_encode(
             "MQTT 5.0 var byte integer >24 bits throws error",
             MqttPacket::Publish(PublishPacket {
-                qos: 2,
+                qos: PublishQoS::QoS2(69),
                 dup: true,
                 retain: true,
                 properties: Some(PublishProperties {
-                    payload_format_indicator: false,
+                    payload_format_indicator: Some(false),
                     subscription_identifiers: vec![268435456],
                     content_type: None,
-                    correlation_data: vec![],
+                    correlation_data: None,
                     message_expiry_interval: None,
                     response_topic: None,
                     topic_alias: None,
@@ -323,10 +356,88 @@ mod tests {
                 }),
                 topic: "test".to_string(),
                 payload: vec![116, 101, 115, 116],
-                message_id: Some(69),
             }),
             "Invalid subscription_identifier: 268435456",
-            5,
+            ProtocolVersion::V5,
+        );
+    }
+
+    #[test]
+    fn test_error_disallowed_property() {
+        test_decode_error(
+            "Protocol error: property Session Expiry Interval (0x11) is not allowed in PUBLISH properties",
+            vec![
+                48, 12, // Header
+                0, 4, // Topic length
+                116, 101, 115, 116, // Topic (test)
+                5,  // properties length
+                17, 0, 0, 0, 1, // sessionExpiryInterval
+            ],
+            ProtocolVersion::V5,
+        );
+    }
+
+    #[test]
+    fn test_publish_absent_properties() {
+        let packet = MqttPacket::Publish(PublishPacket {
+            qos: PublishQoS::QoS0,
+            dup: false,
+            retain: false,
+            properties: Some(PublishProperties {
+                message_expiry_interval: Some(60),
+                ..PublishProperties::default()
+            }),
+            topic: "test".to_string(),
+            payload: vec![],
+        });
+        let buf = vec![
+            48, 12, // Header
+            0, 4, // Topic length
+            116, 101, 115, 116, // Topic (test)
+            5,   // properties length
+            2, 0, 0, 0, 60, // message expiry interval
+        ];
+        test_decode(
+            "publish without format indicator",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V5,
+        );
+        test_encode(
+            "publish without format indicator",
+            packet.clone(),
+            buf,
+            ProtocolVersion::V5,
+        );
+        if let MqttPacket::Publish(PublishPacket {
+            properties: Some(props),
+            ..
+        }) = packet
+        {
+            assert!(!props.payload_format_indicator());
+            assert_eq!(None, props.correlation_data);
+        }
+    }
+
+    #[test]
+    fn test_publish_qos_from_parts() {
+        assert_eq!(
+            Ok(PublishQoS::QoS0),
+            PublishQoS::from_parts(QoS::QoS0, None)
+        );
+        assert_eq!(
+            Ok(PublishQoS::QoS2(5)),
+            PublishQoS::from_parts(QoS::QoS2, Some(5))
+        );
+        assert_eq!(
+            Err("Invalid messageId".to_string()),
+            PublishQoS::from_parts(QoS::QoS1, None)
+        );
+        assert_eq!(
+            Err("QoS 0 PUBLISH must not have a messageId".to_string()),
+            PublishQoS::from_parts(QoS::QoS0, Some(1))
         );
+        assert_eq!(QoS::QoS1, PublishQoS::QoS1(3).qos());
+        assert_eq!(Some(3), PublishQoS::QoS1(3).message_id());
     }
 }
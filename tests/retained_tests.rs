@@ -0,0 +1,125 @@
+mod common;
+
+mod tests {
+    use crate::common::*;
+    use mqtt_packet_3_5::clock::*;
+    use mqtt_packet_3_5::retained::*;
+    use mqtt_packet_3_5::structure::*;
+    use std::time::Duration;
+
+    fn subscription(topic: &str, qos: QoS, rap: bool, rh: RetainHandling) -> Subscription {
+        Subscription {
+            topic: topic.to_string(),
+            qos,
+            nl: false,
+            rap,
+            rh,
+        }
+    }
+
+    fn topics(packets: &[PublishPacket]) -> Vec<&str> {
+        packets.iter().map(|p| p.topic.as_str()).collect()
+    }
+
+    #[test]
+    fn test_publish() {
+        let clock = ManualClock::new();
+        let mut store = MemoryRetainedStore::new();
+        assert!(!store.publish(&publish("PUBLISH topic=a payload=1"), &clock));
+        assert!(store.is_empty());
+        assert!(store.publish(
+            &publish("PUBLISH dup qos=1 id=1 retain topic=a payload=1"),
+            &clock
+        ));
+        assert!(store.publish(&publish("PUBLISH retain topic=a payload=2"), &clock));
+        assert_eq!(1, store.len());
+        assert_eq!(
+            publish("PUBLISH retain topic=a payload=2"),
+            store.get("a").unwrap().publish
+        );
+        // an empty payload deletes the retained message
+        assert!(store.publish(&publish("PUBLISH retain topic=a payload=\"\""), &clock));
+        assert!(store.is_empty());
+        assert!(!store.publish(&publish("PUBLISH retain topic=a payload=\"\""), &clock));
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let clock = ManualClock::new();
+        let mut store = MemoryRetainedStore::new();
+        for topic in ["a/b", "a/c", "a", "b/c", "$SYS/x"] {
+            store.publish(
+                &publish(&format!(
+                    "PUBLISH qos=2 id=1 retain topic={} payload=x",
+                    topic
+                )),
+                &clock,
+            );
+        }
+        let sub = subscription("a/#", QoS::QoS1, false, RetainHandling::SendAtSubscribe);
+        let sent = store.subscribe(&sub, true, &clock);
+        assert_eq!(vec!["a", "a/b", "a/c"], topics(&sent));
+        // sent with RETAIN regardless of Retain As Published, QoS downgraded
+        assert_eq!(
+            publish("PUBLISH qos=1 id=1 retain topic=a payload=x"),
+            sent[0]
+        );
+        assert_eq!(
+            vec!["$SYS/x"],
+            topics(&store.subscribe(
+                &subscription("$SYS/#", QoS::QoS0, false, RetainHandling::SendAtSubscribe),
+                false,
+                &clock
+            ))
+        );
+        assert!(store
+            .subscribe(
+                &subscription("#", QoS::QoS0, false, RetainHandling::SendAtSubscribe),
+                false,
+                &clock
+            )
+            .iter()
+            .all(|p| !p.topic.starts_with('$') && p.qos == PublishQoS::QoS0));
+
+        let if_new = subscription(
+            "a/b",
+            QoS::QoS2,
+            false,
+            RetainHandling::SendAtSubscribeIfNew,
+        );
+        assert_eq!(1, store.subscribe(&if_new, false, &clock).len());
+        assert!(store.subscribe(&if_new, true, &clock).is_empty());
+        let never = subscription("a/b", QoS::QoS2, false, RetainHandling::DoNotSend);
+        assert!(store.subscribe(&never, false, &clock).is_empty());
+        let shared = subscription(
+            "$share/g/a/b",
+            QoS::QoS2,
+            false,
+            RetainHandling::SendAtSubscribe,
+        );
+        assert!(store.subscribe(&shared, false, &clock).is_empty());
+    }
+
+    #[test]
+    fn test_expiry() {
+        let clock = ManualClock::new();
+        let mut store = MemoryRetainedStore::new();
+        store.publish(
+            &publish("PUBLISH retain topic=a props{expiry=10} payload=x"),
+            &clock,
+        );
+        store.publish(&publish("PUBLISH retain topic=b payload=x"), &clock);
+        let sub = subscription("+", QoS::QoS0, false, RetainHandling::SendAtSubscribe);
+        clock.advance(Duration::from_millis(3500));
+        assert_eq!(
+            publish("PUBLISH retain topic=a props{expiry=7} payload=x"),
+            store.subscribe(&sub, false, &clock)[0]
+        );
+        assert!(!store.get("a").unwrap().is_expired(&clock));
+        clock.advance(Duration::from_millis(6500));
+        assert_eq!(vec!["b"], topics(&store.subscribe(&sub, false, &clock)));
+        assert_eq!(1, store.remove_expired(&clock));
+        assert_eq!(None, store.get("a"));
+        assert_eq!(1, store.len());
+    }
+}
@@ -0,0 +1,83 @@
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::strategies;
+    use mqtt_packet_3_5::structure::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use proptest::sample::select;
+    use std::io::Cursor;
+
+    fn versions() -> impl Strategy<Value = ProtocolVersion> {
+        select(vec![
+            ProtocolVersion::V31,
+            ProtocolVersion::V311,
+            ProtocolVersion::V5,
+        ])
+    }
+
+    fn round_trip(packet: &MqttPacket, version: ProtocolVersion) -> Res<MqttPacket> {
+        let buf = packet.clone().encode(version)?;
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(buf));
+        let decoded = decoder.decode_packet(version)?;
+        if decoder.has_more() {
+            return Err("Bytes left after decoding".to_string());
+        }
+        Ok(decoded)
+    }
+
+    /// decodes everything in buf, which must neither panic nor loop forever
+    fn decode_all(buf: Vec<u8>, options: DecoderOptions, version: ProtocolVersion) {
+        let mut decoder = PacketDecoder::from_stream(Cursor::new(buf)).with_options(options);
+        while decoder.has_more() {
+            let _ = decoder.try_decode_packet(version);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip_v31(packet in strategies::packet(ProtocolVersion::V31)) {
+            prop_assert_eq!(Ok(packet.clone()), round_trip(&packet, ProtocolVersion::V31));
+        }
+
+        #[test]
+        fn test_round_trip_v311(packet in strategies::packet(ProtocolVersion::V311)) {
+            prop_assert_eq!(Ok(packet.clone()), round_trip(&packet, ProtocolVersion::V311));
+        }
+
+        #[test]
+        fn test_round_trip_v5(packet in strategies::packet(ProtocolVersion::V5)) {
+            prop_assert_eq!(Ok(packet.clone()), round_trip(&packet, ProtocolVersion::V5));
+        }
+
+        #[test]
+        fn test_decode_random_bytes(buf in vec(any::<u8>(), 0..64), version in versions()) {
+            decode_all(buf.clone(), DecoderOptions::strict(), version);
+            decode_all(buf, DecoderOptions::lenient(), version);
+        }
+
+        #[test]
+        fn test_decode_corrupted_packets(
+            packet in strategies::packet(ProtocolVersion::V5),
+            version in versions(),
+            index in any::<prop::sample::Index>(),
+            byte in any::<u8>(),
+        ) {
+            let mut buf = packet.encode(ProtocolVersion::V5).unwrap();
+            let i = index.index(buf.len());
+            buf[i] = byte;
+            decode_all(buf.clone(), DecoderOptions::strict(), version);
+            decode_all(buf, DecoderOptions::lenient(), version);
+        }
+
+        #[test]
+        fn test_encode_arbitrary_packets(data in vec(any::<u8>(), 0..512), version in versions()) {
+            // packets that don't follow the spec may fail to encode, but must not panic
+            if let Ok(packet) = MqttPacket::arbitrary(&mut Unstructured::new(&data)) {
+                if let Ok(buf) = packet.encode(version) {
+                    decode_all(buf, DecoderOptions::strict(), version);
+                }
+            }
+        }
+    }
+}
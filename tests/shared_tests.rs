@@ -0,0 +1,236 @@
+mod common;
+
+mod tests {
+    use crate::common::*;
+    use mqtt_packet_3_5::shared::*;
+    use mqtt_packet_3_5::structure::*;
+
+    fn subscription(topic: &str, qos: QoS) -> Subscription {
+        Subscription {
+            topic: topic.to_string(),
+            qos,
+            nl: false,
+            rap: false,
+            rh: RetainHandling::SendAtSubscribe,
+        }
+    }
+
+    /// a dispatcher with members a, b and c of `$share/g/t/#`
+    fn dispatcher(strategy: Strategy) -> SharedGroupDispatcher {
+        let mut dispatcher = SharedGroupDispatcher::new(strategy);
+        for client_id in ["a", "b", "c"] {
+            dispatcher
+                .subscribe(client_id, &subscription("$share/g/t/#", QoS::QoS1))
+                .unwrap();
+        }
+        dispatcher
+    }
+
+    /// the receiving member of a QoS 0 message for each topic
+    fn receivers(dispatcher: &mut SharedGroupDispatcher, topics: &[&str]) -> Vec<String> {
+        topics
+            .iter()
+            .flat_map(|topic| {
+                dispatcher.dispatch(&publish(&format!("PUBLISH topic={} payload=x", topic)))
+            })
+            .map(|delivery| delivery.client_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let mut dispatcher = SharedGroupDispatcher::new(Strategy::RoundRobin);
+        assert!(dispatcher
+            .subscribe("a", &subscription("t/#", QoS::QoS0))
+            .is_err());
+        dispatcher
+            .subscribe("a", &subscription("$share/g/t/#", QoS::QoS0))
+            .unwrap();
+        dispatcher
+            .subscribe("b", &subscription("$share/h/t/x", QoS::QoS2))
+            .unwrap();
+        dispatcher
+            .subscribe("a", &subscription("$share/g/t/#", QoS::QoS1))
+            .unwrap();
+        assert_eq!(vec!["a"], dispatcher.members("$share/g/t/#"));
+
+        // one member of every matching group, with the QoS of its subscription
+        let deliveries = dispatcher.dispatch(&publish("PUBLISH qos=2 id=1 topic=t/x payload=x"));
+        assert_eq!(2, deliveries.len());
+        assert_eq!(
+            ("a", "$share/g/t/#"),
+            (
+                deliveries[0].client_id.as_str(),
+                deliveries[0].filter.as_str()
+            )
+        );
+        assert_eq!(PublishQoS::QoS1(1), deliveries[0].publish.qos);
+        assert_eq!(
+            ("b", "$share/h/t/x"),
+            (
+                deliveries[1].client_id.as_str(),
+                deliveries[1].filter.as_str()
+            )
+        );
+        assert_eq!(PublishQoS::QoS2(1), deliveries[1].publish.qos);
+        assert!(dispatcher
+            .dispatch(&publish("PUBLISH topic=u payload=x"))
+            .is_empty());
+
+        assert!(dispatcher.unsubscribe("b", "$share/h/t/x"));
+        assert!(!dispatcher.unsubscribe("b", "$share/h/t/x"));
+        assert_eq!(
+            1,
+            dispatcher
+                .dispatch(&publish("PUBLISH topic=t/x payload=x"))
+                .len()
+        );
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut dispatcher = dispatcher(Strategy::RoundRobin);
+        assert_eq!(
+            vec!["a", "b", "c", "a"],
+            receivers(&mut dispatcher, &["t/1", "t/1", "t/2", "t/3"])
+        );
+        // disconnected members are skipped until they connect again
+        assert!(dispatcher.disconnected("c").is_empty());
+        assert_eq!(
+            vec!["b", "a", "b"],
+            receivers(&mut dispatcher, &["t/1", "t/1", "t/1"])
+        );
+        dispatcher.connected("c");
+        assert_eq!(vec!["c", "a"], receivers(&mut dispatcher, &["t/1", "t/1"]));
+        // with no member connected the message waits in a session
+        for client_id in ["a", "b", "c"] {
+            dispatcher.disconnected(client_id);
+        }
+        assert_eq!(vec!["b"], receivers(&mut dispatcher, &["t/1"]));
+    }
+
+    #[test]
+    fn test_random() {
+        let topics = ["t/1"; 20];
+        let first = receivers(&mut dispatcher(Strategy::Random { seed: 7 }), &topics);
+        assert_eq!(
+            first,
+            receivers(&mut dispatcher(Strategy::Random { seed: 7 }), &topics)
+        );
+        assert_ne!(
+            first,
+            receivers(&mut dispatcher(Strategy::Random { seed: 8 }), &topics)
+        );
+        for client_id in ["a", "b", "c"] {
+            assert!(first.iter().any(|c| c == client_id));
+        }
+    }
+
+    #[test]
+    fn test_sticky_by_topic() {
+        let mut dispatcher = dispatcher(Strategy::StickyByTopic);
+        assert_eq!(
+            vec!["a", "b", "a", "c", "b", "a"],
+            receivers(&mut dispatcher, &["t/1", "t/2", "t/1", "t/3", "t/2", "t/1"])
+        );
+        // the topics of a member that left are assigned again
+        dispatcher.disconnected("a");
+        assert_eq!(
+            vec!["b", "b", "c"],
+            receivers(&mut dispatcher, &["t/1", "t/1", "t/3"])
+        );
+        dispatcher.connected("a");
+        assert_eq!(vec!["b"], receivers(&mut dispatcher, &["t/1"]));
+    }
+
+    #[test]
+    fn test_least_inflight() {
+        let mut dispatcher = dispatcher(Strategy::LeastInflight);
+        let message = publish("PUBLISH qos=1 id=1 topic=t/1 payload=x");
+        let first: Vec<Delivery> = (0..3).flat_map(|_| dispatcher.dispatch(&message)).collect();
+        assert_eq!(
+            vec!["a", "b", "c"],
+            first
+                .iter()
+                .map(|d| d.client_id.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(dispatcher.completed(first[1].id));
+        assert!(!dispatcher.completed(first[1].id));
+        assert_eq!(0, dispatcher.inflight("b"));
+        assert_eq!("b", dispatcher.dispatch(&message)[0].client_id);
+        assert!(dispatcher.completed(first[2].id));
+        // a tie is broken round robin
+        assert_eq!("c", dispatcher.dispatch(&message)[0].client_id);
+        // QoS 0 deliveries are not tracked
+        assert_eq!(vec!["a"], receivers(&mut dispatcher, &["t/1"]));
+        assert_eq!(1, dispatcher.inflight("a"));
+    }
+
+    #[test]
+    fn test_redelivery() {
+        let mut dispatcher = dispatcher(Strategy::RoundRobin);
+        dispatcher
+            .subscribe("c", &subscription("$share/g/t/#", QoS::QoS2))
+            .unwrap();
+        let qos1 = dispatcher
+            .dispatch(&publish("PUBLISH qos=1 id=1 topic=t/1 payload=x"))
+            .remove(0);
+        assert_eq!("a", qos1.client_id);
+        let redelivered = dispatcher.failed(qos1.id).unwrap();
+        assert_eq!("b", redelivered.client_id);
+        assert_eq!(
+            publish("PUBLISH qos=1 id=1 topic=t/1 payload=x"),
+            redelivered.publish
+        );
+        assert_eq!(None, dispatcher.failed(qos1.id));
+        assert_eq!((0, 1), (dispatcher.inflight("a"), dispatcher.inflight("b")));
+
+        // a QoS 2 delivery stays with its member
+        let qos2 = dispatcher
+            .dispatch(&publish("PUBLISH qos=2 id=2 topic=t/1 payload=x"))
+            .remove(0);
+        assert_eq!(
+            ("c", PublishQoS::QoS2(2)),
+            (qos2.client_id.as_str(), qos2.publish.qos)
+        );
+        assert_eq!(None, dispatcher.failed(qos2.id));
+        assert!(dispatcher.disconnected("c").is_empty());
+        assert_eq!(1, dispatcher.inflight("c"));
+
+        // a refused message is discarded, not sent to another member
+        let refused = dispatcher
+            .dispatch(&publish("PUBLISH qos=1 id=3 topic=t/1 payload=x"))
+            .remove(0);
+        assert_eq!("a", refused.client_id);
+        assert!(dispatcher.rejected(refused.id));
+        assert!(!dispatcher.rejected(refused.id));
+        assert_eq!(None, dispatcher.failed(refused.id));
+        assert_eq!(0, dispatcher.inflight("a"));
+
+        // the unacknowledged QoS 1 messages of a disconnected member move on
+        let moved = dispatcher.disconnected("b");
+        assert_eq!(
+            vec!["a"],
+            moved
+                .iter()
+                .map(|d| d.client_id.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!((1, 0), (dispatcher.inflight("a"), dispatcher.inflight("b")));
+
+        // the last member keeps its message
+        let mut dispatcher = SharedGroupDispatcher::new(Strategy::RoundRobin);
+        dispatcher
+            .subscribe("a", &subscription("$share/g/t", QoS::QoS1))
+            .unwrap();
+        let only = dispatcher
+            .dispatch(&publish("PUBLISH qos=1 id=1 topic=t payload=x"))
+            .remove(0);
+        assert_eq!(None, dispatcher.failed(only.id));
+        assert_eq!(1, dispatcher.inflight("a"));
+        assert!(dispatcher.session_ended("a").is_empty());
+        assert_eq!(0, dispatcher.inflight("a"));
+        assert!(dispatcher.members("$share/g/t").is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+mod common;
+
+mod tests {
+    use crate::common::*;
+    use mqtt_packet_3_5::clock::*;
+    use mqtt_packet_3_5::stored::*;
+    use std::time::Duration;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_stored_message() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let message =
+            StoredMessage::new(publish("PUBLISH topic=a props{expiry=5} payload=x"), &clock);
+        assert_eq!(start, message.received);
+        assert_eq!(Some(start + secs(5)), message.expires_at());
+        assert_eq!(
+            Some(publish("PUBLISH topic=a props{expiry=5} payload=x")),
+            message.forward(&clock)
+        );
+        // the remaining lifetime is rounded up to whole seconds
+        clock.advance(Duration::from_millis(1999));
+        assert_eq!(
+            Some(publish("PUBLISH topic=a props{expiry=4} payload=x")),
+            message.forward(&clock)
+        );
+        clock.advance(Duration::from_millis(3000));
+        assert!(!message.is_expired(&clock));
+        assert_eq!(
+            Some(publish("PUBLISH topic=a props{expiry=1} payload=x")),
+            message.forward(&clock)
+        );
+        clock.advance(Duration::from_millis(1));
+        assert!(message.is_expired(&clock));
+        assert_eq!(None, message.forward(&clock));
+
+        // without an expiry interval a message never expires
+        let message = StoredMessage::new(publish("PUBLISH topic=a props{} payload=x"), &clock);
+        assert_eq!(None, message.expires_at());
+        clock.advance(secs(1_000_000));
+        assert_eq!(Some(message.publish.clone()), message.forward(&clock));
+        // an interval of 0 expires at once
+        let message =
+            StoredMessage::new(publish("PUBLISH topic=a props{expiry=0} payload=x"), &clock);
+        assert!(message.is_expired(&clock));
+    }
+
+    #[test]
+    fn test_offline_queue() {
+        let clock = ManualClock::new();
+        let mut queue = OfflineQueue::new();
+        queue.push(publish("PUBLISH topic=a props{expiry=1} payload=1"), &clock);
+        queue.push(publish("PUBLISH topic=a payload=2"), &clock);
+        queue.push(
+            publish("PUBLISH topic=a props{expiry=10} payload=3"),
+            &clock,
+        );
+        queue.push(publish("PUBLISH topic=a props{expiry=2} payload=4"), &clock);
+        assert_eq!(4, queue.len());
+        clock.advance(secs(1));
+        assert_eq!(
+            Some(publish("PUBLISH topic=a payload=2")),
+            queue.pop(&clock)
+        );
+        assert_eq!(2, queue.len());
+        assert_eq!(0, queue.remove_expired(&clock));
+        clock.advance(secs(1));
+        assert_eq!(1, queue.remove_expired(&clock));
+        assert_eq!(
+            vec![publish("PUBLISH topic=a props{expiry=8} payload=3")],
+            queue.drain(&clock)
+        );
+        assert!(queue.is_empty());
+        assert_eq!(None, queue.pop(&clock));
+    }
+}
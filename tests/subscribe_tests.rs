@@ -9,7 +9,12 @@ mod tests {
         PacketDecoder::new(ByteReader::new(BufReader::new(c)))
     }
 
-    fn test_decode(name: &str, packet: MqttPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", name);
         assert_eq!(packet, decoder.decode_packet(protocol_version).unwrap());
@@ -17,7 +22,7 @@ mod tests {
         assert_eq!(buf, packet.encode(protocol_version).unwrap());
     }
 
-    fn test_decode_error(msg: &str, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode_error(msg: &str, buf: Vec<u8>, protocol_version: ProtocolVersion) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", msg);
         assert_eq!(
@@ -37,7 +42,7 @@ mod tests {
                 116, 101, 115, 116, // Topic (test)
                 0,   // Qos (0)
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -46,14 +51,14 @@ mod tests {
         test_decode(
             "subscribe to one topic",
             MqttPacket::Subscribe(SubscribePacket {
-                qos: 1,
+                qos: QoS::QoS1,
                 message_id: 6,
                 subscriptions: vec![Subscription {
                     qos: QoS::QoS0,
                     topic: "test".to_string(),
                     nl: false,
                     rap: false,
-                    rh: None,
+                    rh: RetainHandling::SendAtSubscribe,
                 }],
                 properties: None,
             }),
@@ -64,7 +69,7 @@ mod tests {
                 116, 101, 115, 116, // Topic (test)
                 0,   // Qos (0)
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -79,7 +84,7 @@ mod tests {
                 116, 101, 115, 116, // Topic (test)
                 3,   // Qos
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -95,7 +100,7 @@ mod tests {
                 116, 101, 115, 116,  // Topic (test)
                 0x80, // Flags
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -111,7 +116,7 @@ mod tests {
                 116, 101, 115, 116,  // Topic (test)
                 0x30, // Flags
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -126,7 +131,7 @@ mod tests {
                 116, 101, 115, 116,  // Topic (test)
                 0x08, // Flags
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -135,17 +140,17 @@ mod tests {
         test_decode(
             "subscribe to one topic by MQTT 5",
             MqttPacket::Subscribe(SubscribePacket {
-                qos: 1,
+                qos: QoS::QoS1,
                 message_id: 6,
                 subscriptions: vec![Subscription {
                     topic: "test".to_string(),
                     qos: QoS::QoS0,
                     nl: false,
                     rap: true,
-                    rh: Some(1),
+                    rh: RetainHandling::SendAtSubscribeIfNew,
                 }],
                 properties: Some(SubscribeProperties {
-                    subscription_identifier: 145,
+                    subscription_identifier: Some(145),
                     user_properties: [("test".to_string(), vec!["test".to_string()])]
                         .into_iter()
                         .collect::<UserProperties>(),
@@ -161,7 +166,7 @@ mod tests {
                 116, 101, 115, 116, // Topic (test)
                 24, // settings(qos: 0, noLocal: false, Retain as Published: true, retain handling: 1)
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -170,7 +175,7 @@ mod tests {
         test_decode(
             "subscribe to three topics",
             MqttPacket::Subscribe(SubscribePacket {
-                qos: 1,
+                qos: QoS::QoS1,
                 message_id: 6,
                 subscriptions: vec![
                     Subscription {
@@ -178,21 +183,21 @@ mod tests {
                         qos: QoS::QoS0,
                         nl: false,
                         rap: false,
-                        rh: None,
+                        rh: RetainHandling::SendAtSubscribe,
                     },
                     Subscription {
                         topic: "uest".to_string(),
                         qos: QoS::QoS1,
                         nl: false,
                         rap: false,
-                        rh: None,
+                        rh: RetainHandling::SendAtSubscribe,
                     },
                     Subscription {
                         topic: "tfst".to_string(),
                         qos: QoS::QoS2,
                         nl: false,
                         rap: false,
-                        rh: None,
+                        rh: RetainHandling::SendAtSubscribe,
                     },
                 ],
                 properties: None,
@@ -210,7 +215,7 @@ mod tests {
                 116, 102, 115, 116, // Topic (tfst)
                 2,   // Qos (2)
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -219,10 +224,10 @@ mod tests {
         test_decode(
             "subscribe to 3 topics by MQTT 5",
             MqttPacket::Subscribe(SubscribePacket {
-                qos: 1,
+                qos: QoS::QoS1,
                 message_id: 6,
                 properties: Some(SubscribeProperties {
-                    subscription_identifier: 145,
+                    subscription_identifier: Some(145),
                     user_properties: [("test".to_string(), vec!["test".to_string()])]
                         .into_iter()
                         .collect::<UserProperties>(),
@@ -233,21 +238,21 @@ mod tests {
                         qos: QoS::QoS0,
                         nl: false,
                         rap: true,
-                        rh: Some(1),
+                        rh: RetainHandling::SendAtSubscribeIfNew,
                     },
                     Subscription {
                         topic: "uest".to_string(),
                         qos: QoS::QoS1,
                         nl: false,
                         rap: false,
-                        rh: Some(0),
+                        rh: RetainHandling::SendAtSubscribe,
                     },
                     Subscription {
                         topic: "tfst".to_string(),
                         qos: QoS::QoS2,
                         nl: true,
                         rap: false,
-                        rh: Some(0),
+                        rh: RetainHandling::SendAtSubscribe,
                     },
                 ],
             }),
@@ -267,7 +272,7 @@ mod tests {
                 116, 102, 115, 116, // Topic (tfst)
                 6,   // Qos (2), No Local: true
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -289,7 +294,7 @@ mod tests {
                 0, 6, // Message ID
                 0, 1, 2, 0x80, // rejected subscription
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -298,16 +303,14 @@ mod tests {
         test_decode(
             "suback",
             MqttPacket::Suback(SubackPacket {
-                reason_code: None,
                 properties: None,
                 message_id: 6,
-                granted_reason_codes: vec![
+                granted: vec![
                     SubscriptionReasonCode::GrantedQoS0,
                     SubscriptionReasonCode::GrantedQoS1,
                     SubscriptionReasonCode::GrantedQoS2,
                     SubscriptionReasonCode::UnspecifiedError,
                 ],
-                granted: vec![],
             }),
             vec![
                 144, 7, // Header
@@ -315,7 +318,7 @@ mod tests {
                 0, // Property length
                 0, 1, 2, 128, // Granted qos (0, 1, 2) and a rejected being 0x80
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 
@@ -328,7 +331,7 @@ mod tests {
                 0, 6, // Message ID
                 0, 1, 2, 0x79, // Granted qos (0, 1, 2) and an invalid code
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -337,7 +340,6 @@ mod tests {
         test_decode(
             "suback MQTT 5",
             MqttPacket::Suback(SubackPacket {
-                reason_code: None,
                 properties: Some(ConfirmationProperties {
                     reason_string: Some("test".to_string()),
                     user_properties: [("test".to_string(), vec!["test".to_string()])]
@@ -345,13 +347,12 @@ mod tests {
                         .collect::<UserProperties>(),
                 }),
                 message_id: 6,
-                granted_reason_codes: vec![
+                granted: vec![
                     SubscriptionReasonCode::GrantedQoS0,
                     SubscriptionReasonCode::GrantedQoS1,
                     SubscriptionReasonCode::GrantedQoS2,
                     SubscriptionReasonCode::UnspecifiedError,
                 ],
-                granted: vec![],
             }),
             vec![
                 144, 27, // Header
@@ -361,7 +362,99 @@ mod tests {
                 38, 0, 4, 116, 101, 115, 116, 0, 4, 116, 101, 115, 116, // userProperties
                 0, 1, 2, 128, // Granted qos (0, 1, 2) and a rejected being 0x80
             ],
-            5,
+            ProtocolVersion::V5,
+        );
+    }
+
+    #[test]
+    fn test_sub_error_duplicate_identifier() {
+        test_decode_error(
+            "Protocol error: duplicate property Subscription Identifier (0x0B) in SUBSCRIBE properties",
+            vec![
+                130, 14, // Header (subscribeqos=1length=14)
+                0, 6, // Message ID (6)
+                4, // properties length
+                11, 1, // subscriptionIdentifier
+                11, 2, // subscriptionIdentifier (again)
+                0, 4, // Topic length,
+                116, 101, 115, 116, // Topic (test)
+                0,   // Options
+            ],
+            ProtocolVersion::V5,
+        );
+    }
+
+    #[test]
+    fn test_suback_v5_codes_on_v3() {
+        let packet = MqttPacket::Suback(SubackPacket {
+            properties: None,
+            message_id: 6,
+            granted: vec![
+                SubscriptionReasonCode::GrantedQoS1,
+                SubscriptionReasonCode::NotAuthorized,
+                SubscriptionReasonCode::WildcardSubscriptionsNotSupported,
+            ],
+        });
+        assert_eq!(
+            Ok(vec![
+                144, 5, // Header
+                0, 6, // Message ID
+                1, 0x80, 0x80, // v5 only failures are written as 0x80
+            ]),
+            packet.clone().encode(ProtocolVersion::V311)
+        );
+        assert_eq!(
+            Ok(vec![
+                144, 6, // Header
+                0, 6, // Message ID
+                0, // Property length
+                1, 0x87, 0xA2,
+            ]),
+            packet.encode(ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_suback_failure_on_v31() {
+        // a failure code that only exists in MQTT 5 is written as 0x80, which
+        // decodes to the same packet on MQTT 3.1 as on 3.1.1
+        let packet = MqttPacket::Suback(SubackPacket {
+            properties: None,
+            message_id: 6,
+            granted: vec![
+                SubscriptionReasonCode::GrantedQoS1,
+                SubscriptionReasonCode::NotAuthorized,
+            ],
+        });
+        let buf = vec![
+            144, 4, // Header
+            0, 6, // Message ID
+            1, 0x80,
+        ];
+        for protocol_version in [ProtocolVersion::V31, ProtocolVersion::V311] {
+            assert_eq!(Ok(buf.clone()), packet.clone().encode(protocol_version));
+            test_decode(
+                "suback with a failure",
+                MqttPacket::Suback(SubackPacket::new_v3(
+                    6,
+                    vec![Granted::QoS1, Granted::Failure],
+                )),
+                buf.clone(),
+                protocol_version,
+            );
+        }
+    }
+
+    #[test]
+    fn test_suback_encode_empty_error() {
+        let empty = SubackPacket {
+            properties: None,
+            message_id: 6,
+            granted: vec![],
+        };
+        assert_eq!(
+            Err("Cannot encode SUBACK without any granted results".to_string()),
+            empty.encode(ProtocolVersion::V5)
         );
     }
 }
@@ -0,0 +1,33 @@
+mod common;
+
+mod tests {
+    use crate::common::*;
+    use mqtt_packet_3_5::structure::*;
+    use mqtt_packet_3_5::subscription::*;
+
+    fn subscription(topic: &str, qos: QoS, rap: bool) -> Subscription {
+        Subscription {
+            topic: topic.to_string(),
+            qos,
+            nl: false,
+            rap,
+            rh: RetainHandling::SendAtSubscribe,
+        }
+    }
+
+    #[test]
+    fn test_forward() {
+        let received =
+            publish("PUBLISH dup qos=2 id=7 retain topic=a props{topic_alias=3} payload=x");
+        let rap = subscription("a", QoS::QoS2, true);
+        assert_eq!(
+            publish("PUBLISH qos=2 id=7 retain topic=a props{} payload=x"),
+            forward(&received, &rap)
+        );
+        let sub = subscription("a", QoS::QoS0, false);
+        assert_eq!(
+            publish("PUBLISH qos=0 topic=a props{} payload=x"),
+            forward(&received, &sub)
+        );
+    }
+}
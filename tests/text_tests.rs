@@ -0,0 +1,282 @@
+mod tests {
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::strategies;
+    use mqtt_packet_3_5::structure::*;
+    use mqtt_packet_3_5::text;
+    use proptest::prelude::*;
+
+    /// text is printed as is and parsed back into packet
+    fn check(text: &str, packet: MqttPacket) {
+        assert_eq!(Ok(packet.clone()), text::parse(text));
+        assert_eq!(text, packet.to_string());
+    }
+
+    #[test]
+    fn test_publish() {
+        let mut user_properties = UserProperties::new();
+        user_properties.push("k", "v");
+        let packet = MqttPacket::Publish(PublishPacket {
+            dup: false,
+            qos: PublishQoS::QoS1(10),
+            retain: true,
+            topic: "a/b".to_string(),
+            payload: b"hi".to_vec(),
+            properties: Some(PublishProperties {
+                message_expiry_interval: Some(60),
+                user_properties,
+                ..Default::default()
+            }),
+        });
+        check(
+            "PUBLISH qos=1 id=10 retain topic=\"a/b\" props{expiry=60 user{k=v}} payload=\"hi\"",
+            packet.clone(),
+        );
+        // fields in any order, flags with values, numbers in hex
+        assert_eq!(
+            Ok(packet),
+            "PUBLISH  payload=\"hi\" retain=true id=0x0a qos=1 props{ user{ \"k\"=v } expiry=60 } topic=\"a/b\""
+                .parse::<MqttPacket>()
+        );
+        check(
+            "PUBLISH dup qos=0 topic=t props{correlation=0xff00 sub_id=1 sub_id=2} payload=0x80",
+            MqttPacket::Publish(PublishPacket {
+                dup: true,
+                qos: PublishQoS::QoS0,
+                retain: false,
+                topic: "t".to_string(),
+                payload: vec![0x80],
+                properties: Some(PublishProperties {
+                    correlation_data: Some(vec![0xff, 0]),
+                    subscription_identifiers: vec![1, 2],
+                    ..Default::default()
+                }),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_connect() {
+        check(
+            "CONNECT version=5 client_id=c-1 clean_session keep_alive=30 user_name=\"user name\" \
+             will{qos=1 retain topic=w payload=\"\\\"bye\\\"\\n\" props{will_delay=5}} \
+             props{session_expiry=10 auth_method=SCRAM receive_max=20}",
+            MqttPacket::Connect(ConnectPacket {
+                client_id: "c-1".to_string(),
+                protocol_version: ProtocolVersion::V5,
+                clean_session: true,
+                keep_alive: 30,
+                user_name: Some("user name".to_string()),
+                password: None,
+                will: Some(LastWill {
+                    topic: Some("w".to_string()),
+                    payload: Some("\"bye\"\n".to_string()),
+                    qos: QoS::QoS1,
+                    retain: true,
+                    properties: Some(WillProperties {
+                        will_delay_interval: Some(5),
+                        ..Default::default()
+                    }),
+                }),
+                properties: Some(ConnectProperties {
+                    session_expiry_interval: Some(10),
+                    receive_maximum: Some(20),
+                    authentication_method: Some("SCRAM".to_string()),
+                    ..Default::default()
+                }),
+            }),
+        );
+        check(
+            "CONNACK session_present code=0",
+            MqttPacket::Connack(ConnackPacket {
+                code: 0,
+                session_present: true,
+                properties: None,
+            }),
+        );
+        check(
+            "CONNACK code=0 props{max_qos=1 retain_available=false}",
+            MqttPacket::Connack(ConnackPacket {
+                code: 0,
+                session_present: false,
+                properties: Some(ConnackProperties {
+                    maximum_qos: Some(QoS::QoS1),
+                    retain_available: Some(false),
+                    ..Default::default()
+                }),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_confirmations() {
+        check(
+            "PUBACK id=1 reason=NoMatchingSubscribers props{reason_string=none}",
+            MqttPacket::Puback(PubackPacket {
+                message_id: 1,
+                reason_code: Some(PubackPubrecCode::NoMatchingSubscribers),
+                properties: Some(ConfirmationProperties {
+                    reason_string: Some("none".to_string()),
+                    ..Default::default()
+                }),
+            }),
+        );
+        check(
+            "PUBREC id=2",
+            MqttPacket::Pubrec(PubrecPacket {
+                message_id: 2,
+                reason_code: None,
+                properties: None,
+            }),
+        );
+        check(
+            "PUBREL id=3 reason=PacketIdentifierNotFound",
+            MqttPacket::Pubrel(PubrelPacket {
+                message_id: 3,
+                reason_code: Some(PubcompPubrelCode::PacketIdentifierNotFound),
+                properties: None,
+            }),
+        );
+        check(
+            "PUBCOMP id=4 reason=Success",
+            MqttPacket::Pubcomp(PubcompPacket {
+                message_id: 4,
+                reason_code: Some(PubcompPubrelCode::Success),
+                properties: None,
+            }),
+        );
+        // reason codes may be given as numbers
+        assert_eq!(
+            text::parse("PUBCOMP id=4 reason=0x92"),
+            text::parse("PUBCOMP id=4 reason=PacketIdentifierNotFound")
+        );
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        check(
+            "SUBSCRIBE id=5 props{sub_id=3} sub{topic=\"a/#\" qos=2 no_local retain_handling=DoNotSend} sub{topic=b qos=0}",
+            MqttPacket::Subscribe(SubscribePacket {
+                qos: QoS::QoS1,
+                message_id: 5,
+                properties: Some(SubscribeProperties {
+                    subscription_identifier: Some(3),
+                    ..Default::default()
+                }),
+                subscriptions: vec![
+                    Subscription {
+                        topic: "a/#".to_string(),
+                        qos: QoS::QoS2,
+                        nl: true,
+                        rap: false,
+                        rh: RetainHandling::DoNotSend,
+                    },
+                    Subscription {
+                        topic: "b".to_string(),
+                        qos: QoS::QoS0,
+                        nl: false,
+                        rap: false,
+                        rh: RetainHandling::SendAtSubscribe,
+                    },
+                ],
+            }),
+        );
+        check(
+            "SUBACK id=5 granted=[GrantedQoS2 NotAuthorized]",
+            MqttPacket::Suback(SubackPacket {
+                message_id: 5,
+                properties: None,
+                granted: vec![
+                    SubscriptionReasonCode::GrantedQoS2,
+                    SubscriptionReasonCode::NotAuthorized,
+                ],
+            }),
+        );
+        check(
+            "UNSUBSCRIBE id=6 topics=[\"a/#\" b]",
+            MqttPacket::Unsubscribe(UnsubscribePacket {
+                qos: QoS::QoS1,
+                message_id: 6,
+                properties: None,
+                unsubscriptions: vec!["a/#".to_string(), "b".to_string()],
+            }),
+        );
+        check(
+            "UNSUBACK id=6 props{user{a=1 a=2}} granted=[Success NoSubscriptionExisted]",
+            MqttPacket::Unsuback(UnsubackPacket {
+                message_id: 6,
+                granted: vec![UnsubackCode::Success, UnsubackCode::NoSubscriptionExisted],
+                properties: Some(ConfirmationProperties {
+                    user_properties: vec![
+                        ("a".to_string(), "1".to_string()),
+                        ("a".to_string(), "2".to_string()),
+                    ]
+                    .into(),
+                    ..Default::default()
+                }),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_other_packets() {
+        check("PINGREQ", MqttPacket::Pingreq);
+        check("PINGRESP", MqttPacket::Pingresp);
+        check(
+            "DISCONNECT reason=NormalDisconnection props{session_expiry=0}",
+            MqttPacket::Disconnect(DisconnectPacket {
+                reason_code: Some(DisconnectCode::NormalDisconnection),
+                properties: Some(DisconnectProperties {
+                    session_expiry_interval: Some(0),
+                    ..Default::default()
+                }),
+            }),
+        );
+        check(
+            "AUTH reason=ContinueAuthentication props{auth_method=SCRAM auth_data=\"\"}",
+            MqttPacket::Auth(AuthPacket {
+                reason_code: AuthCode::ContinueAuthentication,
+                properties: Some(AuthProperties {
+                    authentication_method: "SCRAM".to_string(),
+                    authentication_data: Some("".to_string()),
+                    reason_string: None,
+                    user_properties: UserProperties::new(),
+                }),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let errors = [
+            ("PUBLISH qos=1 topic=t", "Invalid messageId"),
+            ("PUBLISH topic=t nope", "Unknown field nope in PUBLISH"),
+            ("PUBLISH topic=t topic=u", "Duplicate topic in PUBLISH"),
+            ("PUBLISH qos=0", "Missing topic in PUBLISH"),
+            ("PUBLISH topic=\"t", "Unterminated string at byte 16"),
+            ("PUBLISH topic=t props{expiry=1", "Missing closing bracket at byte 30"),
+            ("PUBLISH topic=t props{expiry=-1}", "Invalid number -1"),
+            ("PUBLISH topic=t props{nope=1}", "Unknown property nope"),
+            ("PUBACK id=1 reason=Nope", "Invalid reason code Nope"),
+            ("FOO", "Unknown packet type FOO"),
+            (
+                "PUBLISH topic=t props{session_expiry=1}",
+                "Protocol error: property Session Expiry Interval (0x11) is not allowed in PUBLISH properties",
+            ),
+        ];
+        for (input, error) in errors {
+            assert_eq!(Err(error.to_string()), text::parse(input), "{}", input);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_text_round_trip_v5(packet in strategies::packet(ProtocolVersion::V5)) {
+            prop_assert_eq!(Ok(packet.clone()), text::parse(&text::print(&packet)));
+        }
+
+        #[test]
+        fn test_text_round_trip_v311(packet in strategies::packet(ProtocolVersion::V311)) {
+            prop_assert_eq!(Ok(packet.clone()), text::parse(&text::print(&packet)));
+        }
+    }
+}
@@ -0,0 +1,37 @@
+mod tests {
+    use mqtt_packet_3_5::topic::*;
+
+    #[test]
+    fn test_matches() {
+        let cases = [
+            ("a/b", "a/b", true),
+            ("a/b", "a/c", false),
+            ("a/+", "a/b", true),
+            ("a/+", "a/", true),
+            ("a/+", "a", false),
+            ("a/+", "a/b/c", false),
+            ("+/+", "/b", true),
+            ("a/#", "a", true),
+            ("a/#", "a/b/c", true),
+            ("#", "a/b", true),
+            ("+/b/#", "a/b", true),
+            ("a", "a/b", false),
+            // wildcards at the start do not match $ topics
+            ("#", "$SYS/uptime", false),
+            ("+/uptime", "$SYS/uptime", false),
+            ("$SYS/#", "$SYS/uptime", true),
+        ];
+        for (filter, topic, expected) in cases {
+            assert_eq!(expected, matches(filter, topic), "{} {}", filter, topic);
+        }
+    }
+
+    #[test]
+    fn test_shared() {
+        assert_eq!(Some(("g", "a/#")), shared("$share/g/a/#"));
+        assert_eq!(None, shared("a/#"));
+        assert_eq!(None, shared("$share/g"));
+        assert_eq!(None, shared("$share//a"));
+        assert_eq!(None, shared("$share/g+/a"));
+    }
+}
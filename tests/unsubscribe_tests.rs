@@ -9,18 +9,28 @@ mod tests {
         PacketDecoder::new(ByteReader::new(BufReader::new(c)))
     }
 
-    fn test_decode(name: &str, packet: MqttPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", name);
         assert_eq!(packet, decoder.decode_packet(protocol_version).unwrap());
     }
 
-    fn test_encode(name: &str, packet: MqttPacket, buf: Vec<u8>, protocol_version: u8) {
+    fn test_encode(
+        name: &str,
+        packet: MqttPacket,
+        buf: Vec<u8>,
+        protocol_version: ProtocolVersion,
+    ) {
         println!("Failed encode {}", name);
         assert_eq!(buf, packet.encode(protocol_version).unwrap());
     }
 
-    fn test_decode_error(msg: &str, buf: Vec<u8>, protocol_version: u8) {
+    fn test_decode_error(msg: &str, buf: Vec<u8>, protocol_version: ProtocolVersion) {
         let mut decoder = dec_from_buf(buf.clone());
         println!("Failed: {}", msg);
         assert_eq!(
@@ -31,7 +41,7 @@ mod tests {
     #[test]
     fn test_unsubscribe_0() {
         let packet = MqttPacket::Unsubscribe(UnsubscribePacket {
-            qos: 1,
+            qos: QoS::QoS1,
             message_id: 7,
             properties: None,
             unsubscriptions: vec!["tfst".to_string(), "test".to_string()],
@@ -43,8 +53,13 @@ mod tests {
             0, 4, // Topic length,
             116, 101, 115, 116, // Topic (test)
         ];
-        test_decode("unsubscribe", packet.clone(), buf.clone(), 3);
-        test_encode("unsubscribe", packet, buf, 3);
+        test_decode(
+            "unsubscribe",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V31,
+        );
+        test_encode("unsubscribe", packet, buf, ProtocolVersion::V31);
     }
 
     #[test]
@@ -58,14 +73,14 @@ mod tests {
                 0, 4, // Topic length,
                 116, 101, 115, 116, // Topic (test)
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
     #[test]
     fn test_unsubscribe_1() {
         let packet = MqttPacket::Unsubscribe(UnsubscribePacket {
-            qos: 1,
+            qos: QoS::QoS1,
             message_id: 7,
             properties: Some(UnsubscribeProperties {
                 user_properties: vec![("test".to_string(), vec!["test".to_string()])]
@@ -83,8 +98,13 @@ mod tests {
             0, 4, // Topic length,
             116, 101, 115, 116, // Topic (test)
         ];
-        test_decode("unsubscribe MQTT 5", packet.clone(), buf.clone(), 5);
-        test_encode("unsubscribe MQTT 5", packet, buf, 5);
+        test_decode(
+            "unsubscribe MQTT 5",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V5,
+        );
+        test_encode("unsubscribe MQTT 5", packet, buf, ProtocolVersion::V5);
     }
 
     #[test]
@@ -98,8 +118,13 @@ mod tests {
             176, 2, // Header
             0, 8, // Message ID
         ];
-        test_decode("unsuback", packet.clone(), buf.clone(), 3);
-        test_encode("unsuback", packet, buf, 3);
+        test_decode(
+            "unsuback",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V31,
+        );
+        test_encode("unsuback", packet, buf, ProtocolVersion::V31);
     }
 
     #[test]
@@ -110,7 +135,7 @@ mod tests {
                 177, 2, // Header
                 0, 8, // Message ID
             ],
-            3,
+            ProtocolVersion::V31,
         );
     }
 
@@ -134,8 +159,13 @@ mod tests {
             38, 0, 4, 116, 101, 115, 116, 0, 4, 116, 101, 115, 116, // userProperties
             0, 128, // success and error
         ];
-        test_decode("unsuback MQTT 5", packet.clone(), buf.clone(), 5);
-        test_encode("unsuback MQTT 5", packet, buf, 5);
+        test_decode(
+            "unsuback MQTT 5",
+            packet.clone(),
+            buf.clone(),
+            ProtocolVersion::V5,
+        );
+        test_encode("unsuback MQTT 5", packet, buf, ProtocolVersion::V5);
     }
 
     #[test]
@@ -148,7 +178,7 @@ mod tests {
                 0,    // properties length
                 0x84, // reason codes
             ],
-            5,
+            ProtocolVersion::V5,
         );
     }
 }
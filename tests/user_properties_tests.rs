@@ -0,0 +1,77 @@
+mod tests {
+    use mqtt_packet_3_5::byte_reader::*;
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::structure::*;
+    use std::collections::HashMap;
+    use std::io::{BufReader, Cursor};
+
+    fn dec_from_buf(v: Vec<u8>) -> PacketDecoder<Cursor<Vec<u8>>> {
+        let c = Cursor::new(v);
+        PacketDecoder::new(ByteReader::new(BufReader::new(c)))
+    }
+
+    #[test]
+    fn test_lookup_helpers() {
+        let mut props = UserProperties::new();
+        props.push("a", "1");
+        props.push("b", "2");
+        props.push("a", "3");
+        assert_eq!(3, props.len());
+        assert_eq!(Some("1"), props.get("a"));
+        assert_eq!(vec!["1", "3"], props.get_all("a").collect::<Vec<&str>>());
+        assert!(props.contains_key("b"));
+        assert!(!props.contains_key("c"));
+        assert_eq!(vec!["1".to_string(), "3".to_string()], props.remove("a"));
+        assert_eq!(&[("b".to_string(), "2".to_string())], props.as_slice());
+    }
+
+    #[test]
+    fn test_from_map_is_sorted() {
+        let mut map = HashMap::new();
+        map.insert("z".to_string(), vec!["1".to_string()]);
+        map.insert("a".to_string(), vec!["2".to_string(), "3".to_string()]);
+        map.insert("m".to_string(), vec!["4".to_string()]);
+        let props = UserProperties::from(map);
+        assert_eq!(
+            vec![
+                ("a".to_string(), "2".to_string()),
+                ("a".to_string(), "3".to_string()),
+                ("m".to_string(), "4".to_string()),
+                ("z".to_string(), "1".to_string()),
+            ],
+            props.into_vec()
+        );
+    }
+
+    #[test]
+    fn test_interleaved_keys_keep_order() {
+        let user_properties = vec![
+            ("b".to_string(), "1".to_string()),
+            ("a".to_string(), "2".to_string()),
+            ("b".to_string(), "3".to_string()),
+        ]
+        .into_iter()
+        .collect::<UserProperties>();
+        let packet = MqttPacket::Unsubscribe(UnsubscribePacket {
+            qos: QoS::QoS1,
+            message_id: 7,
+            properties: Some(UnsubscribeProperties { user_properties }),
+            unsubscriptions: vec!["t".to_string()],
+        });
+        let buf = vec![
+            162, 27, // Header
+            0, 7,  // Message ID
+            21, // properties length
+            38, 0, 1, 98, 0, 1, 49, // userProperties (b = 1)
+            38, 0, 1, 97, 0, 1, 50, // userProperties (a = 2)
+            38, 0, 1, 98, 0, 1, 51, // userProperties (b = 3)
+            0, 1, 116, // Topic (t)
+        ];
+        assert_eq!(buf, packet.clone().encode(ProtocolVersion::V5).unwrap());
+        let mut decoder = dec_from_buf(buf.clone());
+        let decoded = decoder.decode_packet(ProtocolVersion::V5).unwrap();
+        assert_eq!(packet, decoded);
+        // encoding again yields exactly the same bytes
+        assert_eq!(buf, decoded.encode(ProtocolVersion::V5).unwrap());
+    }
+}
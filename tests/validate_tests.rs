@@ -0,0 +1,235 @@
+mod tests {
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::structure::*;
+    use mqtt_packet_3_5::validate::*;
+
+    fn ids(packet: &MqttPacket, version: ProtocolVersion) -> Vec<(&'static str, String)> {
+        validate(packet, version)
+            .into_iter()
+            .map(|v| (v.id, v.path))
+            .collect()
+    }
+
+    fn connect(client_id: &str, version: ProtocolVersion) -> ConnectPacket {
+        ConnectPacket {
+            protocol_version: version,
+            keep_alive: 30,
+            clean_session: true,
+            user_name: None,
+            password: None,
+            will: None,
+            client_id: client_id.to_string(),
+            properties: None,
+        }
+    }
+
+    fn subscription(topic: &str, nl: bool) -> Subscription {
+        Subscription {
+            topic: topic.to_string(),
+            qos: QoS::QoS1,
+            nl,
+            rap: false,
+            rh: RetainHandling::SendAtSubscribe,
+        }
+    }
+
+    #[test]
+    fn test_valid_packets() {
+        let packet = MqttPacket::Connect(connect("client1", ProtocolVersion::V5));
+        assert_eq!(
+            Vec::<Violation>::new(),
+            validate(&packet, ProtocolVersion::V5)
+        );
+        let packet = MqttPacket::Subscribe(SubscribePacket {
+            qos: QoS::QoS1,
+            message_id: 1,
+            properties: None,
+            subscriptions: vec![subscription("a/+/b/#", false), subscription("#", true)],
+        });
+        assert_eq!(
+            Vec::<Violation>::new(),
+            validate(&packet, ProtocolVersion::V5)
+        );
+        assert!(validate(&MqttPacket::Pingreq, ProtocolVersion::V311).is_empty());
+    }
+
+    #[test]
+    fn test_client_id() {
+        // servers may allow more than 23 alphanumeric characters
+        let packet = MqttPacket::Connect(connect("my-client", ProtocolVersion::V5));
+        assert!(ids(&packet, ProtocolVersion::V5).is_empty());
+        let packet = MqttPacket::Connect(connect(&"x".repeat(24), ProtocolVersion::V311));
+        assert!(ids(&packet, ProtocolVersion::V311).is_empty());
+        let mut empty = connect("", ProtocolVersion::V311);
+        empty.clean_session = false;
+        assert_eq!(
+            vec![("MQTT-3.1.3-7", "clean_session".to_string())],
+            ids(&MqttPacket::Connect(empty), ProtocolVersion::V311)
+        );
+        let packet = MqttPacket::Connect(connect("a\u{0}b", ProtocolVersion::V5));
+        assert_eq!(
+            vec![("MQTT-1.5.4-2", "client_id".to_string())],
+            ids(&packet, ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_will_and_password() {
+        let mut packet = connect("client", ProtocolVersion::V311);
+        packet.password = Some("secret".to_string());
+        packet.will = Some(LastWill {
+            topic: None,
+            payload: None,
+            qos: QoS::QoS1,
+            retain: false,
+            properties: None,
+        });
+        assert_eq!(
+            vec![
+                ("MQTT-3.1.2-22", "password".to_string()),
+                ("MQTT-3.1.2-9", "will.topic".to_string())
+            ],
+            ids(&MqttPacket::Connect(packet.clone()), ProtocolVersion::V311)
+        );
+        // MQTT 5 allows a password without user name
+        packet.will.as_mut().unwrap().topic = Some("a/+".to_string());
+        assert_eq!(
+            vec![("MQTT-4.7.0-1", "will.topic".to_string())],
+            ids(&MqttPacket::Connect(packet), ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_publish() {
+        let packet = MqttPacket::Publish(PublishPacket {
+            dup: false,
+            qos: PublishQoS::QoS1(0),
+            retain: false,
+            topic: "a/#".to_string(),
+            payload: vec![],
+            properties: Some(PublishProperties {
+                topic_alias: Some(0),
+                subscription_identifiers: vec![0, 5],
+                user_properties: UserProperties::from(vec![("k".to_string(), "\u{0}".to_string())]),
+                ..PublishProperties::default()
+            }),
+        });
+        assert_eq!(
+            vec![
+                ("MQTT-2.2.1-3", "qos".to_string()),
+                ("MQTT-3.3.2-2", "topic".to_string()),
+                ("MQTT-3.3.2-8", "properties.topic_alias".to_string()),
+                (
+                    "3.3.2.3.8",
+                    "properties.subscription_identifiers[0]".to_string()
+                ),
+                (
+                    "MQTT-1.5.4-2",
+                    "properties.user_properties[0].value".to_string()
+                ),
+            ],
+            ids(&packet, ProtocolVersion::V5)
+        );
+    }
+
+    #[test]
+    fn test_publish_empty_topic() {
+        let mut publish = PublishPacket {
+            dup: true,
+            qos: PublishQoS::QoS0,
+            retain: false,
+            topic: "".to_string(),
+            payload: vec![],
+            properties: None,
+        };
+        assert_eq!(
+            vec![
+                ("MQTT-3.3.1-2", "dup".to_string()),
+                ("MQTT-4.7.3-1", "topic".to_string())
+            ],
+            ids(&MqttPacket::Publish(publish.clone()), ProtocolVersion::V5)
+        );
+        // a topic alias replaces the topic name in MQTT 5
+        publish.dup = false;
+        publish.properties = Some(PublishProperties {
+            topic_alias: Some(1),
+            ..PublishProperties::default()
+        });
+        assert!(validate(&MqttPacket::Publish(publish), ProtocolVersion::V5).is_empty());
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let packet = MqttPacket::Subscribe(SubscribePacket {
+            qos: QoS::QoS1,
+            message_id: 0,
+            properties: Some(SubscribeProperties {
+                subscription_identifier: Some(VARBYTEINT_MAX + 1),
+                ..SubscribeProperties::default()
+            }),
+            subscriptions: vec![
+                subscription("$share/group/a/b", true),
+                subscription("a/b#", false),
+                subscription("a+/b", false),
+            ],
+        });
+        let expected = vec![
+            ("MQTT-2.2.1-3", "message_id".to_string()),
+            ("MQTT-3.8.3-4", "subscriptions[0].nl".to_string()),
+            ("MQTT-4.7.1-1", "subscriptions[1].topic".to_string()),
+            ("MQTT-4.7.1-2", "subscriptions[2].topic".to_string()),
+            (
+                "3.8.2.1.2",
+                "properties.subscription_identifier".to_string(),
+            ),
+        ];
+        assert_eq!(expected, ids(&packet, ProtocolVersion::V5));
+        let empty = MqttPacket::Subscribe(SubscribePacket {
+            qos: QoS::QoS1,
+            message_id: 1,
+            properties: None,
+            subscriptions: vec![],
+        });
+        assert_eq!(
+            vec![("MQTT-3.8.3-3", "subscriptions".to_string())],
+            ids(&empty, ProtocolVersion::V311)
+        );
+    }
+
+    #[test]
+    fn test_connack_session_present() {
+        let packet = MqttPacket::Connack(ConnackPacket {
+            code: 0x87,
+            session_present: true,
+            properties: None,
+        });
+        let violations = validate(&packet, ProtocolVersion::V311);
+        assert_eq!(1, violations.len());
+        assert_eq!(
+            "[MQTT-3.2.2-4] session_present: session present must be 0 if the connection is refused",
+            violations[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_authentication_data() {
+        let auth = |data: &str| AuthProperties {
+            authentication_method: "SCRAM\0".to_string(),
+            authentication_data: Some(data.to_string()),
+            reason_string: None,
+            user_properties: UserProperties::default(),
+        };
+        // binary data is not checked as a string
+        let packet = MqttPacket::Auth(AuthPacket {
+            reason_code: AuthCode::ContinueAuthentication,
+            properties: Some(auth("\0\u{1}")),
+        });
+        assert_eq!(
+            vec![(
+                "MQTT-1.5.4-2",
+                "properties.authentication_method".to_string()
+            )],
+            ids(&packet, ProtocolVersion::V5)
+        );
+    }
+}
@@ -0,0 +1,128 @@
+mod common;
+
+mod tests {
+    use crate::common::{connect, disconnect};
+    use mqtt_packet_3_5::clock::*;
+    use mqtt_packet_3_5::packet::*;
+    use mqtt_packet_3_5::will::*;
+    use std::time::Duration;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_publish() {
+        let packet = connect(
+            "CONNECT version=5 will{qos=1 retain topic=w payload=bye props{payload_format=true \
+             expiry=60 content_type=text response_topic=r correlation=0x01 will_delay=5 user{k=v}}}",
+        );
+        assert_eq!(
+            Ok(MqttPacket::Publish(publish(packet.will.as_ref().unwrap(), 3).unwrap())),
+            "PUBLISH qos=1 id=3 retain topic=w props{payload_format=true expiry=60 content_type=text \
+             response_topic=r correlation=0x01 user{k=v}} payload=bye"
+                .parse()
+        );
+        let packet = connect("CONNECT version=4 will{topic=w}");
+        assert_eq!(
+            Ok(MqttPacket::Publish(
+                publish(packet.will.as_ref().unwrap(), 3).unwrap()
+            )),
+            "PUBLISH topic=w payload=\"\"".parse()
+        );
+        let packet = connect("CONNECT version=4 will{payload=p}");
+        assert!(publish(packet.will.as_ref().unwrap(), 1).is_err());
+    }
+
+    #[test]
+    fn test_delay() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut tracker = WillTracker::new(&connect(
+            "CONNECT version=5 will{topic=w props{will_delay=10}} props{session_expiry=60}",
+        ));
+        assert_eq!(None, tracker.deadline());
+        tracker.connection_closed(&clock);
+        assert_eq!(Some(start + secs(10)), tracker.deadline());
+        clock.advance(secs(9));
+        assert_eq!(None, tracker.poll(&clock));
+        clock.advance(secs(1));
+        assert_eq!(Some("w"), tracker.poll(&clock).unwrap().topic.as_deref());
+        // only once
+        clock.advance(secs(1));
+        assert_eq!(None, tracker.poll(&clock));
+        assert_eq!(None, tracker.deadline());
+    }
+
+    #[test]
+    fn test_session_end() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        // the session ends before the will delay
+        let mut tracker = WillTracker::new(&connect(
+            "CONNECT version=5 will{topic=w props{will_delay=10}} props{session_expiry=3}",
+        ));
+        tracker.connection_closed(&clock);
+        assert_eq!(Some(start + secs(3)), tracker.deadline());
+        clock.advance(secs(1));
+        tracker.session_ended(&clock);
+        assert!(tracker.poll(&clock).is_some());
+        // MQTT 3 sessions with clean session end with the connection
+        let mut tracker =
+            WillTracker::new(&connect("CONNECT version=4 clean_session will{topic=w}"));
+        tracker.connection_closed(&clock);
+        assert_eq!(Some(clock.now()), tracker.deadline());
+        let mut tracker = WillTracker::new(&connect(
+            "CONNECT version=5 will{topic=w props{will_delay=10}}",
+        ));
+        tracker.connection_closed(&clock);
+        assert_eq!(Some(clock.now()), tracker.deadline());
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let text = "CONNECT version=5 will{topic=w props{will_delay=10}} props{session_expiry=60}";
+        let mut tracker = WillTracker::new(&connect(text));
+        tracker.disconnect(&disconnect("DISCONNECT reason=NormalDisconnection"), &clock);
+        assert_eq!(None, tracker.will());
+        tracker.connection_closed(&clock);
+        clock.advance(secs(60));
+        assert_eq!(None, tracker.poll(&clock));
+
+        let mut tracker = WillTracker::new(&connect("CONNECT version=4 will{topic=w}"));
+        tracker.disconnect(&disconnect("DISCONNECT"), &clock);
+        assert_eq!(None, tracker.will());
+
+        let clock = ManualClock::starting_at(start);
+        let mut tracker = WillTracker::new(&connect(text));
+        tracker.disconnect(
+            &disconnect("DISCONNECT reason=DisconnectWithWillMessage props{session_expiry=2}"),
+            &clock,
+        );
+        assert_eq!(Some(start + secs(2)), tracker.deadline());
+        clock.advance(secs(2));
+        assert!(tracker.poll(&clock).is_some());
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let clock = ManualClock::new();
+        let text = "CONNECT version=5 will{topic=w props{will_delay=10}} props{session_expiry=60}";
+        let mut tracker = WillTracker::new(&connect(text));
+        tracker.connection_closed(&clock);
+        clock.advance(secs(5));
+        tracker.reconnected(false, &clock);
+        assert_eq!(None, tracker.deadline());
+        clock.advance(secs(5));
+        assert_eq!(None, tracker.poll(&clock));
+        // a clean start ends the previous session, so the will is sent at once
+        let clock = ManualClock::new();
+        let mut tracker = WillTracker::new(&connect(text));
+        tracker.connection_closed(&clock);
+        clock.advance(secs(5));
+        tracker.reconnected(true, &clock);
+        assert!(tracker.poll(&clock).is_some());
+    }
+}